@@ -0,0 +1,59 @@
+use std::{env, fs};
+use std::fmt::Write;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+const EMBED_DIR_VAR: &str = "LUCENT_EMBED_DIR";
+const SOURCE_DATE_EPOCH_VAR: &str = "SOURCE_DATE_EPOCH";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed={}", EMBED_DIR_VAR);
+    println!("cargo:rerun-if-env-changed={}", SOURCE_DATE_EPOCH_VAR);
+
+    let mut files = vec![];
+    if let Ok(dir) = env::var(EMBED_DIR_VAR) {
+        let dir = fs::canonicalize(&dir).unwrap_or_else(|_| panic!("{} `{}` does not exist", EMBED_DIR_VAR, dir));
+        collect_files(&dir, &dir, &mut files);
+    }
+    files.sort();
+
+    let modified = match env::var(SOURCE_DATE_EPOCH_VAR).ok().and_then(|epoch| epoch.trim().parse::<u64>().ok()) {
+        Some(epoch) => epoch,
+        _ => files.iter().map(|(_, path)| modified_secs(Path::new(path))).max().unwrap_or(0),
+    };
+    let mut output = format!("pub const EMBEDDED_MODIFIED: u64 = {};\n\n", modified);
+    writeln!(output, "pub static EMBEDDED_PATHS: &[&str] = &{:?};\n", files.iter().map(|f| &f.0).collect::<Vec<_>>())
+        .unwrap();
+
+    if files.is_empty() {
+        output.push_str("pub fn embedded_file(_: &str) -> Option<&'static [u8]> {\n    None\n}\n");
+    } else {
+        output.push_str("pub fn embedded_file(path: &str) -> Option<&'static [u8]> {\n    match path {\n");
+        for (name, path) in &files {
+            writeln!(output, "        {:?} => Some(include_bytes!({:?})),", name, path).unwrap();
+        }
+        output.push_str("        _ => None,\n    }\n}\n");
+    }
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("embedded_files.rs"), output).unwrap();
+}
+
+fn modified_secs(path: &Path) -> u64 {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).unwrap_or(UNIX_EPOCH);
+    modified.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0)
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, String)>) {
+    println!("cargo:rerun-if-changed={}", dir.display());
+    for entry in fs::read_dir(dir).unwrap().filter_map(Result::ok) {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(root, &path, files);
+        } else if let (Ok(name), Some(full_path)) = (path.strip_prefix(root), path.to_str()) {
+            println!("cargo:rerun-if-changed={}", full_path);
+            let name = name.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+            files.push((name, full_path.to_string()));
+        }
+    }
+}
@@ -7,31 +7,54 @@ pub const SERVER_NAME_VERSION: &str = "Lucent/1.0.0";
 
 pub const MAX_URI_LENGTH: usize = 8_192;
 pub const MAX_HEADER_LENGTH: usize = 8_192;
+pub const MAX_HEADER_COUNT: usize = 100;
+pub const MAX_HEADERS_LENGTH: usize = 65_536;
 pub const MAX_GET_BODY_LENGTH: usize = 4 << 20;
 pub const MAX_OTHER_BODY_LENGTH: usize = 512 << 20;
 pub const MAX_READ_TIMEOUT: Duration = Duration::from_secs(10);
 pub const MAX_WRITE_TIMEOUT: Duration = Duration::from_secs(20);
+pub const MAX_HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+pub const WORKER_COUNT: usize = 1_024;
+pub const WORKER_QUEUE_SIZE: usize = 1_024;
 
 pub const MAX_BODY_BEFORE_CHUNK: usize = 8_192;
 pub const CHUNK_SIZE: usize = 4_096;
 pub const READ_CHUNK_SIZE: usize = 65_536;
+pub const CGI_OUTPUT_BUFFER_CHUNKS: usize = 4;
+pub const MAX_CGI_LOCAL_REDIRECTS: usize = 10;
+pub const DIGEST_NONCE_LIFETIME: Duration = Duration::from_secs(300);
+pub const DIGEST_NONCE_CACHE_SIZE: usize = 4_096;
+pub const AUTH_FAILURE_CACHE_SIZE: usize = 4_096;
+pub const MEDIA_TYPE_SNIFF_LENGTH: usize = 512;
 pub const DIR_LISTING_VIEWABLE: &str = ".viewable";
+pub const DIR_LISTING_FORMAT_PARAM: &str = "format";
+pub const DOWNLOAD_PARAM: &str = "download";
+pub const ACME_CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
 
 pub const TEMPLATE_ERROR: &str = "error.html";
 pub const TEMPLATE_DIR_LISTING: &str = "dir_listing.html";
 
+pub const TIMING_PARSE: &str = "parse";
+pub const TIMING_MIDDLEWARE: &str = "middleware";
+pub const TIMING_FS: &str = "fs";
+
 pub const H_ACCEPT: &str = "accept";
 pub const H_ACCEPT_CHARSET: &str = "accept-charset";
 pub const H_ACCEPT_ENCODING: &str = "accept-encoding";
 pub const H_ACCEPT_LANGUAGE: &str = "accept-language";
 pub const H_CACHE_CONTROL: &str = "cache-control";
 pub const H_TE: &str = "te";
+pub const H_TRAILER: &str = "trailer";
 pub const H_TRANSFER_ENCODING: &str = "transfer-encoding";
 pub const H_UPGRADE: &str = "upgrade";
 pub const H_VIA: &str = "via";
+pub const H_X_FORWARDED_FOR: &str = "x-forwarded-for";
+pub const H_X_FORWARDED_PROTO: &str = "x-forwarded-proto";
 pub const H_CONTENT_LENGTH: &str = "content-length";
 pub const H_CONTENT_TYPE: &str = "content-type";
+pub const H_CONTENT_ENCODING: &str = "content-encoding";
 pub const H_CONTENT_RANGE: &str = "content-range";
+pub const H_CONTENT_LANGUAGE: &str = "content-language";
 pub const H_HOST: &str = "host";
 pub const H_SERVER: &str = "server";
 pub const H_DATE: &str = "date";
@@ -45,8 +68,31 @@ pub const H_IF_MODIFIED_SINCE: &str = "if-modified-since";
 pub const H_IF_UNMODIFIED_SINCE: &str = "if-unmodified-since";
 pub const H_IF_RANGE: &str = "if-range";
 pub const H_RANGE: &str = "range";
+pub const H_ACCEPT_RANGES: &str = "accept-ranges";
 pub const H_AUTHORIZATION: &str = "authorization";
+pub const H_PROXY_AUTHORIZATION: &str = "proxy-authorization";
+pub const H_PROXY: &str = "proxy";
+pub const H_COOKIE: &str = "cookie";
+pub const H_SET_COOKIE: &str = "set-cookie";
 pub const H_WWW_AUTHENTICATE: &str = "www-authenticate";
+pub const H_VARY: &str = "vary";
+pub const H_LOCATION: &str = "location";
+pub const H_EXPIRES: &str = "expires";
+pub const H_SERVER_TIMING: &str = "server-timing";
+pub const H_SEC_WEBSOCKET_KEY: &str = "sec-websocket-key";
+pub const H_SEC_WEBSOCKET_ACCEPT: &str = "sec-websocket-accept";
+pub const H_SEC_WEBSOCKET_VERSION: &str = "sec-websocket-version";
+pub const H_X_CONTENT_TYPE_OPTIONS: &str = "x-content-type-options";
+pub const H_X_FRAME_OPTIONS: &str = "x-frame-options";
+pub const H_CONTENT_SECURITY_POLICY: &str = "content-security-policy";
+pub const H_REFERRER_POLICY: &str = "referrer-policy";
+pub const H_STRICT_TRANSPORT_SECURITY: &str = "strict-transport-security";
+pub const H_CGI_STATUS: &str = "status";
+pub const H_CONTENT_DISPOSITION: &str = "content-disposition";
+pub const H_ALLOW: &str = "allow";
+pub const H_DAV: &str = "dav";
+pub const H_DEPTH: &str = "depth";
+pub const H_MS_AUTHOR_VIA: &str = "ms-author-via";
 
 pub const H_T_ENC_CHUNKED: &str = "chunked";
 pub const _H_T_ENC_COMPRESS: &str = "compress";
@@ -54,52 +100,136 @@ pub const _H_T_ENC_IDENTITY: &str = "identity";
 pub const _H_T_ENC_DEFLATE: &str = "deflate";
 pub const _H_T_ENC_GZIP: &str = "gzip";
 
+pub const H_C_ENC_GZIP: &str = "gzip";
+pub const H_C_ENC_BROTLI: &str = "br";
+
 pub const H_CONN_KEEP_ALIVE: &str = "keep-alive";
 pub const H_CONN_CLOSE: &str = "close";
+pub const H_CONN_UPGRADE: &str = "upgrade";
+
+pub const H_UPGRADE_WEBSOCKET: &str = "websocket";
+
+pub const H_DAV_COMPLIANCE: &str = "1";
+pub const H_DEPTH_ZERO: &str = "0";
+pub const H_DEPTH_ONE: &str = "1";
 
 pub const H_EXPECT_CONTINUE: &str = "100-continue";
 
+pub const H_CACHE_PRIVATE: &str = "private";
+pub const H_CACHE_NO_STORE: &str = "no-store";
+pub const H_CACHE_NO_CACHE: &str = "no-cache";
+pub const H_CACHE_PUBLIC: &str = "public";
+pub const H_CACHE_MAX_AGE: &str = "max-age";
+pub const H_CACHE_IMMUTABLE: &str = "immutable";
+
+pub const SECURITY_HEADERS: &[(&str, &str)] = &[
+    (H_X_CONTENT_TYPE_OPTIONS, "nosniff"),
+    (H_X_FRAME_OPTIONS, "DENY"),
+    (H_CONTENT_SECURITY_POLICY, "default-src 'self'"),
+    (H_REFERRER_POLICY, "strict-origin-when-cross-origin"),
+];
+
+pub const CHARSET_UTF_8: &str = "utf-8";
+pub const CHARSET_UTF_16_BE: &str = "utf-16be";
+pub const CHARSET_UTF_16_LE: &str = "utf-16le";
+
+pub const BOM_UTF_8: &[u8] = b"\xef\xbb\xbf";
+pub const BOM_UTF_16_BE: &[u8] = b"\xfe\xff";
+pub const BOM_UTF_16_LE: &[u8] = b"\xff\xfe";
+
 pub const H_RANGE_UNIT_BYTES: &str = "bytes";
+pub const H_RANGE_UNIT_NONE: &str = "none";
 
 pub const H_AUTH_REALM: &str = "realm";
 pub const H_AUTH_BASIC: &str = "basic";
+pub const H_AUTH_DIGEST: &str = "digest";
+pub const H_AUTH_BEARER: &str = "bearer";
+pub const H_AUTH_ERROR: &str = "error";
+pub const H_AUTH_INVALID_TOKEN: &str = "invalid_token";
+pub const H_AUTH_NONCE: &str = "nonce";
+pub const H_AUTH_QOP: &str = "qop";
+pub const H_AUTH_QOP_AUTH: &str = "auth";
+pub const H_AUTH_ALGORITHM: &str = "algorithm";
+pub const H_AUTH_STALE: &str = "stale";
 
+pub const H_MEDIA_3GP: &str = "video/3gpp";
+pub const H_MEDIA_7Z: &str = "application/x-7z-compressed";
 pub const H_MEDIA_AAC: &str = "audio/aac";
+pub const H_MEDIA_APNG: &str = "image/apng";
+pub const H_MEDIA_ATOM: &str = "application/atom+xml";
 pub const H_MEDIA_AVI: &str = "video/x-msvideo";
+pub const H_MEDIA_AVIF: &str = "image/avif";
 pub const H_MEDIA_BINARY: &str = "application/octet-stream";
 pub const H_MEDIA_BITMAP: &str = "image/bmp";
+pub const H_MEDIA_BZIP2: &str = "application/x-bzip2";
+pub const H_MEDIA_CALENDAR: &str = "text/calendar";
 pub const H_MEDIA_CGI_SCRIPT: &str = "application/x-httpd-cgi";
 pub const H_MEDIA_CSS: &str = "text/css";
 pub const H_MEDIA_CSV: &str = "text/csv";
+pub const H_MEDIA_DOC: &str = "application/msword";
+pub const H_MEDIA_DOCX: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+pub const H_MEDIA_EOT: &str = "application/vnd.ms-fontobject";
 pub const H_MEDIA_EPUB: &str = "application/epub+zip";
-pub const H_MEDIA_GZIP: &str = "application/gzip";
+pub const H_MEDIA_FLAC: &str = "audio/flac";
 pub const H_MEDIA_GIF: &str = "image/gif";
+pub const H_MEDIA_GZIP: &str = "application/gzip";
+pub const H_MEDIA_HEIC: &str = "image/heic";
 pub const H_MEDIA_HTML: &str = "text/html";
-pub const _H_MEDIA_HTTP: &str = "message/http";
+pub const H_MEDIA_HTTP: &str = "message/http";
 pub const H_MEDIA_ICON: &str = "image/vnd.microsoft.icon";
-pub const H_MEDIA_JPEG: &str = "image/jpeg";
+pub const H_MEDIA_JAR: &str = "application/java-archive";
 pub const H_MEDIA_JAVASCRIPT: &str = "text/javascript";
+pub const H_MEDIA_JPEG: &str = "image/jpeg";
 pub const H_MEDIA_JSON: &str = "application/json";
+pub const H_MEDIA_JSON_LD: &str = "application/ld+json";
+pub const H_MEDIA_M4A: &str = "audio/mp4";
+pub const H_MEDIA_MANIFEST: &str = "application/manifest+json";
+pub const H_MEDIA_MARKDOWN: &str = "text/markdown";
+pub const H_MEDIA_MIDI: &str = "audio/midi";
+pub const H_MEDIA_MKV: &str = "video/x-matroska";
 pub const H_MEDIA_MP3: &str = "audio/mpeg";
 pub const H_MEDIA_MP4: &str = "video/mp4";
+pub const H_MEDIA_MPEG: &str = "video/mpeg";
 pub const H_MEDIA_MULTIPART_RANGE: &str = "multipart/byteranges";
+pub const H_MEDIA_ODS: &str = "application/vnd.oasis.opendocument.spreadsheet";
+pub const H_MEDIA_ODT: &str = "application/vnd.oasis.opendocument.text";
+pub const H_MEDIA_OGG: &str = "application/ogg";
 pub const H_MEDIA_OGG_AUDIO: &str = "audio/ogg";
-pub const H_MEDIA_PNG: &str = "image/png";
+pub const H_MEDIA_OGG_VIDEO: &str = "video/ogg";
+pub const H_MEDIA_OPUS: &str = "audio/opus";
+pub const H_MEDIA_OTF: &str = "font/otf";
 pub const H_MEDIA_PDF: &str = "application/pdf";
 pub const H_MEDIA_PHP: &str = "application/php";
+pub const H_MEDIA_PNG: &str = "image/png";
+pub const H_MEDIA_PPT: &str = "application/vnd.ms-powerpoint";
+pub const H_MEDIA_PPTX: &str = "application/vnd.openxmlformats-officedocument.presentationml.presentation";
+pub const H_MEDIA_QUICKTIME: &str = "video/quicktime";
+pub const H_MEDIA_RAR: &str = "application/vnd.rar";
+pub const H_MEDIA_RSS: &str = "application/rss+xml";
 pub const H_MEDIA_RTF: &str = "application/rtf";
+pub const H_MEDIA_SH: &str = "application/x-sh";
 pub const H_MEDIA_SVG: &str = "image/svg+xml";
 pub const H_MEDIA_SWF: &str = "application/x-shockwave-flash";
-pub const H_MEDIA_TTF: &str = "font/ttf";
+pub const H_MEDIA_TAR: &str = "application/x-tar";
 pub const H_MEDIA_TEXT: &str = "text/plain";
+pub const H_MEDIA_TIFF: &str = "image/tiff";
+pub const H_MEDIA_TSV: &str = "text/tab-separated-values";
+pub const H_MEDIA_TTF: &str = "font/ttf";
+pub const H_MEDIA_VCARD: &str = "text/vcard";
+pub const H_MEDIA_WASM: &str = "application/wasm";
 pub const H_MEDIA_WAV: &str = "audio/wav";
 pub const H_MEDIA_WEBM_AUDIO: &str = "audio/webm";
 pub const H_MEDIA_WEBM_VIDEO: &str = "video/webm";
 pub const H_MEDIA_WEBP_IMAGE: &str = "image/webp";
+pub const H_MEDIA_WEBVTT: &str = "text/vtt";
 pub const H_MEDIA_WOFF: &str = "font/woff";
 pub const H_MEDIA_WOFF2: &str = "font/woff2";
 pub const H_MEDIA_XHTML: &str = "application/xhtml+xml";
+pub const H_MEDIA_XLS: &str = "application/vnd.ms-excel";
+pub const H_MEDIA_XLSX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
 pub const H_MEDIA_XML: &str = "application/xml";
+pub const H_MEDIA_XZ: &str = "application/x-xz";
+pub const H_MEDIA_YAML: &str = "application/yaml";
 pub const H_MEDIA_ZIP: &str = "application/zip";
 
 pub const CGI_VAR_AUTH_TYPE: &str = "AUTH_TYPE";
@@ -119,3 +249,5 @@ pub const CGI_VAR_SERVER_NAME: &str = "SERVER_NAME";
 pub const CGI_VAR_SERVER_PORT: &str = "SERVER_PORT";
 pub const CGI_VAR_SERVER_PROTOCOL: &str = "SERVER_PROTOCOL";
 pub const CGI_VAR_SERVER_SOFTWARE: &str = "SERVER_SOFTWARE";
+
+pub const CGI_GATEWAY_INTERFACE: &str = "CGI/1.1";
@@ -2,23 +2,38 @@ use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::fmt;
 
+use linked_hash_map::LinkedHashMap;
+
 use crate::consts;
 use crate::util;
 
 const MULTI_VALUE_HEADER_NAMES: &[&str] = &[
     consts::H_ACCEPT, consts::H_ACCEPT_CHARSET, consts::H_ACCEPT_ENCODING, consts::H_ACCEPT_LANGUAGE,
-    consts::H_CACHE_CONTROL, consts::H_TE, consts::H_TRANSFER_ENCODING, consts::H_UPGRADE, consts::H_VIA,
+    consts::H_CACHE_CONTROL, consts::H_CONNECTION, consts::H_TE, consts::H_TRAILER, consts::H_TRANSFER_ENCODING, consts::H_UPGRADE, consts::H_VIA,
+    consts::H_VARY, consts::H_X_FORWARDED_FOR, consts::H_ALLOW,
+];
+
+const REPEATED_HEADER_NAMES: &[&str] = &[consts::H_SET_COOKIE];
+
+const IRREGULAR_HEADER_NAMES: &[&str] = &[
+    "DAV", "DNT", "ETag", "MS-Author-Via", "Sec-WebSocket-Accept", "Sec-WebSocket-Extensions", "Sec-WebSocket-Key",
+    "Sec-WebSocket-Protocol", "Sec-WebSocket-Version", "TE", "WWW-Authenticate", "X-XSS-Protection",
 ];
 
-type HeaderMap = HashMap<String, Vec<String>>;
+type HeaderMap = LinkedHashMap<String, Vec<String>>;
 
 pub struct Headers {
     headers: HeaderMap,
+    casing: Vec<String>,
 }
 
 impl Headers {
+    pub fn new() -> Self {
+        Headers { headers: LinkedHashMap::new(), casing: vec![] }
+    }
+
     pub fn from(headers: HashMap<String, Vec<String>>) -> Self {
-        Headers { headers }
+        Headers { headers: headers.into_iter().collect(), casing: vec![] }
     }
 
     pub fn get(&self, name: &str) -> Option<&Vec<String>> {
@@ -30,14 +45,14 @@ impl Headers {
     }
 
     pub fn contains(&self, name: &str) -> bool {
-        matches!(self.get(name), Some(_))
+        self.get(name).is_some()
     }
 
     pub fn set_one(&mut self, name: &str, value: &str) -> bool {
         if !is_token_string(name) || !is_valid_header_value(&value) {
             false
         } else {
-            self.headers.insert(Self::normalize_header_name(name), vec![value.to_string()]);
+            self.insert(name, vec![value.to_string()]);
             true
         }
     }
@@ -46,14 +61,77 @@ impl Headers {
         if !is_token_string(name) || !values.iter().all(is_valid_header_value) {
             false
         } else {
-            let values = values.iter().map(|s| s.to_string()).collect();
-            self.headers.insert(Self::normalize_header_name(name), values);
+            self.insert(name, values.iter().map(|s| s.to_string()).collect());
             true
         }
     }
 
+    pub fn add_vary(&mut self, field: &str) {
+        let mut vary = self
+            .get(consts::H_VARY)
+            .into_iter()
+            .flatten()
+            .flat_map(|value| value.split(','))
+            .map(|field| field.trim_matches(consts::OPTIONAL_WHITESPACE))
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if field.is_empty() || vary.iter().any(|existing| existing == "*" || existing.eq_ignore_ascii_case(field)) {
+            return;
+        } else if field == "*" {
+            vary.clear();
+        }
+
+        vary.push(field.to_string());
+        self.set(consts::H_VARY, vary.iter().map(String::as_str).collect());
+    }
+
+    pub fn set_casing(&mut self, names: &[String]) {
+        self.casing = names.to_vec();
+    }
+
     pub fn remove(&mut self, name: &str) {
-        self.headers.remove(name);
+        self.headers.remove(&Self::normalize_header_name(name));
+    }
+
+    pub fn canonical_name(name: &str) -> String {
+        if let Some(irregular) = IRREGULAR_HEADER_NAMES.iter().find(|irregular| irregular.eq_ignore_ascii_case(name)) {
+            return irregular.to_string();
+        }
+        name.split('-')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase(),
+                    _ => String::new(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    fn insert(&mut self, name: &str, values: Vec<String>) {
+        let name = Self::normalize_header_name(name);
+        match self.headers.get_mut(&name) {
+            Some(existing) => *existing = values,
+            _ => {
+                self.headers.insert(name, values);
+            }
+        }
+    }
+
+    fn header_lines(&self, name: &str, values: &[String]) -> Vec<String> {
+        let name = match self.casing.iter().find(|cased| cased.eq_ignore_ascii_case(name)) {
+            Some(cased) => cased.clone(),
+            _ => Self::canonical_name(name),
+        };
+        if REPEATED_HEADER_NAMES.iter().any(|repeated| repeated.eq_ignore_ascii_case(&name)) {
+            values.iter().map(|value| format!("{}: {}", name, value)).collect()
+        } else if Self::is_multi_value(&name) {
+            vec![format!("{}: {}", name, values.join(", "))]
+        } else {
+            values.last().map(|value| format!("{}: {}", name, value)).into_iter().collect()
+        }
     }
 
     pub fn is_multi_value(name: &str) -> bool {
@@ -65,14 +143,20 @@ impl Headers {
     }
 }
 
+impl Default for Headers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Debug for Headers {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let headers_joined = self
             .headers
             .iter()
-            .map(|h| format!("{}: {}", h.0, h.1.join(", ")))
+            .flat_map(|(name, values)| self.header_lines(name, values))
             .collect::<Vec<_>>()
-            .join("\n");
+            .join("\r\n");
         write!(f, "{}", headers_joined)
     }
 }
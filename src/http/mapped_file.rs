@@ -0,0 +1,152 @@
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Once;
+use std::time::SystemTime;
+use std::{mem, ptr};
+
+use libc::{c_int, c_void, sigaction, siginfo_t};
+use memmap2::Mmap;
+
+use crate::util::Range;
+
+const MAX_GUARDED_MAPPINGS: usize = 1_024;
+
+struct GuardSlot {
+    start: AtomicUsize,
+    len: AtomicUsize,
+    faulted: AtomicBool,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: GuardSlot =
+    GuardSlot { start: AtomicUsize::new(0), len: AtomicUsize::new(0), faulted: AtomicBool::new(false) };
+
+static GUARD_SLOTS: [GuardSlot; MAX_GUARDED_MAPPINGS] = [EMPTY_SLOT; MAX_GUARDED_MAPPINGS];
+static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+static INSTALL_HANDLER: Once = Once::new();
+static mut PREVIOUS_ACTION: Option<sigaction> = None;
+
+struct Guard {
+    slot: &'static GuardSlot,
+}
+
+impl Guard {
+    fn register(start: usize, len: usize) -> Option<Self> {
+        INSTALL_HANDLER.call_once(install_handler);
+        let slot = GUARD_SLOTS.iter().find(|slot| {
+            slot.start.compare_exchange(0, usize::MAX, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+        })?;
+        slot.faulted.store(false, Ordering::SeqCst);
+        slot.len.store(len, Ordering::SeqCst);
+        slot.start.store(start, Ordering::SeqCst);
+        Some(Guard { slot })
+    }
+
+    fn faulted(&self) -> bool {
+        self.slot.faulted.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.slot.len.store(0, Ordering::SeqCst);
+        self.slot.start.store(0, Ordering::SeqCst);
+    }
+}
+
+pub struct MappedFile {
+    guard: Guard,
+    map: Mmap,
+    file: File,
+    modified: Option<SystemTime>,
+}
+
+impl MappedFile {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let modified = file.metadata()?.modified().ok();
+        let map = unsafe { Mmap::map(&file)? };
+        let guard = Guard::register(map.as_ptr() as usize, map.len())
+            .ok_or_else(|| io::Error::other("too many mapped files"))?;
+        Ok(MappedFile { guard, map, file, modified })
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn read(&self, range: &Range) -> io::Result<Vec<u8>> {
+        self.check()?;
+        let bytes = self.map[range.low..range.high].to_vec();
+        self.check()?;
+        Ok(bytes)
+    }
+
+    fn check(&self) -> io::Result<()> {
+        let metadata = self.file.metadata()?;
+        let changed = metadata.len() != self.map.len() as u64 || metadata.modified().ok() != self.modified;
+        if changed || self.guard.faulted() {
+            Err(io::Error::new(ErrorKind::InvalidData, "file changed while mapped"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn install_handler() {
+    unsafe {
+        PAGE_SIZE.store(libc::sysconf(libc::_SC_PAGESIZE) as usize, Ordering::SeqCst);
+        let mut action: sigaction = mem::zeroed();
+        action.sa_sigaction = handle_sigbus as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        let mut previous: sigaction = mem::zeroed();
+        if libc::sigaction(libc::SIGBUS, &action, &mut previous) == 0 {
+            PREVIOUS_ACTION = Some(previous);
+        }
+    }
+}
+
+extern "C" fn handle_sigbus(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+    let address = unsafe { (*info).si_addr() } as usize;
+    let page_size = PAGE_SIZE.load(Ordering::SeqCst);
+    for slot in GUARD_SLOTS.iter() {
+        let start = slot.start.load(Ordering::SeqCst);
+        let len = slot.len.load(Ordering::SeqCst);
+        if start == 0 || start == usize::MAX || address < start || address >= start + len {
+            continue;
+        }
+
+        let page = (address & !(page_size - 1)) as *mut c_void;
+        let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED;
+        if unsafe { libc::mmap(page, page_size, libc::PROT_READ, flags, -1, 0) } != libc::MAP_FAILED {
+            slot.faulted.store(true, Ordering::SeqCst);
+            return;
+        }
+    }
+
+    unsafe {
+        match ptr::addr_of!(PREVIOUS_ACTION).read() {
+            Some(previous) if previous.sa_flags & libc::SA_SIGINFO != 0 && previous.sa_sigaction > 1 => {
+                let handler = mem::transmute::<usize, extern "C" fn(c_int, *mut siginfo_t, *mut c_void)>(
+                    previous.sa_sigaction,
+                );
+                handler(signal, info, context);
+            }
+            Some(previous) => {
+                libc::sigaction(libc::SIGBUS, &previous, ptr::null_mut());
+            }
+            _ => {
+                let mut default: sigaction = mem::zeroed();
+                default.sa_sigaction = libc::SIG_DFL;
+                libc::sigaction(libc::SIGBUS, &default, ptr::null_mut());
+            }
+        }
+    }
+}
@@ -1,35 +1,47 @@
-use std::collections::HashMap;
+use std::time::Duration;
 
 use async_std::fs::File;
 use async_std::io;
-use async_std::io::prelude::{ReadExt, WriteExt};
-use async_std::io::Write;
+use async_std::io::prelude::{ReadExt, SeekExt, WriteExt};
+use async_std::io::{SeekFrom, Write};
 use async_std::task;
+use futures::channel::mpsc::Receiver;
+use futures::StreamExt;
 
 use crate::{consts, util};
 use crate::http::headers::Headers;
+use crate::http::mapped_file::MappedFile;
 use crate::http::request::{HttpVersion, Method, Request};
 use crate::http::response::{Response, Status};
 use crate::http::uri::Uri;
+use crate::util::Range;
 
 pub enum Body {
     Bytes(Vec<u8>),
-    Stream(File, usize),
+    File(File, Range),
+    Mapped(MappedFile, Range),
+    Stream(Receiver<io::Result<Vec<u8>>>),
 }
 
 impl Body {
     pub async fn len(&self) -> usize {
         match self {
             Body::Bytes(bytes) => bytes.len(),
-            Body::Stream(_, len) => *len,
+            Body::File(_, range) | Body::Mapped(_, range) => range.high - range.low,
+            Body::Stream(_) => 0,
         }
     }
+
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
 }
 
 pub trait Message {
     fn get_headers_mut(&mut self) -> &mut Headers;
     fn get_body_mut(&mut self) -> &mut Option<Body>;
     fn into_body(self) -> Option<Body>;
+    fn get_trailers(&self) -> &Headers;
     fn to_bytes_no_body(&self) -> Vec<u8>;
 
     fn is_chunked(&self) -> bool;
@@ -42,7 +54,7 @@ pub struct MessageBuilder<M: Message> {
 
 impl MessageBuilder<Request> {
     pub fn _new() -> Self {
-        let mut headers = Headers::from(HashMap::new());
+        let mut headers = Headers::new();
         headers.set_one(consts::H_CONTENT_LENGTH, "0");
 
         MessageBuilder {
@@ -52,7 +64,12 @@ impl MessageBuilder<Request> {
                 http_version: HttpVersion::Http11,
                 headers,
                 body: None,
+                trailers: Headers::new(),
                 chunked: false,
+                expect_continue: false,
+                id: None,
+                timing: None,
+                secure: false,
             }
         }
     }
@@ -78,7 +95,7 @@ impl MessageBuilder<Request> {
 
 impl MessageBuilder<Response> {
     pub fn new() -> Self {
-        let mut headers = Headers::from(HashMap::new());
+        let mut headers = Headers::new();
         headers.set_one(consts::H_CONTENT_LENGTH, "0");
         headers.set_one(consts::H_SERVER, consts::SERVER_NAME_VERSION);
         headers.set_one(consts::H_DATE, &util::format_time_imf(&util::get_time_utc()));
@@ -89,6 +106,7 @@ impl MessageBuilder<Response> {
                 status: Status::Ok,
                 headers,
                 body: None,
+                trailers: Headers::new(),
                 chunked: false,
             }
         }
@@ -96,7 +114,7 @@ impl MessageBuilder<Response> {
 
     pub fn set_status(&mut self, status: Status) {
         self.message.status = status;
-        if status == Status::NoContent || status < Status::Ok {
+        if status == Status::NoContent || status == Status::NotModified || status < Status::Ok {
             self.message.headers.remove(consts::H_CONTENT_LENGTH);
         }
     }
@@ -105,11 +123,42 @@ impl MessageBuilder<Response> {
         self.set_status(status);
         self
     }
+
+    pub fn set_upgrade(&mut self, protocol: &str) {
+        self.set_status(Status::SwitchingProtocols);
+        self.set_header(consts::H_UPGRADE, protocol);
+        self.set_header(consts::H_CONNECTION, consts::H_CONN_UPGRADE);
+    }
+
+    pub fn with_upgrade(mut self, protocol: &str) -> Self {
+        self.set_upgrade(protocol);
+        self
+    }
+
+    pub fn set_trailer(&mut self, name: &str, value: &str) {
+        let mut names = self.message.headers.get(consts::H_TRAILER).cloned().unwrap_or_default();
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(Headers::canonical_name(name));
+        }
+        self.set_header_multi(consts::H_TRAILER, names.iter().map(|n| n.as_str()).collect());
+        self.message.trailers.set_one(name, value);
+    }
+
+    pub fn with_trailer(mut self, name: &str, value: &str) -> Self {
+        self.set_trailer(name, value);
+        self
+    }
+}
+
+impl Default for MessageBuilder<Response> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<M: Message> MessageBuilder<M> {
     pub fn set_header(&mut self, name: &str, value: &str) {
-        self.message.get_headers_mut().set_one(&name, value);
+        self.message.get_headers_mut().set_one(name, value);
     }
 
     pub fn with_header(mut self, name: &str, value: &str) -> Self {
@@ -121,13 +170,27 @@ impl<M: Message> MessageBuilder<M> {
         self.message.get_headers_mut().remove(name);
     }
 
+    pub fn add_vary(&mut self, field: &str) {
+        self.message.get_headers_mut().add_vary(field);
+    }
+
+    pub fn with_vary(mut self, field: &str) -> Self {
+        self.add_vary(field);
+        self
+    }
+
+    pub fn with_header_casing(mut self, names: &[String]) -> Self {
+        self.message.get_headers_mut().set_casing(names);
+        self
+    }
+
     pub fn without_header(mut self, name: &str) -> Self {
         self.unset_header(name);
         self
     }
 
     pub fn set_header_multi(&mut self, name: &str, value: Vec<&str>) {
-        self.message.get_headers_mut().set(&name, value);
+        self.message.get_headers_mut().set(name, value);
     }
 
     pub fn with_header_multi(mut self, name: &str, value: Vec<&str>) -> Self {
@@ -137,65 +200,153 @@ impl<M: Message> MessageBuilder<M> {
 
     pub fn with_body(mut self, body: Body, media_type: &str) -> Self {
         self.set_header(consts::H_CONTENT_LENGTH, &task::block_on(body.len()).to_string());
-        if let Body::Bytes(bytes) = &body {
-            if bytes.len() > consts::MAX_BODY_BEFORE_CHUNK {
-                self.message.set_chunked();
-                self = self
-                    .with_header(consts::H_TRANSFER_ENCODING, consts::H_T_ENC_CHUNKED)
-                    .without_header(consts::H_CONTENT_LENGTH);
-            }
+        let chunked = match &body {
+            Body::Bytes(bytes) => bytes.len() > consts::MAX_BODY_BEFORE_CHUNK,
+            Body::Stream(_) => true,
+            _ => false,
+        };
+        if chunked {
+            self.message.set_chunked();
+            self = self
+                .with_header(consts::H_TRANSFER_ENCODING, consts::H_T_ENC_CHUNKED)
+                .without_header(consts::H_CONTENT_LENGTH);
         }
 
         *self.message.get_body_mut() = Some(body);
         self.with_header(consts::H_CONTENT_TYPE, media_type)
     }
 
+    pub fn with_file_body(mut self, file: File, range: Range, media_type: &str) -> Self {
+        self.set_header(consts::H_CONTENT_LENGTH, &(range.high - range.low).to_string());
+        *self.message.get_body_mut() = Some(Body::File(file, range));
+        self.with_header(consts::H_CONTENT_TYPE, media_type)
+    }
+
     pub fn build(self) -> M {
         self.message
     }
 }
 
-pub async fn send(writer: &mut (impl Write + Unpin), message: impl Message) -> io::Result<()> {
-    io::timeout(consts::MAX_WRITE_TIMEOUT, async {
-        writer.write_all(&message.to_bytes_no_body()).await?;
-        writer.flush().await?;
-
-        let chunked = message.is_chunked();
-        if let Some(body) = message.into_body() {
-            match body {
-                Body::Stream(file, len) => with_file(len, file, |c| task::block_on(writer.write_all(&c))).await?,
-                Body::Bytes(bytes) => {
-                    if chunked {
-                        for chunk in bytes.chunks(consts::CHUNK_SIZE) {
-                            write_chunk(writer, chunk).await?;
-                        }
-                        writer.write_all(b"0\r\n\r\n").await?;
-                    } else {
-                        writer.write_all(&bytes).await?;
-                    }
-                }
+pub async fn send(
+    writer: &mut (impl Write + Unpin),
+    mut message: impl Message,
+    chunk_size: usize,
+    timeout: Duration,
+) -> io::Result<()> {
+    if !message.is_chunked() {
+        message.get_headers_mut().remove(consts::H_TRAILER);
+    }
+    write_timeout(writer, &message.to_bytes_no_body(), timeout).await?;
+    io::timeout(timeout, writer.flush()).await?;
+
+    let last_chunk = if message.is_chunked() { Some(last_chunk_bytes(message.get_trailers())) } else { None };
+    match message.into_body() {
+        Some(Body::File(file, range)) => send_file(writer, file, range, chunk_size, timeout).await?,
+        Some(Body::Mapped(file, range)) => send_mapped(writer, file, range, chunk_size, timeout).await?,
+        Some(Body::Stream(stream)) => send_stream(writer, stream, last_chunk.as_deref(), timeout).await?,
+        Some(Body::Bytes(bytes)) if last_chunk.is_some() => {
+            for chunk in bytes.chunks(consts::CHUNK_SIZE) {
+                write_chunk(writer, chunk, timeout).await?;
             }
-            writer.flush().await?;
+            write_timeout(writer, &last_chunk.unwrap(), timeout).await?;
         }
-        Ok(())
-    }).await
+        Some(Body::Bytes(bytes)) => write_timeout(writer, &bytes, timeout).await?,
+        _ => return Ok(()),
+    }
+    io::timeout(timeout, writer.flush()).await
 }
 
-async fn with_file<F: FnMut(Vec<u8>) -> io::Result<()>>(len: usize, mut file: File, mut op: F) -> io::Result<()> {
-    let chunk_count = (len - 1) / consts::READ_CHUNK_SIZE + 1;
-    for n in 0..chunk_count {
-        let chunk_len = if n == chunk_count - 1 { len % consts::READ_CHUNK_SIZE } else { consts::READ_CHUNK_SIZE };
-        let mut chunk = vec![0; chunk_len];
-        file.read_exact(&mut chunk).await?;
-        op(chunk)?;
+pub async fn send_body(
+    writer: &mut (impl Write + Unpin),
+    message: impl Message,
+    chunk_size: usize,
+    timeout: Duration,
+) -> io::Result<()> {
+    match message.into_body() {
+        Some(Body::File(file, range)) => send_file(writer, file, range, chunk_size, timeout).await?,
+        Some(Body::Mapped(file, range)) => send_mapped(writer, file, range, chunk_size, timeout).await?,
+        Some(Body::Stream(stream)) => send_stream(writer, stream, None, timeout).await?,
+        Some(Body::Bytes(bytes)) => write_timeout(writer, &bytes, timeout).await?,
+        _ => return Ok(()),
+    }
+    io::timeout(timeout, writer.flush()).await
+}
+
+async fn send_mapped(
+    writer: &mut (impl Write + Unpin),
+    file: MappedFile,
+    range: Range,
+    chunk_size: usize,
+    timeout: Duration,
+) -> io::Result<()> {
+    let mut low = range.low;
+    while low < range.high {
+        let high = range.high.min(low + chunk_size.max(1));
+        let chunk = file.read(&Range { low, high })?;
+        write_timeout(writer, &chunk, timeout).await?;
+        low = high;
     }
     Ok(())
 }
 
-async fn write_chunk(writer: &mut (impl Write + Unpin), chunk: &[u8]) -> io::Result<()> {
-    let size = format!("{:x}\r\n", chunk.len()).into_bytes();
-    writer.write_all(&size).await?;
-    writer.write_all(chunk).await?;
-    writer.write_all(b"\r\n").await?;
+async fn send_file(
+    writer: &mut (impl Write + Unpin),
+    mut file: File,
+    range: Range,
+    chunk_size: usize,
+    timeout: Duration,
+) -> io::Result<()> {
+    file.seek(SeekFrom::Start(range.low as u64)).await?;
+    let mut remaining = range.high - range.low;
+    let mut buf = vec![0; chunk_size.max(1).min(remaining)];
+    while remaining > 0 {
+        let chunk_len = buf.len().min(remaining);
+        file.read_exact(&mut buf[..chunk_len]).await?;
+        write_timeout(writer, &buf[..chunk_len], timeout).await?;
+        remaining -= chunk_len;
+    }
     Ok(())
 }
+
+async fn send_stream(
+    writer: &mut (impl Write + Unpin),
+    mut stream: Receiver<io::Result<Vec<u8>>>,
+    last_chunk: Option<&[u8]>,
+    timeout: Duration,
+) -> io::Result<()> {
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if chunk.is_empty() {
+            continue;
+        } else if last_chunk.is_some() {
+            write_chunk(writer, &chunk, timeout).await?;
+        } else {
+            write_timeout(writer, &chunk, timeout).await?;
+        }
+        io::timeout(timeout, writer.flush()).await?;
+    }
+
+    match last_chunk {
+        Some(last_chunk) => write_timeout(writer, last_chunk, timeout).await,
+        _ => Ok(()),
+    }
+}
+
+fn last_chunk_bytes(trailers: &Headers) -> Vec<u8> {
+    if trailers.get_all().is_empty() {
+        b"0\r\n\r\n".to_vec()
+    } else {
+        format!("0\r\n{:?}\r\n\r\n", trailers).into_bytes()
+    }
+}
+
+async fn write_timeout(writer: &mut (impl Write + Unpin), bytes: &[u8], timeout: Duration) -> io::Result<()> {
+    io::timeout(timeout, writer.write_all(bytes)).await
+}
+
+async fn write_chunk(writer: &mut (impl Write + Unpin), chunk: &[u8], timeout: Duration) -> io::Result<()> {
+    let size = format!("{:x}\r\n", chunk.len()).into_bytes();
+    write_timeout(writer, &size, timeout).await?;
+    write_timeout(writer, chunk, timeout).await?;
+    write_timeout(writer, b"\r\n", timeout).await
+}
@@ -4,3 +4,5 @@ pub mod uri;
 pub mod headers;
 pub mod parser;
 pub mod message;
+pub mod mapped_file;
+pub mod proxy_protocol;
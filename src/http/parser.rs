@@ -1,34 +1,41 @@
-use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::error;
 
-use async_std::{io, prelude::Future};
-use async_std::io::{BufRead, Write};
+use std::pin::Pin;
+use std::time::Duration;
+
+use async_std::{future, io, prelude::Future};
+use async_std::io::BufRead;
 use async_std::io::prelude::BufReadExt;
 use futures::AsyncReadExt;
 
 use crate::consts;
 use crate::http::headers;
 use crate::http::headers::Headers;
-use crate::http::message::{Body, MessageBuilder};
+use crate::http::message::Body;
 use crate::http::request::{HttpVersion, Method, Request};
 use crate::http::response::{Response, Status};
-use crate::http::uri::Uri;
+use crate::http::uri::{Authority, Uri};
 
 #[derive(Copy, Clone, Debug)]
 pub enum MessageParseError {
     UnsupportedMethod,
     InvalidUri,
+    ControlCharacter,
+    ForbiddenPath,
     UriTooLong,
     UnsupportedVersion,
     InvalidStatusCode,
 
     InvalidHeader,
     HeaderTooLong,
+    TooManyHeaders,
     NoHostHeader,
+    InvalidHostHeader,
     InvalidExpectHeader,
 
     UnsupportedTransferEncoding,
+    ConflictingFraming,
     InvalidBody,
     BodyTooLarge,
 
@@ -45,6 +52,9 @@ impl<T: error::Error> From<T> for MessageParseError {
 
 pub type MessageParseResult<T> = Result<T, MessageParseError>;
 
+const MAX_TOKEN_LENGTH: usize = 16;
+const MAX_VERSION_SUFFIX_LENGTH: usize = 11;
+
 macro_rules! err_if {
     ($cond:expr, $err:ident) => {
         if $cond {
@@ -53,45 +63,130 @@ macro_rules! err_if {
     }
 }
 
-pub struct MessageParser<R: BufRead + Unpin, W: Write + Unpin> {
+#[derive(Clone, Copy)]
+pub struct ParseLimits {
+    pub max_uri_length: usize,
+    pub max_header_length: usize,
+    pub max_header_count: usize,
+    pub max_header_bytes: usize,
+    pub read_timeout: Duration,
+    pub head_timeout: Duration,
+    pub body_timeout: Option<Duration>,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_uri_length: consts::MAX_URI_LENGTH,
+            max_header_length: consts::MAX_HEADER_LENGTH,
+            max_header_count: consts::MAX_HEADER_COUNT,
+            max_header_bytes: consts::MAX_HEADERS_LENGTH,
+            read_timeout: consts::MAX_READ_TIMEOUT,
+            head_timeout: consts::MAX_HEAD_TIMEOUT,
+            body_timeout: None,
+        }
+    }
+}
+
+pub struct MessageParser<R: BufRead + Unpin> {
     reader: R,
-    writer: W,
+    limits: ParseLimits,
 }
 
-impl<R: BufRead + Unpin, W: Write + Unpin> MessageParser<R, W> {
-    pub fn new(reader: R, writer: W) -> Self {
-        MessageParser { reader, writer }
+impl<R: BufRead + Unpin> MessageParser<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_limits(reader, ParseLimits::default())
+    }
+
+    pub fn with_limits(reader: R, limits: ParseLimits) -> Self {
+        MessageParser { reader, limits }
     }
 
     pub async fn parse_request(&mut self) -> MessageParseResult<Request> {
-        let (method, uri, http_version) = self.parse_request_line().await?;
-        let headers = self.parse_headers(true).await?;
-        let body = self.parse_body(method, &headers).await?.map(|b| Body::Bytes(b));
+        let reader = &mut self.reader;
+        let at_end = future::poll_fn(|cx| Pin::new(&mut *reader).poll_fill_buf(cx).map_ok(<[u8]>::is_empty));
+        let at_end = match with_timeout(self.limits.read_timeout, at_end).await {
+            Err(MessageParseError::TimedOut) => true,
+            result => result?,
+        };
+        err_if!(at_end, EndOfStream);
+
+        let (method, uri, http_version, mut headers) = match future::timeout(self.limits.head_timeout,
+            self.parse_request_head()).await {
+            Ok(head) => head?,
+            _ => return Err(MessageParseError::TimedOut),
+        };
+        check_framing(&mut headers)?;
+        check_host(&uri, &headers, http_version)?;
+
+        let uri = match uri {
+            Uri::AbsoluteForm { authority, path } => {
+                headers.set_one(consts::H_HOST, &Authority { user_info: None, ..authority }.to_string());
+                Uri::OriginForm { path }
+            }
+            uri => uri,
+        };
+
+        let expect_continue = match headers.get(consts::H_EXPECT) {
+            Some(expect) => {
+                err_if!(!expect[0].eq_ignore_ascii_case(consts::H_EXPECT_CONTINUE), InvalidExpectHeader);
+                true
+            }
+            _ => false,
+        };
 
         Ok(Request {
             method,
             uri,
             http_version,
             headers,
-            body,
+            body: None,
+            trailers: Headers::new(),
             chunked: false,
+            expect_continue,
+            id: None,
+            timing: None,
+            secure: false,
         })
     }
 
+    pub async fn parse_request_body(&mut self, request: &mut Request, max_length: usize) -> MessageParseResult<()> {
+        let body_timeout = self.limits.body_timeout;
+        let body = self.parse_body(&request.headers, max_length);
+        let (body, trailers) = match body_timeout {
+            Some(timeout) => future::timeout(timeout, body).await.unwrap_or(Err(MessageParseError::TimedOut))?,
+            _ => body.await?,
+        };
+        request.body = body.map(Body::Bytes);
+        request.trailers = trailers;
+        request.expect_continue = false;
+        Ok(())
+    }
+
     pub async fn parse_response(&mut self) -> MessageParseResult<Response> {
         let (http_version, status) = self.parse_status_line().await?;
-        let headers = self.parse_headers(false).await?;
-        let body = self.parse_body(Method::Post, &headers).await?.map(|b| Body::Bytes(b));
+        let headers = self.parse_headers().await?;
+        let (body, trailers) = self.parse_body(&headers, consts::MAX_OTHER_BODY_LENGTH).await?;
 
         Ok(Response {
             http_version,
             status,
             headers,
-            body,
+            body: body.map(Body::Bytes),
+            trailers,
             chunked: false,
         })
     }
 
+    async fn parse_request_head(&mut self) -> MessageParseResult<(Method, Uri, HttpVersion, Headers)> {
+        let (method, uri, http_version) = self.parse_request_line().await?;
+        let headers = match http_version {
+            HttpVersion::Http09 => Headers::new(),
+            _ => self.parse_headers().await?,
+        };
+        Ok((method, uri, http_version, headers))
+    }
+
     async fn parse_request_line(&mut self) -> MessageParseResult<(Method, Uri, HttpVersion)> {
         let mut buf = Vec::with_capacity(8);
 
@@ -105,24 +200,31 @@ impl<R: BufRead + Unpin, W: Write + Unpin> MessageParser<R, W> {
             b"CONNECT " => Method::Connect,
             b"OPTIONS " => Method::Options,
             b"TRACE " => Method::Trace,
+            b"PROPFIND " => Method::Propfind,
+            b"MKCOL " => Method::Mkcol,
             _ => return Err(MessageParseError::UnsupportedMethod),
         };
-        buf.clear();
 
-        self.read_until_space(&mut buf).await?;
-        let uri_raw = String::from_utf8(buf[..buf.len() - 1].to_vec());
-        err_if!(uri_raw.is_err(), InvalidUri);
-        let uri = Uri::from(&method, &uri_raw.unwrap())?;
+        let mut buf = vec![];
+        let max_length = self.limits.max_uri_length + MAX_VERSION_SUFFIX_LENGTH;
+        err_if!(!self.read_line_bounded(&mut buf, max_length).await?, UriTooLong);
+        let buf = String::from_utf8(buf).map_err(|_| MessageParseError::InvalidUri)?;
+        let line = buf.strip_suffix(consts::CRLF).ok_or(MessageParseError::InvalidUri)?;
+        let (uri_raw, version) = match line.find(' ') {
+            Some(index) => (&line[..index], Some(&line[index + 1..])),
+            _ => (line, None),
+        };
 
-        let mut buf = String::new();
-        with_timeout(self.reader.read_line(&mut buf)).await?;
-        let version = match buf.as_str() {
-            "HTTP/0.9\r\n" => HttpVersion::Http09,
-            "HTTP/1.0\r\n" => HttpVersion::Http10,
-            "HTTP/1.1\r\n" => HttpVersion::Http11,
+        let version = match version {
+            None | Some("HTTP/0.9") => HttpVersion::Http09,
+            Some("HTTP/1.0") => HttpVersion::Http10,
+            Some("HTTP/1.1") => HttpVersion::Http11,
             _ => return Err(MessageParseError::UnsupportedVersion),
         };
+        err_if!(version == HttpVersion::Http09 && method != Method::Get, UnsupportedVersion);
 
+        err_if!(uri_raw.len() > self.limits.max_uri_length, UriTooLong);
+        let uri = Uri::from(&method, uri_raw)?;
         Ok((method, uri, version))
     }
 
@@ -146,86 +248,88 @@ impl<R: BufRead + Unpin, W: Write + Unpin> MessageParser<R, W> {
         err_if!(status.is_err(), InvalidStatusCode);
 
         let mut buf = String::new();
-        with_timeout(self.reader.read_line(&mut buf)).await?;
+        with_timeout(self.limits.read_timeout, self.reader.read_line(&mut buf)).await?;
 
         Ok((version, status.unwrap()))
     }
 
-    async fn parse_headers(&mut self, require_host: bool) -> MessageParseResult<Headers> {
-        let mut headers = Headers::from(HashMap::new());
-        let mut buf = String::new();
+    async fn parse_headers(&mut self) -> MessageParseResult<Headers> {
+        let mut headers = Headers::new();
+        let mut line = vec![];
+        let mut header_count = 0;
+        let mut header_bytes = 0;
 
         loop {
-            buf.clear();
-            match with_timeout(self.reader.read_line(&mut buf)).await {
-                Ok(_) if buf == "\r\n" => break,
-                Ok(_) if buf.len() > consts::MAX_HEADER_LENGTH => return Err(MessageParseError::HeaderTooLong),
-                Ok(_) if buf.contains(':') => self.parse_header(&mut headers, &mut buf).await?,
-                Err(e) => return Err(e),
+            line.clear();
+            let within_limit = self.read_line_bounded(&mut line, self.limits.max_header_length + 2).await?;
+            err_if!(!within_limit, HeaderTooLong);
+
+            let buf = std::str::from_utf8(&line).map_err(|_| MessageParseError::InvalidHeader)?;
+            match buf {
+                consts::CRLF => break,
+                _ if buf.contains(':') => {
+                    header_count += 1;
+                    header_bytes += buf.len();
+                    err_if!(header_count > self.limits.max_header_count, TooManyHeaders);
+                    err_if!(header_bytes > self.limits.max_header_bytes, TooManyHeaders);
+                    self.parse_header(&mut headers, buf).await?
+                }
                 _ => return Err(MessageParseError::InvalidHeader),
             }
         }
-
-        err_if!(require_host && !headers.contains(consts::H_HOST), NoHostHeader);
         Ok(headers)
     }
 
-    async fn parse_header(&mut self, headers: &mut Headers, buf: &mut String) -> MessageParseResult<()> {
+    async fn parse_header(&mut self, headers: &mut Headers, buf: &str) -> MessageParseResult<()> {
         let parts = buf.splitn(2, ':').collect::<Vec<_>>();
-        let header_name = parts[0].to_ascii_lowercase();
         let header_value = parts[1]
             .strip_suffix(consts::CRLF)
             .unwrap_or(parts[1])
             .trim_matches(consts::OPTIONAL_WHITESPACE);
+        err_if!(header_value.chars().any(|c| c.is_control() && c != '\t'), ControlCharacter);
 
-        let header_values = if Headers::is_multi_value(parts[0]) {
-            header_value.split(',').map(|v| v.trim_matches(consts::OPTIONAL_WHITESPACE)).collect()
+        let mut header_values = headers.get(parts[0]).cloned().unwrap_or_default();
+        if Headers::is_multi_value(parts[0]) {
+            let values = header_value.split(',').map(|v| v.trim_matches(consts::OPTIONAL_WHITESPACE).to_string());
+            header_values.extend(values);
         } else {
-            vec![header_value]
-        };
-
-        err_if!(!headers.set(&parts[0], header_values), InvalidHeader);
-        if header_name.as_str() == consts::H_EXPECT {
-            let response = MessageBuilder::<Response>::new();
-            err_if!(header_value != consts::H_EXPECT_CONTINUE, InvalidExpectHeader);
-            response.with_status(Status::Continue).build().send(&mut self.writer).await?;
+            header_values.push(header_value.to_string());
         }
+
+        err_if!(!headers.set(parts[0], header_values.iter().map(|v| v.as_str()).collect()), InvalidHeader);
         Ok(())
     }
 
-    async fn parse_body(&mut self, method: Method, headers: &Headers) -> MessageParseResult<Option<Vec<u8>>> {
+    async fn parse_body(&mut self, headers: &Headers, max_length: usize) -> MessageParseResult<(Option<Vec<u8>>, Headers)> {
+        let no_trailers = Headers::new();
         Ok(if let Some(encodings) = headers.get(consts::H_TRANSFER_ENCODING) {
-            err_if!(encodings.iter().any(|e| e != consts::H_T_ENC_CHUNKED), UnsupportedTransferEncoding);
-            Some(self.parse_chunked_body().await?.0)
-        } else if let Some(length) = headers.get(consts::H_CONTENT_LENGTH) {
-            let length = length[0].parse();
-            err_if!(length.is_err(), InvalidBody);
-            let length = length.unwrap();
-
-            let exceeded_get_body_max = method == Method::Get && length > consts::MAX_GET_BODY_LENGTH;
-            err_if!(exceeded_get_body_max || length > consts::MAX_OTHER_BODY_LENGTH, BodyTooLarge);
-
+            let unsupported = encodings.iter().any(|e| !e.eq_ignore_ascii_case(consts::H_T_ENC_CHUNKED));
+            err_if!(unsupported, UnsupportedTransferEncoding);
+            let (body, trailers) = self.parse_chunked_body(max_length).await?;
+            (Some(body), trailers)
+        } else if let Some(length) = check_body_length(headers, max_length)? {
             let mut body = vec![0; length];
-            with_timeout(self.reader.read_exact(body.as_mut_slice())).await?;
-            Some(body)
+            with_timeout(self.limits.read_timeout, self.reader.read_exact(body.as_mut_slice())).await?;
+            (Some(body), no_trailers)
         } else {
-            None
+            (None, no_trailers)
         })
     }
 
-    async fn parse_chunked_body(&mut self) -> MessageParseResult<(Vec<u8>, Headers)> {
+    async fn parse_chunked_body(&mut self, max_length: usize) -> MessageParseResult<(Vec<u8>, Headers)> {
         let mut body = vec![0u8; 0];
         let mut line = String::new();
         let mut chunk_size = 1;
 
         while chunk_size > 0 {
-            with_timeout(self.reader.read_line(&mut line)).await?;
+            with_timeout(self.limits.read_timeout, self.reader.read_line(&mut line)).await?;
             err_if!(line.len() < 2, InvalidBody);
 
             let parts = line[..line.len() - 2].split(';').collect::<Vec<_>>();
             err_if!(parts.len() > 2, InvalidBody);
 
             chunk_size = usize::from_str_radix(parts[0], 16)?;
+            err_if!(chunk_size > max_length - body.len(), BodyTooLarge);
             let chunk_ext = parts.get(1).unwrap_or(&"").split('=').collect::<Vec<_>>();
             if chunk_ext.len() == 2 {
                 let (name, value) = (chunk_ext[0], chunk_ext[1]);
@@ -235,30 +339,135 @@ impl<R: BufRead + Unpin, W: Write + Unpin> MessageParser<R, W> {
 
             if chunk_size > 0 {
                 let mut buf = vec![0; chunk_size];
-                with_timeout(self.reader.read_exact(buf.as_mut_slice())).await?;
+                with_timeout(self.limits.read_timeout, self.reader.read_exact(buf.as_mut_slice())).await?;
                 body.extend_from_slice(&buf);
 
-                with_timeout(self.reader.read_line(&mut line)).await?;
+                with_timeout(self.limits.read_timeout, self.reader.read_line(&mut line)).await?;
                 err_if!(line != "\r\n", InvalidBody);
                 line.clear();
             }
         }
 
-        let trailers = self.parse_headers(false).await?;
+        let trailers = self.parse_headers().await?;
         Ok((body, trailers))
     }
 
     async fn read_until_space(&mut self, buf: &mut Vec<u8>) -> MessageParseResult<usize> {
-        let result = with_timeout(self.reader.read_until(b' ', buf)).await;
+        let mut reader = (&mut self.reader).take(MAX_TOKEN_LENGTH as u64);
+        let result = with_timeout(self.limits.read_timeout, reader.read_until(b' ', buf)).await;
         err_if!(buf.is_empty(), EndOfStream);
         result
     }
+
+    async fn read_line_bounded(&mut self, buf: &mut Vec<u8>, max_length: usize) -> MessageParseResult<bool> {
+        let mut reader = (&mut self.reader).take(max_length as u64);
+        with_timeout(self.limits.read_timeout, reader.read_until(b'\n', buf)).await?;
+        Ok(buf.ends_with(b"\n") || buf.len() < max_length)
+    }
 }
 
-async fn with_timeout<F: Future<Output=io::Result<R>>, R>(fut: F) -> MessageParseResult<R> {
-    match io::timeout(consts::MAX_READ_TIMEOUT, fut).await {
+fn check_host(uri: &Uri, headers: &Headers, http_version: HttpVersion) -> MessageParseResult<()> {
+    let host = match headers.get(consts::H_HOST) {
+        Some(host) => {
+            err_if!(host.len() > 1, InvalidHostHeader);
+            Some(Authority::parse_host(&host[0]).map_err(|_| MessageParseError::InvalidHostHeader)?)
+        }
+        _ => {
+            err_if!(http_version == HttpVersion::Http11, NoHostHeader);
+            None
+        }
+    };
+
+    let target = match uri {
+        Uri::AbsoluteForm { authority, .. } | Uri::AuthorityForm { authority } => Some(authority),
+        _ => None,
+    };
+    err_if!(matches!((target, host), (Some(target), Some(host)) if !host.matches(target)), InvalidHostHeader);
+    Ok(())
+}
+
+fn check_framing(headers: &mut Headers) -> MessageParseResult<()> {
+    if let Some(encodings) = headers.get(consts::H_TRANSFER_ENCODING) {
+        let chunked = encodings.iter().filter(|e| e.eq_ignore_ascii_case(consts::H_T_ENC_CHUNKED)).count();
+        let last_chunked = encodings.last().map(|e| e.eq_ignore_ascii_case(consts::H_T_ENC_CHUNKED)) == Some(true);
+        err_if!(headers.contains(consts::H_CONTENT_LENGTH) || chunked > 1 || !last_chunked, ConflictingFraming);
+    }
+
+    if let Some(lengths) = headers.get(consts::H_CONTENT_LENGTH) {
+        let mut lengths = lengths
+            .iter()
+            .flat_map(|length| length.split(','))
+            .map(|length| length.trim_matches(consts::OPTIONAL_WHITESPACE));
+        let first = lengths.next().unwrap_or("");
+        err_if!(first.is_empty() || !first.bytes().all(|b| b.is_ascii_digit()), InvalidBody);
+        err_if!(lengths.any(|length| length != first), ConflictingFraming);
+
+        let first = first.to_string();
+        headers.set_one(consts::H_CONTENT_LENGTH, &first);
+    }
+    Ok(())
+}
+
+pub fn check_body_length(headers: &Headers, max_length: usize) -> MessageParseResult<Option<usize>> {
+    Ok(match headers.get(consts::H_CONTENT_LENGTH) {
+        Some(length) => {
+            let length = length[0].parse();
+            err_if!(length.is_err(), InvalidBody);
+            let length = length.unwrap();
+            err_if!(length > max_length, BodyTooLarge);
+            Some(length)
+        }
+        _ => None,
+    })
+}
+
+async fn with_timeout<F: Future<Output=io::Result<R>>, R>(timeout: Duration, fut: F) -> MessageParseResult<R> {
+    match io::timeout(timeout, fut).await {
         Ok(result) => Ok(result),
         Err(e) if e.kind() == io::ErrorKind::TimedOut => Err(MessageParseError::TimedOut),
         _ => Err(MessageParseError::Unknown)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use async_std::io::Cursor;
+
+    use super::*;
+
+    async fn parse(raw: &str) -> MessageParseResult<Request> {
+        Request::new(&mut Cursor::new(raw.as_bytes().to_vec()), ParseLimits::default()).await
+    }
+
+    #[async_std::test]
+    async fn absolute_form_is_rewritten_to_host() {
+        let request = parse("GET http://user@example.com:8080/a?b=c HTTP/1.0\r\n\r\n").await.ok().unwrap();
+        assert!(matches!(&request.uri, Uri::OriginForm { path } if path.path == ["a"]));
+        assert_eq!(request.headers.get(consts::H_HOST).unwrap(), &["example.com:8080"]);
+
+        let raw = "GET http://Example.com/ HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let request = parse(raw).await.ok().unwrap();
+        assert!(matches!(&request.uri, Uri::OriginForm { path } if path.path.is_empty()));
+        assert_eq!(request.headers.get(consts::H_HOST).unwrap(), &["Example.com"]);
+    }
+
+    #[async_std::test]
+    async fn absolute_form_must_match_host() {
+        let raw = "GET http://example.com/ HTTP/1.1\r\nHost: example.org\r\n\r\n";
+        assert!(matches!(parse(raw).await, Err(MessageParseError::InvalidHostHeader)));
+        let raw = "GET http://example.com:8080/ HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        assert!(matches!(parse(raw).await, Err(MessageParseError::InvalidHostHeader)));
+    }
+
+    #[async_std::test]
+    async fn target_forms_are_checked_against_the_method() {
+        let request = parse("OPTIONS * HTTP/1.1\r\nHost: a\r\n\r\n").await.ok().unwrap();
+        assert!(matches!(request.uri, Uri::AsteriskForm));
+        let request = parse("CONNECT a:443 HTTP/1.1\r\nHost: a:443\r\n\r\n").await.ok().unwrap();
+        assert!(matches!(request.uri, Uri::AuthorityForm { .. }));
+
+        assert!(matches!(parse("GET * HTTP/1.1\r\nHost: a\r\n\r\n").await, Err(MessageParseError::InvalidUri)));
+        assert!(matches!(parse("CONNECT a HTTP/1.1\r\nHost: a\r\n\r\n").await, Err(MessageParseError::InvalidUri)));
+        assert!(matches!(parse("CONNECT / HTTP/1.1\r\nHost: a\r\n\r\n").await, Err(MessageParseError::InvalidUri)));
+    }
+}
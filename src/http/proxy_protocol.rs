@@ -0,0 +1,77 @@
+use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use async_std::io::{self, BufRead, ErrorKind};
+use async_std::io::prelude::{BufReadExt, ReadExt};
+
+use crate::consts;
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V1_MAX_LENGTH: u64 = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+
+const V2_COMMAND_LOCAL: u8 = 0x0;
+const V2_COMMAND_PROXY: u8 = 0x1;
+const V2_FAMILY_INET: u8 = 0x1;
+const V2_FAMILY_INET6: u8 = 0x2;
+
+pub async fn read_header(reader: &mut (impl BufRead + Unpin)) -> io::Result<Option<SocketAddr>> {
+    io::timeout(consts::MAX_READ_TIMEOUT, async {
+        let mut signature = [0; 12];
+        reader.read_exact(&mut signature).await?;
+
+        if signature == V2_SIGNATURE {
+            read_v2(reader).await
+        } else if signature.starts_with(V1_PREFIX) {
+            let mut line = String::from_utf8(signature.to_vec()).map_err(|_| invalid())?;
+            reader.take(V1_MAX_LENGTH - signature.len() as u64).read_line(&mut line).await?;
+            parse_v1(&line)
+        } else {
+            Err(invalid())
+        }
+    }).await
+}
+
+fn parse_v1(line: &str) -> io::Result<Option<SocketAddr>> {
+    let line = line.strip_suffix(consts::CRLF).ok_or_else(invalid)?;
+    let fields = line.split(' ').skip(1).collect::<Vec<_>>();
+
+    let source = match fields.as_slice() {
+        ["UNKNOWN", ..] => return Ok(None),
+        ["TCP4", source, _, port, _] => source.parse::<Ipv4Addr>().map(IpAddr::V4).map(|ip| (ip, port)),
+        ["TCP6", source, _, port, _] => source.parse::<Ipv6Addr>().map(IpAddr::V6).map(|ip| (ip, port)),
+        _ => return Err(invalid()),
+    };
+    match source {
+        Ok((ip, port)) => Ok(Some(SocketAddr::new(ip, port.parse().map_err(|_| invalid())?))),
+        _ => Err(invalid()),
+    }
+}
+
+async fn read_v2(reader: &mut (impl BufRead + Unpin)) -> io::Result<Option<SocketAddr>> {
+    let mut header = [0; 4];
+    reader.read_exact(&mut header).await?;
+
+    let mut addresses = vec![0; u16::from_be_bytes([header[2], header[3]]) as usize];
+    reader.read_exact(&mut addresses).await?;
+
+    let port = |index: usize| u16::from_be_bytes([addresses[index], addresses[index + 1]]);
+    match (header[0] >> 4, header[0] & 0xf, header[1] >> 4) {
+        (2, V2_COMMAND_LOCAL, _) => Ok(None),
+        (2, V2_COMMAND_PROXY, V2_FAMILY_INET) if addresses.len() >= 12 => {
+            let ip = <[u8; 4]>::try_from(&addresses[..4]).unwrap();
+            Ok(Some(SocketAddr::new(IpAddr::from(ip), port(8))))
+        }
+        (2, V2_COMMAND_PROXY, V2_FAMILY_INET6) if addresses.len() >= 36 => {
+            let ip = <[u8; 16]>::try_from(&addresses[..16]).unwrap();
+            Ok(Some(SocketAddr::new(IpAddr::from(ip), port(32))))
+        }
+        (2, V2_COMMAND_PROXY, V2_FAMILY_INET) | (2, V2_COMMAND_PROXY, V2_FAMILY_INET6) => Err(invalid()),
+        (2, V2_COMMAND_PROXY, _) => Ok(None),
+        _ => Err(invalid()),
+    }
+}
+
+fn invalid() -> io::Error {
+    ErrorKind::InvalidData.into()
+}
@@ -1,14 +1,16 @@
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, SocketAddr};
 
-use async_std::io::{self, BufReader, BufWriter, Write};
-use async_std::io::prelude::Read;
+use async_std::io::{self, BufRead, Write};
 
+use crate::consts;
 use crate::http::headers::Headers;
 use crate::http::message::{Body, Message};
 use crate::http::message;
-use crate::http::parser::{MessageParser, MessageParseResult};
-use crate::http::uri::Uri;
+use crate::http::parser::{ParseLimits, MessageParser, MessageParseResult};
+use crate::http::uri::{Authority, Uri};
+use crate::util;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum Method {
@@ -20,6 +22,8 @@ pub enum Method {
     Connect,
     Options,
     Trace,
+    Propfind,
+    Mkcol,
 }
 
 impl Display for Method {
@@ -33,6 +37,8 @@ impl Display for Method {
             Method::Connect => "CONNECT",
             Method::Options => "OPTIONS",
             Method::Trace => "TRACE",
+            Method::Propfind => "PROPFIND",
+            Method::Mkcol => "MKCOL",
         })
     }
 }
@@ -60,16 +66,81 @@ pub struct Request {
     pub http_version: HttpVersion,
     pub headers: Headers,
     pub body: Option<Body>,
+    pub trailers: Headers,
     pub chunked: bool,
+    pub expect_continue: bool,
+    pub id: Option<String>,
+    pub timing: Option<util::Timing>,
+    pub secure: bool,
 }
 
 impl Request {
-    pub async fn new<R: Read + Unpin, W: Write + Unpin>(reader: &mut R, writer: &mut W) -> MessageParseResult<Self> {
-        MessageParser::new(BufReader::new(reader), BufWriter::new(writer)).parse_request().await
+    pub async fn new<R: BufRead + Unpin>(reader: &mut R, limits: ParseLimits) -> MessageParseResult<Self> {
+        MessageParser::with_limits(reader, limits).parse_request().await
+    }
+
+    pub async fn read_body<R: BufRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+        max_length: usize,
+        limits: ParseLimits,
+    ) -> MessageParseResult<()> {
+        MessageParser::with_limits(reader, limits).parse_request_body(self, max_length).await
+    }
+
+    pub fn keep_alive(&self) -> bool {
+        match self.http_version {
+            HttpVersion::Http09 => false,
+            HttpVersion::Http10 => {
+                self.has_connection_option(consts::H_CONN_KEEP_ALIVE) && !self.has_connection_option(consts::H_CONN_CLOSE)
+            }
+            HttpVersion::Http11 => !self.has_connection_option(consts::H_CONN_CLOSE),
+        }
+    }
+
+    pub fn has_connection_option(&self, option: &str) -> bool {
+        match self.headers.get(consts::H_CONNECTION) {
+            Some(options) => options.iter().any(|o| o.eq_ignore_ascii_case(option)),
+            _ => false,
+        }
+    }
+
+    pub fn upgrade_protocols(&self) -> Vec<&str> {
+        match self.headers.get(consts::H_UPGRADE) {
+            Some(protocols) if self.http_version == HttpVersion::Http11
+                && self.has_connection_option(consts::H_CONN_UPGRADE) => {
+                protocols.iter().map(|p| p.trim_matches(consts::OPTIONAL_WHITESPACE)).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    pub fn host(&self) -> Option<String> {
+        let host = match &self.uri {
+            Uri::AbsoluteForm { authority, .. } | Uri::AuthorityForm { authority } => authority.host.clone(),
+            _ => Authority::parse_host(&self.headers.get(consts::H_HOST)?[0]).ok()?.host,
+        };
+        Some(host.trim_end_matches('.').to_ascii_lowercase())
+    }
+
+    pub fn forwarded_proto(&self) -> Option<&str> {
+        let proto = self.headers.get(consts::H_X_FORWARDED_PROTO)?[0].split(',').next()?;
+        Some(proto.trim_matches(consts::OPTIONAL_WHITESPACE))
+    }
+
+    pub fn forwarded_for(&self) -> Option<Vec<IpAddr>> {
+        let entries = self.headers.get(consts::H_X_FORWARDED_FOR)?;
+        entries
+            .iter()
+            .map(|entry| {
+                let entry = entry.trim_matches(consts::OPTIONAL_WHITESPACE);
+                entry.parse::<IpAddr>().or_else(|_| entry.parse::<SocketAddr>().map(|addr| addr.ip())).ok()
+            })
+            .collect()
     }
 
     pub async fn _send(self, writer: &mut (impl Write + Unpin)) -> io::Result<()> {
-        message::send(writer, self).await
+        message::send(writer, self, consts::READ_CHUNK_SIZE, consts::MAX_WRITE_TIMEOUT).await
     }
 }
 
@@ -86,6 +157,10 @@ impl Message for Request {
         self.body
     }
 
+    fn get_trailers(&self) -> &Headers {
+        &self.trailers
+    }
+
     fn to_bytes_no_body(&self) -> Vec<u8> {
         format!("{} {} {}\r\n{:?}\r\n\r\n", self.method, self.uri, self.http_version, self.headers).into_bytes()
     }
@@ -1,11 +1,13 @@
 use std::fmt::{Display, Formatter};
 use std::fmt;
+use std::time::Duration;
 
 use async_std::io;
-use async_std::io::{BufReader, BufWriter, Write};
+use async_std::io::{BufReader, Write};
 use async_std::io::prelude::Read;
 use num_enum::TryFromPrimitive;
 
+use crate::consts;
 use crate::http::headers::Headers;
 use crate::http::message::{Body, Message};
 use crate::http::message;
@@ -16,20 +18,20 @@ use crate::http::request::HttpVersion;
 #[repr(usize)]
 pub enum Status {
     Continue = 100,
-    _SwitchingProtocols,
+    SwitchingProtocols,
     _Processing,
     Ok = 200,
-    _Created,
+    Created,
     _Accepted,
     _NonAuthoritativeInformation,
     NoContent,
     _ResetContent,
     PartialContent,
-    _MultiStatus,
+    MultiStatus,
     _AlreadyReported,
     _MultipleChoices = 300,
-    _MovedPermanently,
-    _Found,
+    MovedPermanently,
+    Found,
     _SeeOther,
     NotModified,
     _UseProxy,
@@ -41,16 +43,16 @@ pub enum Status {
     Forbidden,
     NotFound,
     MethodNotAllowed,
-    _NotAcceptable,
+    NotAcceptable,
     _ProxyAuthenticationRequired,
     RequestTimeout,
-    _Conflict,
+    Conflict,
     _Gone,
     _LengthRequired,
     PreconditionFailed,
     PayloadTooLarge,
     UriTooLong,
-    _UnsupportedMediaType,
+    UnsupportedMediaType,
     UnsatisfiableRange,
     ExpectationFailed,
     _ImATeapot,
@@ -58,17 +60,17 @@ pub enum Status {
     _UnprocessableEntity,
     _Locked,
     _FailedDependency,
-    _UpgradeRequired = 426,
+    UpgradeRequired = 426,
     _PreconditionRequired = 428,
-    _TooManyRequests,
+    TooManyRequests,
     HeaderFieldsTooLarge = 431,
     _ConnectionClosed = 444,
     _UnavailableForLegalReasons = 451,
     InternalServerError = 500,
     NotImplemented,
-    _BadGateway,
-    _ServiceUnavailable,
-    _GatewayTimeout,
+    BadGateway,
+    ServiceUnavailable,
+    GatewayTimeout,
     HttpVersionUnsupported,
     _VariantAlsoNegotiates,
     _InsufficientStorage,
@@ -88,16 +90,35 @@ pub struct Response {
     pub status: Status,
     pub headers: Headers,
     pub body: Option<Body>,
+    pub trailers: Headers,
     pub chunked: bool,
 }
 
 impl Response {
-    pub async fn new<R: Read + Unpin, W: Write + Unpin>(reader: &mut R, writer: &mut W) -> MessageParseResult<Self> {
-        MessageParser::new(BufReader::new(reader), BufWriter::new(writer)).parse_response().await
+    pub async fn new<R: Read + Unpin>(reader: &mut R) -> MessageParseResult<Self> {
+        MessageParser::new(BufReader::new(reader)).parse_response().await
     }
 
     pub async fn send(self, writer: &mut (impl Write + Unpin)) -> io::Result<()> {
-        message::send(writer, self).await
+        message::send(writer, self, consts::READ_CHUNK_SIZE, consts::MAX_WRITE_TIMEOUT).await
+    }
+
+    pub async fn stream(
+        self,
+        writer: &mut (impl Write + Unpin),
+        chunk_size: usize,
+        timeout: Duration,
+    ) -> io::Result<()> {
+        message::send(writer, self, chunk_size, timeout).await
+    }
+
+    pub async fn stream_body(
+        self,
+        writer: &mut (impl Write + Unpin),
+        chunk_size: usize,
+        timeout: Duration,
+    ) -> io::Result<()> {
+        message::send_body(writer, self, chunk_size, timeout).await
     }
 }
 
@@ -114,6 +135,10 @@ impl Message for Response {
         self.body
     }
 
+    fn get_trailers(&self) -> &Headers {
+        &self.trailers
+    }
+
     fn to_bytes_no_body(&self) -> Vec<u8> {
         format!("{} {}\r\n{:?}\r\n\r\n", self.http_version, self.status, self.headers).into_bytes()
     }
@@ -1,29 +1,53 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::fmt;
+use std::net::Ipv6Addr;
 
-use crate::consts;
 use crate::http::parser::{MessageParseError, MessageParseResult};
 use crate::http::request::Method;
 use crate::util;
 
+macro_rules! err_if {
+    ($cond:expr) => {
+        if $cond {
+            return Err(MessageParseError::InvalidUri);
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Authority {
     pub user_info: Option<String>,
     pub host: String,
     pub port: Option<u16>,
 }
 
+impl Authority {
+    pub fn parse_host(raw: &str) -> MessageParseResult<Self> {
+        let mut parser = UriParser { method: &Method::Connect, raw };
+        let authority = parser.parse_authority(false)?;
+        err_if!(!parser.raw.is_empty() || authority.host.is_empty());
+        Ok(authority)
+    }
+
+    pub fn matches(&self, other: &Authority) -> bool {
+        self.host.eq_ignore_ascii_case(&other.host) && self.port == other.port
+    }
+}
+
 impl Display for Authority {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let user_info = self.user_info.clone().map(|info| format!("{}@", info)).unwrap_or(String::new());
-        let port = self.port.map(|port| format!(":{}", port)).unwrap_or(String::new());
-        write!(f, "{}{}{}", encode_percent(&user_info), encode_percent(&self.host), port)
+        let user_info = self.user_info.clone().map(|info| format!("{}@", info)).unwrap_or_default();
+        let port = self.port.map(|port| format!(":{}", port)).unwrap_or_default();
+        write!(f, "{}{}{}", encode_percent(&user_info, ""), encode_percent(&self.host, ""), port)
     }
 }
 
+#[derive(Clone)]
 pub struct AbsolutePath {
     pub path: Vec<String>,
     pub query: Option<HashMap<String, String>>,
+    pub trailing_slash: bool,
 }
 
 impl AbsolutePath {
@@ -45,12 +69,16 @@ impl AbsolutePath {
 
 impl Display for AbsolutePath {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let path_joined = self.path_as_string();
-        let query_joined = self.query_as_string();
-        write!(f, "/{}{}", encode_percent(&path_joined), encode_percent(&query_joined))
+        let path_joined = self.path.iter().map(|segment| encode_percent(segment, "?")).collect::<Vec<_>>().join("/");
+        let query_joined = match self.query {
+            Some(_) => format!("?{}", self.query_as_string()),
+            _ => String::new(),
+        };
+        write!(f, "/{}{}", path_joined, encode_percent(&query_joined, ""))
     }
 }
 
+#[derive(Clone)]
 pub enum Uri {
     OriginForm { path: AbsolutePath },
     AbsoluteForm {
@@ -78,14 +106,6 @@ impl Display for Uri {
     }
 }
 
-macro_rules! err_if {
-    ($cond:expr) => {
-        if $cond {
-            return Err(MessageParseError::InvalidUri);
-        }
-    }
-}
-
 struct UriParser<'a, 'b> {
     method: &'a Method,
     raw: &'b str,
@@ -93,12 +113,13 @@ struct UriParser<'a, 'b> {
 
 impl UriParser<'_, '_> {
     fn parse(&mut self) -> MessageParseResult<Uri> {
-        if self.raw.len() > consts::MAX_URI_LENGTH {
-            Err(MessageParseError::UriTooLong)
+        if self.raw.chars().any(char::is_control) {
+            Err(MessageParseError::ControlCharacter)
         } else if self.raw == "*" && *self.method == Method::Options {
             Ok(Uri::AsteriskForm)
         } else if *self.method == Method::Connect {
             let authority = self.parse_authority(false)?;
+            err_if!(!self.raw.is_empty() || authority.host.is_empty() || authority.port.is_none());
             Ok(Uri::AuthorityForm { authority })
         } else if self.raw.starts_with('/') {
             let path = self.parse_absolute_path(false)?;
@@ -112,13 +133,12 @@ impl UriParser<'_, '_> {
     }
 
     fn parse_pre_authority(&mut self) -> MessageParseResult<()> {
-        if self.raw.starts_with("http://") && self.raw.len() > 7 {
-            self.raw = &self.raw[7..];
-        } else if self.raw.starts_with("https://") && self.raw.len() > 8 {
-            self.raw = &self.raw[8..];
-        } else {
-            return Err(MessageParseError::InvalidUri);
-        }
+        let scheme_end = self.raw.find("://").ok_or(MessageParseError::InvalidUri)?;
+        let scheme = &self.raw[..scheme_end];
+        err_if!(!scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https"));
+
+        self.raw = &self.raw[scheme_end + 3..];
+        err_if!(self.raw.is_empty() || self.raw.starts_with('/'));
         Ok(())
     }
 
@@ -128,21 +148,27 @@ impl UriParser<'_, '_> {
             Some(index) => {
                 let info = &authority_part[..index];
                 err_if!(!accept_user || !info.chars().all(is_user_info_char));
-                Some(decode_percent(info).ok_or(MessageParseError::InvalidUri)?)
+                Some(decode_component(info)?)
             }
             _ => None,
         };
 
-        let host_and_port = authority_part.split(':').collect::<Vec<_>>();
-        err_if!(host_and_port.is_empty() || host_and_port.len() > 2);
-
-        let mut host = host_and_port[0].to_string();
-        err_if!(!host.chars().all(is_host_char));
-        host = decode_percent(&host).ok_or(MessageParseError::InvalidUri)?;
+        let host_and_port = &authority_part[authority_part.find('@').map(|index| index + 1).unwrap_or(0)..];
+        let (host, port) = if host_and_port.starts_with('[') {
+            let end = host_and_port.find(']').ok_or(MessageParseError::InvalidUri)?;
+            err_if!(host_and_port[1..end].parse::<Ipv6Addr>().is_err());
+            (host_and_port[..=end].to_string(), &host_and_port[end + 1..])
+        } else {
+            let end = host_and_port.find(':').unwrap_or(host_and_port.len());
+            let host = &host_and_port[..end];
+            err_if!(!host.chars().all(is_host_char));
+            (decode_component(host)?, &host_and_port[end..])
+        };
 
-        let port = match host_and_port.get(1).map(|s| s.parse()) {
+        let port = match port.strip_prefix(':').map(|s| s.parse()) {
             Some(Ok(port)) => Some(port),
-            Some(Err(_)) => return Err(MessageParseError::InvalidUri),
+            Some(Err(_)) if port != ":" => return Err(MessageParseError::InvalidUri),
+            _ if !port.is_empty() && port != ":" => return Err(MessageParseError::InvalidUri),
             _ => None,
         };
 
@@ -158,35 +184,46 @@ impl UriParser<'_, '_> {
         } else {
             (self.raw, "")
         };
-        if raw_path.ends_with('/') {
+        let trailing_slash = raw_path.ends_with('/');
+        if trailing_slash {
             raw_path = &raw_path[..raw_path.len() - 1]
         }
 
-        let mut path = raw_path.split('/').map(|segment| segment.to_string()).collect::<Vec<_>>();
-        err_if!(!path[0].is_empty());
+        let mut segments = raw_path.split('/');
+        err_if!(segments.next().map(|first| !first.is_empty()).unwrap_or(false));
 
-        path = path.into_iter().skip(1).map(|s| s.to_string()).collect();
-        err_if!(path.iter().any(|part| part.is_empty() || !part.chars().all(is_path_char) || part == ".."));
+        let mut path = vec![];
+        for segment in segments.filter(|segment| !segment.is_empty()) {
+            err_if!(!segment.chars().all(is_path_char));
+            let segment = decode_component(segment)?;
+            if segment.contains(&['/', '\\'][..]) {
+                return Err(MessageParseError::ForbiddenPath);
+            }
 
-        let old_len = path.len();
-        path = path.iter().filter_map(|s| decode_percent(s)).collect::<Vec<_>>();
-        err_if!(path.len() < old_len);
+            match segment.as_str() {
+                "." => {}
+                ".." => {
+                    path.pop().ok_or(MessageParseError::ForbiddenPath)?;
+                }
+                _ => path.push(segment),
+            }
+        }
 
         if raw_query.is_empty() {
-            Ok(AbsolutePath { path, query: None })
+            Ok(AbsolutePath { path, query: None, trailing_slash })
         } else {
             let params = raw_query
                 .split('&')
                 .map(|param| param.splitn(2, '=').collect::<Vec<&str>>())
                 .collect::<Vec<_>>();
-            err_if!(!params.iter().all(|p| p.len() == 2 && is_query_string(p[0]) && is_query_string(p[1])));
+            err_if!(!params.iter().all(|p| p.iter().all(|part| is_query_string(part))));
 
             let query = params
                 .iter()
-                .filter_map(|p| Some((decode_percent(p[0])?, decode_percent(p[1])?)))
-                .collect::<HashMap<_, _>>();
+                .map(|p| Ok((decode_component(p[0])?, decode_component(p.get(1).unwrap_or(&""))?)))
+                .collect::<MessageParseResult<HashMap<_, _>>>()?;
             err_if!(query.len() < params.len());
-            Ok(AbsolutePath { path, query: Some(query) })
+            Ok(AbsolutePath { path, query: Some(query), trailing_slash })
         }
     }
 }
@@ -213,24 +250,158 @@ fn is_host_char(ch: char) -> bool {
 }
 
 fn decode_percent(str: &str) -> Option<String> {
-    let mut decoded = String::new();
-    let mut last_index = 0;
-    for (index, _) in str.match_indices('%') {
-        decoded.push_str(&str[last_index..index]);
-        if index + 3 > str.len() {
-            return None;
+    let mut decoded = vec![];
+    let mut bytes = str.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+fn decode_component(str: &str) -> MessageParseResult<String> {
+    let decoded = decode_percent(str).ok_or(MessageParseError::InvalidUri)?;
+    if decoded.chars().any(char::is_control) {
+        Err(MessageParseError::ControlCharacter)
+    } else {
+        Ok(decoded)
+    }
+}
+
+fn encode_percent(str: &str, reserved: &str) -> String {
+    let mut encoded = String::new();
+    for ch in str.chars() {
+        if util::is_visible_char(ch) && ch != '%' && ch != '#' && !reserved.contains(ch) {
+            encoded.push(ch);
+        } else {
+            let mut buf = [0; 4];
+            encoded.extend(ch.encode_utf8(&mut buf).bytes().map(|byte| format!("%{:02x}", byte)));
         }
-        let ch = u8::from_str_radix(&str[index + 1..index + 3], 16).ok()? as char;
-        decoded.push(ch);
-        last_index = index + 3;
     }
-    decoded.push_str(&str[last_index..]);
-    Some(decoded)
+    encoded
 }
 
-fn encode_percent(str: &str) -> String {
-    str.chars()
-        .map(|c| if util::is_visible_char(c) { c.to_string() } else { format!("%{:02x}", c as u8) })
-        .collect::<Vec<_>>()
-        .join("")
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_path(raw: &str) -> MessageParseResult<Vec<String>> {
+        match Uri::from(&Method::Get, raw)? {
+            Uri::OriginForm { path } => Ok(path.path),
+            _ => panic!("expected origin form for {}", raw),
+        }
+    }
+
+    fn assert_invalid(method: &Method, raw: &str) {
+        assert!(matches!(Uri::from(method, raw), Err(MessageParseError::InvalidUri)), "{} was accepted", raw);
+    }
+
+    fn assert_forbidden(raw: &str) {
+        assert!(matches!(parse_path(raw), Err(MessageParseError::ForbiddenPath)), "{} was not forbidden", raw);
+    }
+
+    #[test]
+    fn dot_segments_are_resolved() {
+        assert_eq!(parse_path("/a/./b/../c").unwrap(), ["a", "c"]);
+        assert_eq!(parse_path("/a/%2e%2e/b").unwrap(), ["b"]);
+        assert_eq!(parse_path("/a/%2E/b").unwrap(), ["a", "b"]);
+    }
+
+    #[test]
+    fn dot_segments_cannot_escape_root() {
+        assert_forbidden("/..");
+        assert_forbidden("/a/../..");
+        assert_forbidden("/a/../../etc/passwd");
+        assert_forbidden("/%2e%2e/etc/passwd");
+    }
+
+    #[test]
+    fn encoded_separators_are_forbidden() {
+        assert_forbidden("/%2e%2e%2fetc/passwd");
+        assert_forbidden("/a/%2e%2e%2f%2e%2e%2fetc");
+        assert_forbidden("/..%2fetc/passwd");
+        assert_forbidden("/a%2fb");
+        assert_forbidden("/a%5cb");
+        assert_forbidden("/..%5c..%5cwindows");
+    }
+
+    #[test]
+    fn double_encoding_is_decoded_once() {
+        assert_eq!(parse_path("/%252e%252e/etc").unwrap(), ["%2e%2e", "etc"]);
+        assert_eq!(parse_path("/a/%252e%252e%252fb").unwrap(), ["a", "%2e%2e%2fb"]);
+    }
+
+    #[test]
+    fn encoded_nul_is_rejected() {
+        assert!(matches!(parse_path("/file%00.txt"), Err(MessageParseError::ControlCharacter)));
+        assert!(matches!(parse_path("/%00/.."), Err(MessageParseError::ControlCharacter)));
+    }
+
+    #[test]
+    fn origin_form() {
+        match Uri::from(&Method::Get, "/a/b/?c=d").unwrap() {
+            Uri::OriginForm { path } => {
+                assert_eq!(path.path, ["a", "b"]);
+                assert!(path.trailing_slash);
+                assert_eq!(path.query_as_string(), "c=d");
+            }
+            _ => panic!("expected origin form"),
+        }
+        assert_invalid(&Method::Get, "a/b");
+    }
+
+    #[test]
+    fn absolute_form() {
+        match Uri::from(&Method::Get, "http://user@Example.com:8080/a?b=c").unwrap() {
+            Uri::AbsoluteForm { authority, path } => {
+                assert_eq!(authority.user_info.as_deref(), Some("user"));
+                assert_eq!(authority.host, "Example.com");
+                assert_eq!(authority.port, Some(8080));
+                assert_eq!(path.path, ["a"]);
+            }
+            _ => panic!("expected absolute form"),
+        }
+        match Uri::from(&Method::Get, "https://[::1]").unwrap() {
+            Uri::AbsoluteForm { authority, path } => {
+                assert_eq!(authority.host, "[::1]");
+                assert!(path.path.is_empty());
+            }
+            _ => panic!("expected absolute form"),
+        }
+        assert_invalid(&Method::Get, "ftp://example.com/a");
+        assert_invalid(&Method::Get, "http:///a");
+        assert_invalid(&Method::Get, "http://example.com:port/a");
+        assert_invalid(&Method::Get, "http://[::g]/a");
+    }
+
+    #[test]
+    fn authority_form() {
+        match Uri::from(&Method::Connect, "example.com:443").unwrap() {
+            Uri::AuthorityForm { authority } => {
+                assert_eq!(authority.host, "example.com");
+                assert_eq!(authority.port, Some(443));
+            }
+            _ => panic!("expected authority form"),
+        }
+        assert_invalid(&Method::Connect, "example.com");
+        assert_invalid(&Method::Connect, "example.com:");
+        assert_invalid(&Method::Connect, ":443");
+        assert_invalid(&Method::Connect, "user@example.com:443");
+        assert_invalid(&Method::Connect, "example.com:443/a");
+        assert_invalid(&Method::Connect, "/a");
+        assert_invalid(&Method::Get, "example.com:443");
+    }
+
+    #[test]
+    fn asterisk_form() {
+        assert!(matches!(Uri::from(&Method::Options, "*"), Ok(Uri::AsteriskForm)));
+        assert_invalid(&Method::Get, "*");
+        assert_invalid(&Method::Head, "*");
+        assert_invalid(&Method::Connect, "*");
+    }
 }
@@ -0,0 +1,5 @@
+pub mod server;
+pub mod http;
+pub mod log;
+pub mod util;
+pub mod consts;
@@ -1,40 +1,77 @@
-use std::env;
+use std::{env, mem, ptr, thread};
 
 use async_std::process;
 use async_std::sync::Arc;
+use async_std::task;
 
-use crate::server::config::Config;
-use crate::server::file_server::{FileServer, FileServerStartError};
-use crate::server::Server;
-
-mod server;
-mod log;
-mod http;
-mod util;
-mod consts;
+use lucent::log;
+use lucent::server::config::Config;
+use lucent::server::file_server::FileServer;
+use lucent::server::Server;
 
 #[async_std::main]
 async fn main() {
-    let mut args = env::args();
-    if args.len() != 2 {
-        println!("usage: {} <config path>", args.next().unwrap());
-        process::exit(1);
+    let args = env::args().collect::<Vec<_>>();
+    match args.as_slice() {
+        [_, flag, config_path] if flag == "--check" => check_config(config_path).await,
+        [_, config_path] => run_server(config_path.clone()).await,
+        _ => {
+            println!("usage: {} [--check] <config path>", args[0]);
+            process::exit(1);
+        }
     }
+}
 
-    let config = Config::load(&args.nth(1).unwrap()).await
-        .unwrap_or_else(|| log::fatal("Configuration file invalid or missing required settings!"));
+async fn run_server(config_path: String) {
+    let reload_signals = block_reload_signals();
+    let config = Config::load(&config_path).await
+        .unwrap_or_else(|e| log::fatal(format!("Configuration file invalid or missing required settings: {}", e)));
 
     log::fatal(match FileServer::new(config).await {
         Ok(server) => {
             let server = Arc::new(server);
             let server_clone = Arc::clone(&server);
             let _ = ctrlc::set_handler(move || server_clone.stop());
+            let server_clone = Arc::clone(&server);
+            thread::spawn(move || handle_reload_signals(reload_signals, &config_path, &*server_clone));
             return server.start();
         }
-        Err(FileServerStartError::InvalidFileRoot) => "File directory invalid!",
-        Err(FileServerStartError::InvalidTemplates) => "Template directory invalid or incomplete!",
-        Err(FileServerStartError::AddressInUse) => "That address is in use!",
-        Err(FileServerStartError::AddressUnavailable) => "That address is unavailable!",
-        _ => "Cannot bind to that address!",
+        Err(e) => e.to_string(),
     });
 }
+
+async fn check_config(config_path: &str) {
+    let result = match Config::load(config_path).await {
+        Ok(config) => FileServer::check(config).await.map_err(|e| e.to_string()),
+        Err(e) => Err(format!("Configuration file invalid or missing required settings: {}", e)),
+    };
+
+    match result {
+        Ok(_) => println!("{}: configuration valid.", config_path),
+        Err(e) => {
+            eprintln!("{}: {}", config_path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn block_reload_signals() -> libc::sigset_t {
+    unsafe {
+        let mut signals = mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        libc::sigaddset(&mut signals, libc::SIGHUP);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, ptr::null_mut());
+        signals
+    }
+}
+
+fn handle_reload_signals(signals: libc::sigset_t, config_path: &str, server: &impl Server) {
+    let mut signal = 0;
+    while unsafe { libc::sigwait(&signals, &mut signal) } == 0 {
+        log::info("Reloading configuration.");
+        match task::block_on(Config::load(config_path)) {
+            Ok(config) => server.reload(config),
+            Err(e) => log::warn(format!("Configuration file invalid ({}); keeping the running configuration.", e)),
+        }
+    }
+}
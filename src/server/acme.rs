@@ -0,0 +1,493 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use async_std::task;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use ring::rand::SystemRandom;
+use ring::signature::{self, EcdsaKeyPair, EcdsaSigningAlgorithm, KeyPair};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{consts, log};
+use crate::server::config::acme::Acme;
+use crate::server::file_server::ServerState;
+
+const ACCOUNT_KEY_FILE: &str = "account.key";
+const CERTIFICATE_FILE: &str = "cert.pem";
+const PRIVATE_KEY_FILE: &str = "key.pem";
+const PEM_PRIVATE_KEY: &str = "PRIVATE KEY";
+const MEDIA_JOSE_JSON: &str = "application/jose+json";
+const ENV_CERTIFICATE: &str = "LUCENT_CERTIFICATE";
+const ENV_PRIVATE_KEY: &str = "LUCENT_PRIVATE_KEY";
+const H_REPLAY_NONCE: &str = "replay-nonce";
+const ERROR_BAD_NONCE: &str = "urn:ietf:params:acme:error:badNonce";
+const CHALLENGE_HTTP_01: &str = "http-01";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_POLLS: usize = 30;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_SET: u8 = 0x31;
+const DER_INTEGER: u8 = 0x02;
+const DER_BIT_STRING: u8 = 0x03;
+const DER_OCTET_STRING: u8 = 0x04;
+const DER_OID: u8 = 0x06;
+const DER_UTC_TIME: u8 = 0x17;
+const DER_CONTEXT_0: u8 = 0xa0;
+const DER_DNS_NAME: u8 = 0x82;
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_EXTENSION_REQUEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+type AcmeResult<T> = Result<T, String>;
+
+pub struct AcmeManager {
+    acme: Acme,
+    state: Arc<ServerState>,
+}
+
+impl AcmeManager {
+    pub fn new(acme: Acme, state: Arc<ServerState>) -> Self {
+        AcmeManager { acme, state }
+    }
+
+    pub async fn run(self) {
+        loop {
+            let (acme, state) = (self.acme.clone(), Arc::clone(&self.state));
+            if let Err(e) = task::spawn_blocking(move || renew_if_due(&acme, &state)).await {
+                log::warn(format!("Could not obtain a certificate for {}: {}!", self.acme.domains.join(", "), e));
+            }
+            task::sleep(Duration::from_secs(self.acme.check_interval)).await;
+        }
+    }
+}
+
+fn renew_if_due(acme: &Acme, state: &ServerState) -> AcmeResult<()> {
+    let dir = Path::new(&acme.storage).join(&acme.domains[0]);
+    let renew_at = SystemTime::now() + Duration::from_secs(acme.renew_before * SECONDS_PER_DAY);
+    let expiry = fs::read_to_string(dir.join(CERTIFICATE_FILE)).ok().and_then(|pem| certificate_expiry(&pem));
+    if expiry.map(|expiry| expiry > renew_at) == Some(true) {
+        return Ok(());
+    }
+
+    log::info(format!("Requesting a certificate for {}.", acme.domains.join(", ")));
+    let (certificate, private_key) = AcmeClient::new(acme, state)?.issue()?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    write_private(&dir.join(PRIVATE_KEY_FILE), private_key.as_bytes())?;
+    fs::write(dir.join(CERTIFICATE_FILE), certificate).map_err(|e| e.to_string())?;
+    log::info(format!("Stored a new certificate for {} in `{}`.", acme.domains.join(", "), dir.display()));
+    if let Err(e) = deploy(acme, &dir) {
+        log::warn(format!("Could not deploy the certificate for {}: {}!", acme.domains.join(", "), e));
+    }
+    Ok(())
+}
+
+fn deploy(acme: &Acme, dir: &Path) -> AcmeResult<()> {
+    let (program, args) = match acme.deploy_command.split_first() {
+        Some(command) => command,
+        _ => return Ok(()),
+    };
+    let status = Command::new(program)
+        .args(args)
+        .env(ENV_CERTIFICATE, dir.join(CERTIFICATE_FILE))
+        .env(ENV_PRIVATE_KEY, dir.join(PRIVATE_KEY_FILE))
+        .status()
+        .map_err(|e| format!("`{}` could not be run: {}", program, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("`{}` exited with {}", program, status))
+    }
+}
+
+struct AcmeClient<'a> {
+    acme: &'a Acme,
+    state: &'a ServerState,
+    agent: ureq::Agent,
+    rng: SystemRandom,
+    key: EcdsaKeyPair,
+    directory: Value,
+    nonce: Option<String>,
+    kid: Option<String>,
+}
+
+impl<'a> AcmeClient<'a> {
+    fn new(acme: &'a Acme, state: &'a ServerState) -> AcmeResult<Self> {
+        let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).user_agent(consts::SERVER_NAME_VERSION).build();
+        let rng = SystemRandom::new();
+        let key = load_account_key(acme, &rng)?;
+        let directory = agent.get(&acme.directory).call().map_err(|e| e.to_string())?;
+        let directory = parse_json(directory)?;
+        Ok(AcmeClient { acme, state, agent, rng, key, directory, nonce: None, kid: None })
+    }
+
+    fn issue(&mut self) -> AcmeResult<(String, String)> {
+        if !self.acme.agree_tos {
+            return Err("the terms of service have not been agreed to".to_string());
+        }
+        let contact = format!("mailto:{}", self.acme.email);
+        let account = json!({ "termsOfServiceAgreed": self.acme.agree_tos, "contact": [contact] });
+        let (_, kid) = self.post(&self.url("newAccount")?, Some(account))?;
+        self.kid = Some(kid.ok_or("the account has no URL")?);
+
+        let identifiers = self.acme.domains.iter().map(|domain| json!({ "type": "dns", "value": domain }));
+        let order = json!({ "identifiers": identifiers.collect::<Vec<_>>() });
+        let (order, order_url) = self.post(&self.url("newOrder")?, Some(order))?;
+        let order_url = order_url.ok_or("the order has no URL")?;
+        for authorization in order["authorizations"].as_array().cloned().unwrap_or_default() {
+            self.authorize(authorization.as_str().ok_or("authorization URL invalid")?)?;
+        }
+
+        let (csr, private_key) = certificate_request(&self.acme.domains, &self.rng)?;
+        let finalize = order["finalize"].as_str().ok_or("the order has no finalize URL")?;
+        self.post(finalize, Some(json!({ "csr": encode_url_safe(&csr) })))?;
+        let order = self.poll(&order_url)?;
+        let certificate = order["certificate"].as_str().ok_or("the order has no certificate URL")?;
+        let certificate = self.request(certificate, None)?.into_string().map_err(|e| e.to_string())?;
+        Ok((certificate, private_key))
+    }
+
+    fn authorize(&mut self, url: &str) -> AcmeResult<()> {
+        let (authorization, _) = self.post(url, None)?;
+        if authorization["status"] == "valid" {
+            return Ok(());
+        }
+        let challenges = authorization["challenges"].as_array().cloned().unwrap_or_default();
+        let challenge = challenges
+            .iter()
+            .find(|challenge| challenge["type"] == CHALLENGE_HTTP_01)
+            .ok_or("no http-01 challenge was offered")?;
+        let token = challenge["token"].as_str().ok_or("challenge token missing")?;
+        let challenge_url = challenge["url"].as_str().ok_or("challenge URL missing")?;
+
+        let thumbprint = encode_url_safe(&Sha256::digest(self.jwk().to_string().as_bytes()));
+        let key_authorization = format!("{}.{}", token, thumbprint);
+        self.state.acme_challenges.lock().unwrap().insert(token.to_string(), key_authorization);
+        let result = self.post(challenge_url, Some(json!({}))).and_then(|_| self.poll(url));
+        self.state.acme_challenges.lock().unwrap().remove(token);
+        result.map(|_| ())
+    }
+
+    fn poll(&mut self, url: &str) -> AcmeResult<Value> {
+        for _ in 0..MAX_POLLS {
+            let (resource, _) = self.post(url, None)?;
+            match resource["status"].as_str() {
+                Some("valid") => return Ok(resource),
+                Some("invalid") => return Err(format!("{} is invalid: {}", url, resource)),
+                _ => thread::sleep(POLL_INTERVAL),
+            }
+        }
+        Err(format!("{} did not become valid in time", url))
+    }
+
+    fn post(&mut self, url: &str, payload: Option<Value>) -> AcmeResult<(Value, Option<String>)> {
+        let response = self.request(url, payload)?;
+        let location = response.header(consts::H_LOCATION).map(str::to_string);
+        Ok((parse_json(response)?, location))
+    }
+
+    fn request(&mut self, url: &str, payload: Option<Value>) -> AcmeResult<ureq::Response> {
+        let mut retried = false;
+        loop {
+            let nonce = match self.nonce.take() {
+                Some(nonce) => nonce,
+                _ => self.new_nonce()?,
+            };
+            let body = self.sign(url, &nonce, payload.as_ref())?;
+            match self.agent.post(url).set(consts::H_CONTENT_TYPE, MEDIA_JOSE_JSON).send_string(&body) {
+                Ok(response) => {
+                    self.nonce = response.header(H_REPLAY_NONCE).map(str::to_string);
+                    return Ok(response);
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    self.nonce = response.header(H_REPLAY_NONCE).map(str::to_string);
+                    let problem = parse_json(response).unwrap_or_default();
+                    if problem["type"] == ERROR_BAD_NONCE && !retried {
+                        retried = true;
+                        continue;
+                    }
+                    return Err(format!("{} answered {}: {}", url, status, problem));
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+    }
+
+    fn new_nonce(&self) -> AcmeResult<String> {
+        let response = self.agent.head(&self.url("newNonce")?).call().map_err(|e| e.to_string())?;
+        response.header(H_REPLAY_NONCE).map(str::to_string).ok_or_else(|| "no nonce was given".to_string())
+    }
+
+    fn sign(&self, url: &str, nonce: &str, payload: Option<&Value>) -> AcmeResult<String> {
+        let mut protected = json!({ "alg": "ES256", "nonce": nonce, "url": url });
+        match &self.kid {
+            Some(kid) => protected["kid"] = json!(kid),
+            _ => protected["jwk"] = self.jwk(),
+        }
+        let protected = encode_url_safe(protected.to_string().as_bytes());
+        let payload = payload.map(|payload| encode_url_safe(payload.to_string().as_bytes())).unwrap_or_default();
+        let signature = self
+            .key
+            .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+            .map_err(|_| "could not sign the request".to_string())?;
+
+        let signature = encode_url_safe(signature.as_ref());
+        Ok(json!({ "protected": protected, "payload": payload, "signature": signature }).to_string())
+    }
+
+    fn jwk(&self) -> Value {
+        let point = self.key.public_key().as_ref();
+        json!({ "crv": "P-256", "kty": "EC", "x": encode_url_safe(&point[1..33]), "y": encode_url_safe(&point[33..]) })
+    }
+
+    fn url(&self, name: &str) -> AcmeResult<String> {
+        self.directory[name].as_str().map(str::to_string).ok_or_else(|| format!("the directory has no {}", name))
+    }
+}
+
+fn load_account_key(acme: &Acme, rng: &SystemRandom) -> AcmeResult<EcdsaKeyPair> {
+    let path = Path::new(&acme.storage).join(ACCOUNT_KEY_FILE);
+    let pkcs8 = match fs::read_to_string(&path) {
+        Ok(pem) => from_pem(&pem).ok_or_else(|| format!("`{}` is not a PEM file", path.display()))?,
+        _ => {
+            let pkcs8 = generate_key(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, rng)?;
+            fs::create_dir_all(&acme.storage).map_err(|e| e.to_string())?;
+            write_private(&path, to_pem(PEM_PRIVATE_KEY, &pkcs8).as_bytes())?;
+            pkcs8
+        }
+    };
+    EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8, rng)
+        .map_err(|e| format!("`{}` is not a P-256 key: {}", path.display(), e))
+}
+
+fn generate_key(algorithm: &'static EcdsaSigningAlgorithm, rng: &SystemRandom) -> AcmeResult<Vec<u8>> {
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(algorithm, rng).map_err(|_| "could not generate a key".to_string())?;
+    Ok(pkcs8.as_ref().to_vec())
+}
+
+fn certificate_request(domains: &[String], rng: &SystemRandom) -> AcmeResult<(Vec<u8>, String)> {
+    let algorithm = &signature::ECDSA_P256_SHA256_ASN1_SIGNING;
+    let pkcs8 = generate_key(algorithm, rng)?;
+    let key = EcdsaKeyPair::from_pkcs8(algorithm, &pkcs8, rng).map_err(|e| e.to_string())?;
+
+    let algorithm_id = der(DER_SEQUENCE, &[der(DER_OID, OID_EC_PUBLIC_KEY), der(DER_OID, OID_PRIME256V1)].concat());
+    let public_key = der(DER_SEQUENCE, &[algorithm_id, bit_string(key.public_key().as_ref())].concat());
+    let names = domains.iter().map(|domain| der(DER_DNS_NAME, domain.as_bytes())).collect::<Vec<_>>().concat();
+    let alt_names = der(DER_OCTET_STRING, &der(DER_SEQUENCE, &names));
+    let alt_names = der(DER_SEQUENCE, &[der(DER_OID, OID_SUBJECT_ALT_NAME), alt_names].concat());
+    let extensions = der(DER_SET, &der(DER_SEQUENCE, &alt_names));
+    let attributes = der(DER_SEQUENCE, &[der(DER_OID, OID_EXTENSION_REQUEST), extensions].concat());
+    let attributes = der(DER_CONTEXT_0, &attributes);
+    let info = der(DER_SEQUENCE, &[der(DER_INTEGER, &[0]), der(DER_SEQUENCE, &[]), public_key, attributes].concat());
+
+    let signature = key.sign(rng, &info).map_err(|_| "could not sign the certificate request".to_string())?;
+    let signature_algorithm = der(DER_SEQUENCE, &der(DER_OID, OID_ECDSA_WITH_SHA256));
+    let csr = der(DER_SEQUENCE, &[info, signature_algorithm, bit_string(signature.as_ref())].concat());
+    Ok((csr, to_pem(PEM_PRIVATE_KEY, &pkcs8)))
+}
+
+fn certificate_expiry(pem: &str) -> Option<SystemTime> {
+    let certificate = from_pem(pem)?;
+    let (_, certificate, _) = der_element(&certificate)?;
+    let (_, mut fields, _) = der_element(certificate)?;
+    if fields.first() == Some(&DER_CONTEXT_0) {
+        fields = der_element(fields)?.2;
+    }
+    for _ in 0..3 {
+        fields = der_element(fields)?.2;
+    }
+    let (_, validity, _) = der_element(fields)?;
+    let (tag, not_after, _) = der_element(der_element(validity)?.2)?;
+
+    let format = if tag == DER_UTC_TIME { "%y%m%d%H%M%SZ" } else { "%Y%m%d%H%M%SZ" };
+    let not_after = NaiveDateTime::parse_from_str(std::str::from_utf8(not_after).ok()?, format).ok()?;
+    Some(DateTime::<Utc>::from_utc(not_after, Utc).into())
+}
+
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    if contents.len() < 0x80 {
+        encoded.push(contents.len() as u8);
+    } else {
+        let length = contents.len().to_be_bytes();
+        let length = &length[length.iter().position(|byte| *byte != 0).unwrap_or(0)..];
+        encoded.push(0x80 | length.len() as u8);
+        encoded.extend(length);
+    }
+    encoded.extend(contents);
+    encoded
+}
+
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    der(DER_BIT_STRING, &[&[0], bytes].concat())
+}
+
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (tag, first) = (*data.first()?, *data.get(1)? as usize);
+    let (length, start) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let octets = first & 0x7f;
+        (data.get(2..2 + octets)?.iter().fold(0, |length, byte| length << 8 | *byte as usize), 2 + octets)
+    };
+    Some((tag, data.get(start..start + length)?, &data[start + length..]))
+}
+
+fn to_pem(label: &str, der: &[u8]) -> String {
+    let encoded = base64::encode(der);
+    let lines = encoded.as_bytes().chunks(64).map(String::from_utf8_lossy).collect::<Vec<_>>();
+    format!("-----BEGIN {0}-----\n{1}\n-----END {0}-----\n", label, lines.join("\n"))
+}
+
+fn from_pem(pem: &str) -> Option<Vec<u8>> {
+    let body = pem
+        .lines()
+        .skip_while(|line| !line.starts_with("-----BEGIN "))
+        .skip(1)
+        .take_while(|line| !line.starts_with("-----END "))
+        .collect::<String>();
+    base64::decode(body.trim()).ok()
+}
+
+fn encode_url_safe(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn parse_json(response: ureq::Response) -> AcmeResult<Value> {
+    let body = response.into_string().map_err(|e| e.to_string())?;
+    serde_json::from_str(&body).map_err(|e| e.to_string())
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> AcmeResult<()> {
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path);
+    file.as_mut().map_err(|e| e.to_string())?.write_all(contents).map_err(|e| format!("`{}`: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Self-signed certificates for example.com, valid until 2024-04-01 12:30:00 UTC (a UTCTime) and until
+    // 2060-01-01 00:00:00 UTC (a GeneralizedTime).
+    const CERTIFICATE_2024: &str = "-----BEGIN CERTIFICATE-----
+MIIBgjCCASegAwIBAgIUZSo+gGR5bf2XL8c2Wsa/l6iRv7gwCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLZXhhbXBsZS5jb20wHhcNMjQwMTAxMDAwMDAwWhcNMjQwNDAx
+MTIzMDAwWjAWMRQwEgYDVQQDDAtleGFtcGxlLmNvbTBZMBMGByqGSM49AgEGCCqG
+SM49AwEHA0IABBfAVlOG+PFqe4lFF3VHl3Ivzroo72DjEFGzqmrGoZsqOrslKKMp
+j9I+ytczQ+iwpjXZVWjG6NEURXZxsOGeNtajUzBRMB0GA1UdDgQWBBT4om2PjBLB
+zdfunSeLz/ckdevLHTAfBgNVHSMEGDAWgBT4om2PjBLBzdfunSeLz/ckdevLHTAP
+BgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQCRSsUXa+UHW8o3eYHk
+rgLBeGOEy8Jo+NdW8REznSlnWAIhAJxUcnEpnaxSKpX+x6qtGsXC9Q4ahYsEKbIc
+vO1uEvDC
+-----END CERTIFICATE-----
+";
+    const CERTIFICATE_2060: &str = "-----BEGIN CERTIFICATE-----
+MIIBhDCCASmgAwIBAgIUBwGQQEkDJzaFTb+Yn4BEFbymCCkwCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLZXhhbXBsZS5jb20wIBcNMjQwMTAxMDAwMDAwWhgPMjA2MDAx
+MDEwMDAwMDBaMBYxFDASBgNVBAMMC2V4YW1wbGUuY29tMFkwEwYHKoZIzj0CAQYI
+KoZIzj0DAQcDQgAEv4uP0OuqqAnwcJSVPAGFpETGPhWZ3BAouVKqqPTgDjODPsaQ
++RVn5tAtc6iA0xhvxRvzdbrm2/hHUjtK2M/ceaNTMFEwHQYDVR0OBBYEFDNUARrf
+2PPzvyL8d7BS62aF1Re3MB8GA1UdIwQYMBaAFDNUARrf2PPzvyL8d7BS62aF1Re3
+MA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSQAwRgIhALzxcV/8RuMEdl9u
+BK9l4uQzYuvsp+xnSY0vKM5Br0ynAiEA4adR9P+0zaZkrF9rf5/h+BTLjTJyci53
+/m2CUex8siw=
+-----END CERTIFICATE-----
+";
+
+    fn contains(data: &[u8], part: &[u8]) -> bool {
+        data.windows(part.len()).any(|window| window == part)
+    }
+
+    #[test]
+    fn der_lengths_use_the_shortest_form() {
+        assert_eq!(der(DER_INTEGER, &[0]), [DER_INTEGER, 1, 0]);
+        assert_eq!(der(DER_OCTET_STRING, &[7; 0x7f])[..2], [DER_OCTET_STRING, 0x7f]);
+        assert_eq!(der(DER_OCTET_STRING, &[7; 0x80])[..3], [DER_OCTET_STRING, 0x81, 0x80]);
+        assert_eq!(der(DER_OCTET_STRING, &[7; 300])[..4], [DER_OCTET_STRING, 0x82, 0x01, 0x2c]);
+
+        for length in &[0, 1, 0x7f, 0x80, 0xff, 0x100, 70_000] {
+            let contents = vec![7; *length];
+            let encoded = [der(DER_OCTET_STRING, &contents), vec![1, 2]].concat();
+            assert_eq!(der_element(&encoded), Some((DER_OCTET_STRING, &contents[..], &[1, 2][..])));
+        }
+        assert_eq!(der_element(&[DER_OCTET_STRING, 3, 0]), None);
+    }
+
+    #[test]
+    fn certificate_requests_are_signed_by_the_returned_key() {
+        let rng = SystemRandom::new();
+        let domains = vec!["example.com".to_string(), "www.example.com".to_string()];
+        let (csr, private_key) = certificate_request(&domains, &rng).unwrap();
+
+        let (tag, request, rest) = der_element(&csr).unwrap();
+        assert_eq!((tag, rest), (DER_SEQUENCE, &[][..]));
+        let (tag, info, rest) = der_element(request).unwrap();
+        let encoded_info = &request[..request.len() - rest.len()];
+        assert_eq!(tag, DER_SEQUENCE);
+        let (tag, signature_algorithm, rest) = der_element(rest).unwrap();
+        assert_eq!((tag, signature_algorithm), (DER_SEQUENCE, &der(DER_OID, OID_ECDSA_WITH_SHA256)[..]));
+        let (tag, signature, rest) = der_element(rest).unwrap();
+        assert_eq!((tag, signature[0], rest), (DER_BIT_STRING, 0, &[][..]));
+
+        let (_, version, info) = der_element(info).unwrap();
+        let (_, subject, info) = der_element(info).unwrap();
+        let (_, public_key_info, attributes) = der_element(info).unwrap();
+        assert_eq!((version, subject), (&[0][..], &[][..]));
+        let (_, algorithm_id, public_key) = der_element(public_key_info).unwrap();
+        assert!(contains(algorithm_id, OID_EC_PUBLIC_KEY) && contains(algorithm_id, OID_PRIME256V1));
+        let (tag, public_key, _) = der_element(public_key).unwrap();
+        assert_eq!((tag, public_key[0]), (DER_BIT_STRING, 0));
+
+        let (tag, attributes, _) = der_element(attributes).unwrap();
+        assert_eq!(tag, DER_CONTEXT_0);
+        assert!(contains(attributes, OID_EXTENSION_REQUEST) && contains(attributes, OID_SUBJECT_ALT_NAME));
+        for domain in &domains {
+            assert!(contains(attributes, &der(DER_DNS_NAME, domain.as_bytes())));
+        }
+
+        let pkcs8 = from_pem(&private_key).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8, &rng).unwrap();
+        assert_eq!(key.public_key().as_ref(), &public_key[1..]);
+        let verifier = signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, &public_key[1..]);
+        assert!(verifier.verify(encoded_info, &signature[1..]).is_ok());
+    }
+
+    #[test]
+    fn deploy_command_gets_the_stored_paths() {
+        let dir = std::env::temp_dir().join(format!("lucent-acme-deploy-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let command = format!("echo \"$LUCENT_CERTIFICATE $LUCENT_PRIVATE_KEY\" > {}", dir.join("deployed").display());
+        let settings = "{ domains: [example.com], email: admin@example.com, agree_tos: true }";
+        let mut acme = serde_yaml::from_str::<Acme>(settings).unwrap();
+
+        acme.deploy_command = vec!["sh".to_string(), "-c".to_string(), command];
+        let result = deploy(&acme, &dir);
+        let deployed = fs::read_to_string(dir.join("deployed"));
+        acme.deploy_command = vec!["false".to_string()];
+        let failed = deploy(&acme, &dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_ok());
+        let expected = format!("{} {}\n", dir.join(CERTIFICATE_FILE).display(), dir.join(PRIVATE_KEY_FILE).display());
+        assert_eq!(deployed.unwrap(), expected);
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn certificate_expiry_is_read_from_not_after() {
+        let expiry = |seconds| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+        assert_eq!(certificate_expiry(CERTIFICATE_2024), expiry(1_711_974_600));
+        assert_eq!(certificate_expiry(CERTIFICATE_2060), expiry(2_840_140_800));
+        assert_eq!(certificate_expiry("not a certificate"), None);
+    }
+}
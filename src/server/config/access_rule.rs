@@ -0,0 +1,34 @@
+use std::net::IpAddr;
+
+use serde::Deserialize;
+
+use crate::server::config::{AccessOrder, AccessPolicy};
+use crate::server::config::ip_range::IpRange;
+
+#[derive(Clone, Deserialize)]
+pub struct AccessRule {
+    #[serde(default)]
+    pub allow: Vec<IpRange>,
+    #[serde(default)]
+    pub deny: Vec<IpRange>,
+    #[serde(default)]
+    pub order: AccessOrder,
+    #[serde(default)]
+    pub default: Option<AccessPolicy>,
+}
+
+impl AccessRule {
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        let allowed = self.allow.iter().any(|range| range.contains(ip));
+        let denied = self.deny.iter().any(|range| range.contains(ip));
+
+        match (allowed, denied, self.order) {
+            (true, true, AccessOrder::AllowDeny) | (false, true, _) => false,
+            (true, _, _) => true,
+            _ => match self.default {
+                Some(policy) => policy == AccessPolicy::Allow,
+                _ => self.order == AccessOrder::DenyAllow,
+            },
+        }
+    }
+}
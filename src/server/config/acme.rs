@@ -0,0 +1,39 @@
+use serde::Deserialize;
+
+const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+const DEFAULT_STORAGE: &str = "acme";
+const DEFAULT_RENEW_BEFORE: u64 = 30;
+const DEFAULT_CHECK_INTERVAL: u64 = 43_200;
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct Acme {
+    pub domains: Vec<String>,
+    pub email: String,
+    pub agree_tos: bool,
+    #[serde(default = "default_directory")]
+    pub directory: String,
+    #[serde(default = "default_storage")]
+    pub storage: String,
+    #[serde(default = "default_renew_before")]
+    pub renew_before: u64,
+    #[serde(default = "default_check_interval")]
+    pub check_interval: u64,
+    #[serde(default)]
+    pub deploy_command: Vec<String>,
+}
+
+fn default_directory() -> String {
+    LETS_ENCRYPT_DIRECTORY.to_string()
+}
+
+fn default_storage() -> String {
+    DEFAULT_STORAGE.to_string()
+}
+
+fn default_renew_before() -> u64 {
+    DEFAULT_RENEW_BEFORE
+}
+
+fn default_check_interval() -> u64 {
+    DEFAULT_CHECK_INTERVAL
+}
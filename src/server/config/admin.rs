@@ -0,0 +1,14 @@
+use std::net::SocketAddr;
+
+use serde::Deserialize;
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct Admin {
+    pub address: SocketAddr,
+}
+
+impl Admin {
+    pub fn is_local(&self) -> bool {
+        self.address.ip().is_loopback()
+    }
+}
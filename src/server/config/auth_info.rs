@@ -1,5 +1,5 @@
 use serde::{Deserialize, Deserializer};
-use serde::de::{Error, SeqAccess, Visitor};
+use serde::de::{Error, MapAccess, SeqAccess, Visitor};
 use serde::export::{fmt, Formatter};
 
 #[derive(Clone)]
@@ -12,13 +12,15 @@ pub struct Credentials {
 pub struct AuthInfo {
     pub realm: String,
     pub credentials: Vec<Credentials>,
+    pub backend: Option<String>,
+    pub htpasswd: Option<String>,
 }
 
 impl<'a> Deserialize<'a> for AuthInfo {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where D: Deserializer<'a>
     {
-        deserializer.deserialize_seq(AuthInfoStringVisitor)
+        deserializer.deserialize_any(AuthInfoStringVisitor)
     }
 }
 
@@ -28,17 +30,135 @@ impl<'a> Visitor<'a> for AuthInfoStringVisitor {
     type Value = AuthInfo;
 
     fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
-        formatter.write_str("Sequence of two strings, a realm and a semicolon (`;`) separated list of credentials.")
+        formatter.write_str(
+            "Sequence of two strings, a realm and a semicolon (`;`) separated list of credentials, or a mapping of \
+            `realm` and one of `credentials`, `backend` or `htpasswd`.",
+        )
     }
 
     fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, <A as SeqAccess<'a>>::Error>
         where A: SeqAccess<'a>
     {
-        let err = || A::Error::custom(format!("Authentication information invalid!"));
-        let realm = seq.next_element::<String>()?.ok_or(err())?;
-        let credentials_str = seq.next_element::<String>()?.ok_or(err())?;
-        let credentials = parse_credentials(&credentials_str).ok_or(err())?;
-        Ok(AuthInfo { realm, credentials })
+        let err = || A::Error::custom("Authentication information invalid!");
+        let realm = seq.next_element::<String>()?.ok_or_else(err)?;
+        let credentials_str = seq.next_element::<String>()?.ok_or_else(err)?;
+        let credentials = parse_credentials(&credentials_str).ok_or_else(err)?;
+        Ok(AuthInfo { realm, credentials, backend: None, htpasswd: None })
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, <A as MapAccess<'a>>::Error>
+        where A: MapAccess<'a>
+    {
+        let (mut realm, mut credentials, mut backend, mut htpasswd) = (None, None, None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "realm" => realm = Some(map.next_value::<String>()?),
+                "credentials" => credentials = Some(map.next_value::<String>()?),
+                "backend" => backend = Some(map.next_value::<String>()?),
+                "htpasswd" => htpasswd = Some(map.next_value::<String>()?),
+                _ => return Err(A::Error::unknown_field(&key, &["realm", "credentials", "backend", "htpasswd"])),
+            }
+        }
+
+        let err = || A::Error::custom("Authentication information invalid!");
+        let realm = realm.ok_or_else(|| A::Error::missing_field("realm"))?;
+        match (credentials, backend, htpasswd) {
+            (Some(credentials), None, None) => {
+                let credentials = parse_credentials(&credentials).ok_or_else(err)?;
+                Ok(AuthInfo { realm, credentials, backend: None, htpasswd: None })
+            }
+            (None, backend @ Some(_), None) => Ok(AuthInfo { realm, credentials: vec![], backend, htpasswd: None }),
+            (None, None, htpasswd @ Some(_)) => Ok(AuthInfo { realm, credentials: vec![], backend: None, htpasswd }),
+            _ => Err(A::Error::custom("Exactly one of `credentials`, `backend` and `htpasswd` must be given!")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DigestAlgorithm {
+    Md5,
+    Sha256,
+}
+
+#[derive(Clone)]
+pub struct DigestAuthInfo {
+    pub realm: String,
+    pub algorithm: DigestAlgorithm,
+    pub credentials: Vec<Credentials>,
+}
+
+impl<'a> Deserialize<'a> for DigestAuthInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'a>
+    {
+        deserializer.deserialize_seq(DigestAuthInfoStringVisitor)
+    }
+}
+
+pub struct DigestAuthInfoStringVisitor;
+
+impl<'a> Visitor<'a> for DigestAuthInfoStringVisitor {
+    type Value = DigestAuthInfo;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("Sequence of three strings, a realm, an algorithm, and a list of credentials.")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, <A as SeqAccess<'a>>::Error>
+        where A: SeqAccess<'a>
+    {
+        let err = || A::Error::custom("Digest authentication information invalid!");
+        let realm = seq.next_element::<String>()?.ok_or_else(err)?;
+        let algorithm = match seq.next_element::<String>()?.ok_or_else(err)?.to_ascii_uppercase().as_str() {
+            "MD5" => DigestAlgorithm::Md5,
+            "SHA-256" => DigestAlgorithm::Sha256,
+            _ => return Err(err()),
+        };
+        let credentials_str = seq.next_element::<String>()?.ok_or_else(err)?;
+        let credentials = parse_credentials(&credentials_str).ok_or_else(err)?;
+        Ok(DigestAuthInfo { realm, algorithm, credentials })
+    }
+}
+
+#[derive(Clone)]
+pub struct BearerAuthInfo {
+    pub realm: String,
+    pub token_hashes: Vec<String>,
+}
+
+impl<'a> Deserialize<'a> for BearerAuthInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'a>
+    {
+        deserializer.deserialize_seq(BearerAuthInfoStringVisitor)
+    }
+}
+
+pub struct BearerAuthInfoStringVisitor;
+
+impl<'a> Visitor<'a> for BearerAuthInfoStringVisitor {
+    type Value = BearerAuthInfo;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("Sequence of two strings, a realm and a semicolon (`;`) separated list of token hashes.")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, <A as SeqAccess<'a>>::Error>
+        where A: SeqAccess<'a>
+    {
+        let err = || A::Error::custom("Bearer authentication information invalid!");
+        let realm = seq.next_element::<String>()?.ok_or_else(err)?;
+        let token_hashes = seq
+            .next_element::<String>()?
+            .ok_or_else(err)?
+            .split(';')
+            .map(|hash| hash.trim().to_string())
+            .filter(|hash| !hash.is_empty())
+            .collect::<Vec<_>>();
+        if token_hashes.is_empty() {
+            return Err(err());
+        }
+        Ok(BearerAuthInfo { realm, token_hashes })
     }
 }
 
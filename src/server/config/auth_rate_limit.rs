@@ -0,0 +1,8 @@
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct AuthRateLimit {
+    pub max_failures: u32,
+    pub window: u64,
+    pub lockout: u64,
+}
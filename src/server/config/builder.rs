@@ -0,0 +1,90 @@
+use serde_yaml::{Mapping, Value};
+
+use crate::server::config::{Config, ConfigError, VIRTUAL_HOSTS_KEY};
+
+const REQUIRED_TABLES: &[&str] = &["cgi_executors", "routing_table", "basic_auth"];
+
+#[derive(Clone)]
+pub struct ConfigBuilder {
+    settings: Mapping,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        ConfigBuilder { settings: Mapping::new() }
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Into<Value>) {
+        self.settings.insert(Value::from(name), value.into());
+    }
+
+    pub fn with(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    pub fn with_file_root(self, file_root: &str) -> Self {
+        self.with("file_root", file_root)
+    }
+
+    pub fn with_template_root(self, template_root: &str) -> Self {
+        self.with("template_root", template_root)
+    }
+
+    pub fn with_address(mut self, address: &str) -> Self {
+        match self.settings.get_mut(&Value::from("addresses")) {
+            Some(Value::Sequence(addresses)) => addresses.push(Value::from(address)),
+            _ => self.set("addresses", vec![address]),
+        }
+        self
+    }
+
+    pub fn with_route(self, route: &str, replacement: &str) -> Self {
+        self.with_entry("routing_table", route, replacement)
+    }
+
+    pub fn with_basic_auth(self, route: &str, realm: &str, credentials: &str) -> Self {
+        self.with_entry("basic_auth", route, vec![realm, credentials])
+    }
+
+    pub fn with_cgi_executor(self, extension: &str, executor: &str) -> Self {
+        self.with_entry("cgi_executors", extension, executor)
+    }
+
+    pub fn with_virtual_host(self, name: &str, host: ConfigBuilder) -> Self {
+        self.with_entry(VIRTUAL_HOSTS_KEY, name, host.settings)
+    }
+
+    pub fn with_entry(mut self, table: &str, key: &str, value: impl Into<Value>) -> Self {
+        match self.settings.get_mut(&Value::from(table)) {
+            Some(Value::Mapping(entries)) => {
+                entries.insert(Value::from(key), value.into());
+            }
+            _ => {
+                let mut entries = Mapping::new();
+                entries.insert(Value::from(key), value.into());
+                self.set(table, entries);
+            }
+        }
+        self
+    }
+
+    pub fn into_settings(mut self) -> Mapping {
+        for table in REQUIRED_TABLES {
+            if !self.settings.contains_key(&Value::from(*table)) {
+                self.set(table, Mapping::new());
+            }
+        }
+        self.settings
+    }
+
+    pub fn build(self) -> Result<Config, ConfigError> {
+        Config::from_settings(self.into_settings())
+    }
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,54 @@
+use serde::Deserialize;
+
+use crate::consts;
+
+const HASHED_ASSET_MAX_AGE: u64 = 31_536_000;
+
+#[derive(Clone, Default, Deserialize)]
+pub struct CachePolicy {
+    #[serde(default)]
+    pub max_age: Option<u64>,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub immutable: bool,
+    #[serde(default)]
+    pub no_cache: bool,
+    #[serde(default)]
+    pub no_store: bool,
+}
+
+impl CachePolicy {
+    pub fn default_for(path: &str) -> Option<Self> {
+        let file_name = &path[path.rfind('/').map(|index| index + 1).unwrap_or(0)..];
+        let is_hashed = file_name
+            .split(&['.', '-'][..])
+            .any(|part| part.len() >= 8 && part.chars().all(|c| c.is_ascii_hexdigit()));
+
+        if file_name.ends_with(".html") || file_name.ends_with(".htm") {
+            Some(CachePolicy { no_cache: true, ..Default::default() })
+        } else if is_hashed {
+            Some(CachePolicy { max_age: Some(HASHED_ASSET_MAX_AGE), immutable: true, ..Default::default() })
+        } else {
+            None
+        }
+    }
+
+    pub fn directives(&self) -> Vec<String> {
+        let mut directives = vec![];
+        if self.no_store {
+            directives.push(consts::H_CACHE_NO_STORE.to_string());
+        }
+        if self.no_cache {
+            directives.push(consts::H_CACHE_NO_CACHE.to_string());
+        }
+        directives.push(if self.private { consts::H_CACHE_PRIVATE } else { consts::H_CACHE_PUBLIC }.to_string());
+        if let Some(max_age) = self.max_age {
+            directives.push(format!("{}={}", consts::H_CACHE_MAX_AGE, max_age));
+        }
+        if self.immutable {
+            directives.push(consts::H_CACHE_IMMUTABLE.to_string());
+        }
+        directives
+    }
+}
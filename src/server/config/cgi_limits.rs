@@ -0,0 +1,17 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Default, Deserialize)]
+pub struct CgiLimits {
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub max_output: Option<usize>,
+}
+
+impl CgiLimits {
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+}
@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+#[derive(Clone, Default, Deserialize)]
+pub struct ConnectProxy {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub allowed: Vec<String>,
+}
+
+impl ConnectProxy {
+    pub fn allows(&self, host: &str, port: u16) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.allowed.iter().any(|allowed| {
+            let (host_pattern, port_pattern) = match allowed.rfind(':') {
+                Some(index) => (&allowed[..index], &allowed[index + 1..]),
+                _ => return false,
+            };
+            let host_matches = match host_pattern.strip_prefix('*') {
+                Some(suffix) => host.len() > suffix.len() && host.ends_with(&suffix.to_ascii_lowercase()),
+                _ => host.eq_ignore_ascii_case(host_pattern),
+            };
+            host_matches && (port_pattern == "*" || port_pattern.parse::<u16>().ok() == Some(port))
+        })
+    }
+}
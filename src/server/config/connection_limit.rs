@@ -0,0 +1,10 @@
+use serde::Deserialize;
+
+use crate::server::config::ConnectionLimitAction;
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+pub struct ConnectionLimit {
+    pub max: usize,
+    #[serde(default)]
+    pub on_limit: ConnectionLimitAction,
+}
@@ -0,0 +1,18 @@
+use serde::Deserialize;
+
+use crate::server::config::route_spec::RouteSpec;
+
+#[derive(Clone, Default, Deserialize)]
+pub struct Downloads {
+    #[serde(default)]
+    pub routes: Vec<RouteSpec>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+}
+
+impl Downloads {
+    pub fn is_forced(&self, target: &str, file_ext: &str) -> bool {
+        self.extensions.iter().any(|ext| ext.eq_ignore_ascii_case(file_ext))
+            || self.routes.iter().any(|RouteSpec(rule_regex)| rule_regex.is_match(target))
+    }
+}
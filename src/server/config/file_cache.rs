@@ -0,0 +1,20 @@
+use serde::Deserialize;
+
+const DEFAULT_MAX_SIZE: usize = 64 * 1_024 * 1_024;
+const DEFAULT_MAX_FILE_SIZE: usize = 1_024 * 1_024;
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct FileCache {
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: usize,
+}
+
+fn default_max_size() -> usize {
+    DEFAULT_MAX_SIZE
+}
+
+fn default_max_file_size() -> usize {
+    DEFAULT_MAX_FILE_SIZE
+}
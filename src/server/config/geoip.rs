@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::server::geoip::GeoIpDatabase;
+
+#[derive(Clone, Deserialize)]
+pub struct GeoIp {
+    pub database: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default = "default_allow_unknown")]
+    pub allow_unknown: bool,
+    #[serde(skip)]
+    pub reader: Option<Arc<GeoIpDatabase>>,
+}
+
+fn default_allow_unknown() -> bool {
+    true
+}
+
+impl GeoIp {
+    pub fn load(&mut self, loaded: Option<&GeoIp>) -> Result<(), String> {
+        self.reader = match loaded.filter(|loaded| loaded.database == self.database) {
+            Some(loaded) => loaded.reader.clone(),
+            _ => Some(Arc::new(GeoIpDatabase::open(&self.database)?)),
+        };
+        Ok(())
+    }
+
+    pub fn allows(&self, country: Option<&str>) -> bool {
+        let listed = |countries: &[String], country: &str| countries.iter().any(|c| c.eq_ignore_ascii_case(country));
+        match country {
+            Some(country) => !listed(&self.deny, country) && (self.allow.is_empty() || listed(&self.allow, country)),
+            _ => self.allow_unknown,
+        }
+    }
+}
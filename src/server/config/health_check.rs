@@ -0,0 +1,15 @@
+use serde::Deserialize;
+
+const DEFAULT_PATH: &str = "/healthz";
+
+#[derive(Clone, Deserialize)]
+pub struct HealthCheck {
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default)]
+    pub details: bool,
+}
+
+fn default_path() -> String {
+    DEFAULT_PATH.to_string()
+}
@@ -0,0 +1,30 @@
+use serde::Deserialize;
+
+const DEFAULT_MAX_AGE: u64 = 31_536_000;
+
+#[derive(Clone, Deserialize)]
+pub struct Hsts {
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+    #[serde(default)]
+    pub include_subdomains: bool,
+    #[serde(default)]
+    pub preload: bool,
+}
+
+fn default_max_age() -> u64 {
+    DEFAULT_MAX_AGE
+}
+
+impl Hsts {
+    pub fn header_value(&self) -> String {
+        let mut value = format!("max-age={}", self.max_age);
+        if self.include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.preload {
+            value.push_str("; preload");
+        }
+        value
+    }
+}
@@ -0,0 +1,89 @@
+use std::net::IpAddr;
+
+use serde::{de, Deserialize, Deserializer};
+use serde::de::Visitor;
+use serde::export::{fmt, Formatter};
+
+#[derive(Clone, Copy)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_length: u32,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().filter(|_| self.network.is_ipv4()).map(IpAddr::V4).unwrap_or(ip.into()),
+            ip => ip,
+        };
+
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_length).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_length).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Deserialize<'a> for IpRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'a>
+    {
+        deserializer.deserialize_str(IpRangeStringVisitor)
+    }
+}
+
+struct IpRangeStringVisitor;
+
+impl<'a> Visitor<'a> for IpRangeStringVisitor {
+    type Value = IpRange;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("String value containing an IP address or CIDR range.")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where E: de::Error
+    {
+        let mut parts = value.splitn(2, '/');
+        let network = parts.next().and_then(|network| network.parse::<IpAddr>().ok());
+        let max_length = if network.map(|network| network.is_ipv4()) == Some(true) { 32 } else { 128 };
+        let prefix_length = parts.next().map(|length| length.parse::<u32>().ok()).unwrap_or(Some(max_length));
+
+        match (network, prefix_length) {
+            (Some(network), Some(prefix_length)) if prefix_length <= max_length => Ok(IpRange { network, prefix_length }),
+            _ => Err(E::custom(format!("IP range invalid: {}", value))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(range: &str, ip: &str) -> bool {
+        serde_yaml::from_str::<IpRange>(range).unwrap().contains(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn mapped_ipv6_addresses_match_ipv4_ranges() {
+        assert!(contains("10.0.0.0/8", "::ffff:10.1.2.3"));
+        assert!(!contains("10.0.0.0/8", "::ffff:11.1.2.3"));
+        assert!(contains("127.0.0.1", "::ffff:127.0.0.1"));
+    }
+
+    #[test]
+    fn other_ipv6_addresses_do_not_match_ipv4_ranges() {
+        assert!(!contains("0.0.0.0/0", "::1"));
+        assert!(!contains("0.0.0.1", "::1"));
+        assert!(!contains("10.0.0.0/8", "::a01:203"));
+        assert!(contains("::/96", "::a01:203"));
+        assert!(contains("::1", "::1"));
+    }
+}
@@ -0,0 +1,20 @@
+use serde::Deserialize;
+
+const DEFAULT_TTL: u64 = 10;
+const DEFAULT_MAX_SIZE: usize = 16 * 1_024 * 1_024;
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct ListingCache {
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+}
+
+fn default_ttl() -> u64 {
+    DEFAULT_TTL
+}
+
+fn default_max_size() -> usize {
+    DEFAULT_MAX_SIZE
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use linked_hash_map::LinkedHashMap;
+use serde::Deserialize;
+
+use crate::{consts, util};
+use crate::server::config::route_spec::RouteSpec;
+
+#[derive(Clone, Deserialize)]
+pub struct MediaTypes {
+    #[serde(default)]
+    pub overrides: HashMap<String, String>,
+    #[serde(default = "default_media_type")]
+    pub default: String,
+    #[serde(default = "default_charset")]
+    pub charset: Option<String>,
+    #[serde(default)]
+    pub charsets: HashMap<String, String>,
+    #[serde(default)]
+    pub sniff: bool,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub routes: LinkedHashMap<RouteSpec, RouteMediaTypes>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct RouteMediaTypes {
+    #[serde(default)]
+    pub charset: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+fn default_media_type() -> String {
+    consts::H_MEDIA_BINARY.to_string()
+}
+
+fn default_charset() -> Option<String> {
+    Some(consts::CHARSET_UTF_8.to_string())
+}
+
+impl Default for MediaTypes {
+    fn default() -> Self {
+        MediaTypes {
+            overrides: HashMap::new(),
+            default: default_media_type(),
+            charset: default_charset(),
+            charsets: HashMap::new(),
+            sniff: false,
+            language: None,
+            routes: LinkedHashMap::new(),
+        }
+    }
+}
+
+impl MediaTypes {
+    pub fn by_ext(&self, ext: &str) -> Option<String> {
+        let ext = ext.to_ascii_lowercase();
+        match self.overrides.get(&ext) {
+            Some(media_type) => Some(media_type.clone()),
+            _ => match util::media_type_by_ext(&ext) {
+                consts::H_MEDIA_BINARY => None,
+                media_type => Some(media_type.to_string()),
+            },
+        }
+    }
+
+    pub fn with_charset(&self, media_type: &str, target: &str, ext: &str, content: &[u8]) -> String {
+        if !util::is_text_media_type(media_type) || media_type.contains(';') {
+            return media_type.to_string();
+        }
+
+        let charset = util::charset_by_bom(content)
+            .or_else(|| self.charsets.get(&ext.to_ascii_lowercase()).map(String::as_str))
+            .or_else(|| self.route_for(target).and_then(|route| route.charset.as_deref()))
+            .or(self.charset.as_deref());
+        match charset {
+            Some(charset) => format!("{}; charset={}", media_type, charset),
+            _ => media_type.to_string(),
+        }
+    }
+
+    pub fn language_for(&self, target: &str) -> Option<&str> {
+        self.route_for(target).and_then(|route| route.language.as_deref()).or(self.language.as_deref())
+    }
+
+    fn route_for(&self, target: &str) -> Option<&RouteMediaTypes> {
+        self.routes.iter().find(|(RouteSpec(rule_regex), _)| rule_regex.is_match(target)).map(|(_, route)| route)
+    }
+}
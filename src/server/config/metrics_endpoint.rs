@@ -0,0 +1,25 @@
+use std::net::IpAddr;
+
+use serde::Deserialize;
+
+use crate::server::config::ip_range::IpRange;
+
+const DEFAULT_PATH: &str = "/metrics";
+
+#[derive(Clone, Deserialize)]
+pub struct MetricsEndpoint {
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default)]
+    pub allow: Vec<IpRange>,
+}
+
+fn default_path() -> String {
+    DEFAULT_PATH.to_string()
+}
+
+impl MetricsEndpoint {
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        self.allow.is_empty() || self.allow.iter().any(|range| range.contains(ip))
+    }
+}
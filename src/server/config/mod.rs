@@ -1,30 +1,572 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+use std::fmt::{self, Display, Formatter};
 
 use async_std::fs;
 use linked_hash_map::LinkedHashMap;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use serde_yaml::{Mapping, Value};
 
-use crate::server::config::auth_info::AuthInfo;
+use crate::consts;
+use crate::http::parser::ParseLimits;
+use crate::http::request::Method;
+use crate::server::config::admin::Admin;
+use crate::server::config::auth_info::{AuthInfo, BearerAuthInfo, DigestAuthInfo};
+use crate::server::config::auth_rate_limit::AuthRateLimit;
+use crate::server::config::access_rule::AccessRule;
+use crate::server::config::acme::Acme;
+use crate::server::config::builder::ConfigBuilder;
+use crate::server::config::cache_policy::CachePolicy;
+use crate::server::config::cgi_limits::CgiLimits;
+use crate::server::config::connect_proxy::ConnectProxy;
+use crate::server::config::connection_limit::ConnectionLimit;
+use crate::server::config::downloads::Downloads;
+use crate::server::config::file_cache::FileCache;
+use crate::server::config::geoip::GeoIp;
+use crate::server::config::health_check::HealthCheck;
+use crate::server::config::hsts::Hsts;
+use crate::server::config::media_types::MediaTypes;
+use crate::server::config::ip_range::IpRange;
+use crate::server::config::listing_cache::ListingCache;
+use crate::server::config::metrics_endpoint::MetricsEndpoint;
+use crate::server::config::proxy_protocol::ProxyProtocol;
+use crate::server::config::request_id::RequestId;
+use crate::server::config::request_limits::RequestLimits;
 use crate::server::config::route_replacement::RouteReplacement;
 use crate::server::config::route_spec::RouteSpec;
+use crate::server::config::tcp_options::TcpOptions;
+use crate::server::config::throttle::Throttle;
+use crate::server::config::timeouts::Timeouts;
+use crate::server::config::trace::Trace;
+use crate::server::config::unix_socket::UnixSocket;
+use crate::server::config::workers::Workers;
+use crate::server::config::writable_route::WritableRoute;
 
 pub mod route_spec;
 pub mod route_replacement;
 
+pub mod access_rule;
+pub mod acme;
+pub mod admin;
 pub mod auth_info;
+pub mod auth_rate_limit;
+pub mod builder;
+pub mod cache_policy;
+pub mod cgi_limits;
+pub mod connect_proxy;
+pub mod connection_limit;
+pub mod downloads;
+pub mod file_cache;
+pub mod geoip;
+pub mod health_check;
+pub mod hsts;
+pub mod ip_range;
+pub mod listing_cache;
+pub mod media_types;
+pub mod metrics_endpoint;
+pub mod proxy_protocol;
+pub mod request_id;
+pub mod request_limits;
+pub mod tcp_options;
+pub mod throttle;
+pub mod timeouts;
+pub mod trace;
+pub mod unix_socket;
+pub mod workers;
+pub mod writable_route;
+
+const VIRTUAL_HOSTS_KEY: &str = "virtual_hosts";
+const TOML_EXTENSION: &str = ".toml";
 
 #[derive(Clone, Deserialize)]
 pub struct Config {
     pub file_root: String,
     pub template_root: String,
-    pub address: String,
+    #[serde(default)]
+    pub reload_templates: bool,
+    #[serde(default, alias = "address", deserialize_with = "deserialize_addresses")]
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub reuse_port: bool,
+    #[serde(default)]
+    pub unix_socket: Option<UnixSocket>,
+    #[serde(default)]
+    pub proxy_protocol: Option<ProxyProtocol>,
+    #[serde(default)]
+    pub connection_limit: Option<ConnectionLimit>,
+    #[serde(default)]
+    pub workers: Workers,
+    #[serde(default)]
+    pub tcp: TcpOptions,
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpRange>,
+    #[serde(default)]
+    pub request_id: Option<RequestId>,
+    #[serde(default)]
+    pub request_timing: bool,
+    #[serde(default)]
+    pub health_check: Option<HealthCheck>,
+    #[serde(default)]
+    pub metrics: Option<MetricsEndpoint>,
+    #[serde(default)]
+    pub admin: Option<Admin>,
+    #[serde(default = "default_http_09")]
+    pub http_09: Http09Mode,
+    #[serde(default)]
+    pub request_limits: RequestLimits,
+    #[serde(default)]
+    pub timeouts: Timeouts,
     pub cgi_executors: HashMap<String, String>,
+    #[serde(default)]
+    pub cgi_limits: CgiLimits,
     pub routing_table: LinkedHashMap<RouteSpec, RouteReplacement>,
-    pub basic_auth: HashMap<RouteSpec, AuthInfo>,
+    pub basic_auth: LinkedHashMap<RouteSpec, AuthInfo>,
+    #[serde(default)]
+    pub digest_auth: LinkedHashMap<RouteSpec, DigestAuthInfo>,
+    #[serde(default)]
+    pub bearer_auth: LinkedHashMap<RouteSpec, BearerAuthInfo>,
+    #[serde(default)]
+    pub auth_precedence: AuthPrecedence,
+    #[serde(default)]
+    pub access_control: LinkedHashMap<RouteSpec, AccessRule>,
+    #[serde(default)]
+    pub geoip: Option<GeoIp>,
+    #[serde(default)]
+    pub auth_rate_limit: Option<AuthRateLimit>,
+    #[serde(default)]
+    pub websocket_backends: LinkedHashMap<RouteSpec, String>,
+    #[serde(default)]
+    pub body_limits: LinkedHashMap<RouteSpec, HashMap<String, usize>>,
+    #[serde(default = "default_stream_chunk_size")]
+    pub stream_chunk_size: usize,
+    #[serde(default)]
+    pub throttle: Throttle,
+    #[serde(default = "default_index_files")]
+    pub index_files: Vec<String>,
+    #[serde(default)]
+    pub file_source: FileSourceMode,
+    #[serde(default)]
+    pub symlinks: SymlinkPolicy,
+    #[serde(default)]
+    pub dir_listing_show_hidden: bool,
+    #[serde(default)]
+    pub dir_listings: LinkedHashMap<RouteSpec, bool>,
+    #[serde(default)]
+    pub listing_cache: Option<ListingCache>,
+    #[serde(default)]
+    pub file_cache: Option<FileCache>,
+    #[serde(default)]
+    pub mmap_threshold: Option<u64>,
+    #[serde(default)]
+    pub webdav: LinkedHashMap<RouteSpec, bool>,
+    #[serde(default)]
+    pub trace: Trace,
+    #[serde(default)]
+    pub connect_proxy: ConnectProxy,
+    #[serde(default)]
+    pub writable_routes: LinkedHashMap<RouteSpec, WritableRoute>,
+    #[serde(default)]
+    pub content_negotiation: bool,
+    #[serde(default)]
+    pub precompressed_files: bool,
+    #[serde(default)]
+    pub media_types: MediaTypes,
+    #[serde(default)]
+    pub cache_control: LinkedHashMap<RouteSpec, CachePolicy>,
+    #[serde(default)]
+    pub downloads: Downloads,
+    #[serde(default)]
+    pub security_headers: bool,
+    #[serde(default)]
+    pub hsts: Option<Hsts>,
+    #[serde(default)]
+    pub acme: Option<Acme>,
+    #[serde(default)]
+    pub acme_challenge_dir: Option<String>,
+    #[serde(default)]
+    pub response_headers: LinkedHashMap<RouteSpec, LinkedHashMap<String, String>>,
+    #[serde(default)]
+    pub header_casing: Vec<String>,
+    #[serde(default = "default_true")]
+    pub default_cache_control: bool,
+    #[serde(default)]
+    pub auth_cache_control: Option<AuthCacheControl>,
+    #[serde(default)]
+    pub default_host: Option<String>,
+    #[serde(skip)]
+    pub virtual_hosts: LinkedHashMap<String, Config>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Addresses {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn deserialize_addresses<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    match Addresses::deserialize(deserializer)? {
+        Addresses::One(address) => Ok(vec![address]),
+        Addresses::Many(addresses) => Ok(addresses),
+    }
+}
+
+fn default_http_09() -> Http09Mode {
+    Http09Mode::Simple
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_stream_chunk_size() -> usize {
+    consts::READ_CHUNK_SIZE
+}
+
+fn default_index_files() -> Vec<String> {
+    vec!["index.html".to_string(), "index.htm".to_string()]
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn expand_env_vars(value: Value) -> Result<Value, ConfigError> {
+    Ok(match value {
+        Value::String(string) => Value::String(expand_env_vars_in(&string)?),
+        Value::Sequence(values) => Value::Sequence(values.into_iter().map(expand_env_vars).collect::<Result<_, _>>()?),
+        Value::Mapping(entries) => Value::Mapping(
+            entries.into_iter().map(|(key, value)| Ok((key, expand_env_vars(value)?))).collect::<Result<_, _>>()?,
+        ),
+        value => value,
+    })
+}
+
+fn expand_env_vars_in(string: &str) -> Result<String, ConfigError> {
+    let mut expanded = String::new();
+    let mut rest = string;
+
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            _ => break,
+        };
+        expanded.push_str(&rest[..start]);
+
+        let variable = &rest[start + 2..end];
+        let (name, default) = match variable.find(":-") {
+            Some(index) => (&variable[..index], Some(&variable[index + 2..])),
+            _ => (variable, None),
+        };
+        match (env::var(name), default) {
+            (Ok(value), _) => expanded.push_str(&value),
+            (_, Some(default)) => expanded.push_str(default),
+            _ => return Err(ConfigError::UndefinedVariable(name.to_string())),
+        }
+        rest = &rest[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthCacheControl {
+    Private,
+    NoStore,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccessOrder {
+    #[default]
+    AllowDeny,
+    DenyAllow,
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccessPolicy {
+    Allow,
+    Deny,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthPrecedence {
+    #[default]
+    MostSpecific,
+    DefinitionOrder,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileSourceMode {
+    #[default]
+    Disk,
+    Embedded,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    Follow,
+    #[default]
+    WithinRoot,
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConnectionLimitAction {
+    #[default]
+    Wait,
+    Close,
+    Reject,
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Http09Mode {
+    Simple,
+    Reject,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Unreadable,
+    Invalid(String),
+    InvalidVirtualHost(String, String),
+    UndefinedDefaultHost(String),
+    NoAddresses,
+    NonLocalAdmin,
+    UndefinedVariable(String),
+    InvalidGeoIpDatabase(String, String),
+    AcmeUnavailable,
+    NoAcmeDomains,
+    AcmeTermsNotAgreed,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Unreadable => write!(f, "the file could not be read"),
+            ConfigError::Invalid(e) => write!(f, "{}", e),
+            ConfigError::InvalidVirtualHost(host, e) => write!(f, "virtual host {}: {}", host, e),
+            ConfigError::UndefinedDefaultHost(host) => write!(f, "default host {} is not a virtual host", host),
+            ConfigError::NoAddresses => write!(f, "no addresses or unix socket to listen on"),
+            ConfigError::NonLocalAdmin => write!(f, "admin address must be a loopback address"),
+            ConfigError::UndefinedVariable(name) => write!(f, "environment variable {} is not set", name),
+            ConfigError::InvalidGeoIpDatabase(path, e) => write!(f, "geoip database {}: {}", path, e),
+            ConfigError::AcmeUnavailable => write!(f, "acme support is not enabled in this build"),
+            ConfigError::NoAcmeDomains => write!(f, "acme needs at least one domain"),
+            ConfigError::AcmeTermsNotAgreed => write!(f, "acme needs agree_tos to accept the CA's terms of service"),
+        }
+    }
 }
 
 impl Config {
-    pub async fn load(path: &str) -> Option<Self> {
-        serde_yaml::from_str::<Config>(&fs::read_to_string(path).await.ok()?).ok()
+    pub async fn load(path: &str) -> Result<Self, ConfigError> {
+        let raw = fs::read_to_string(path).await.map_err(|_| ConfigError::Unreadable)?;
+        let invalid = |e: serde_yaml::Error| ConfigError::Invalid(e.to_string());
+        let parsed = if path.ends_with(TOML_EXTENSION) {
+            toml::from_str(&raw).map_err(|e| ConfigError::Invalid(e.to_string()))?
+        } else {
+            serde_yaml::from_str(&raw).map_err(invalid)?
+        };
+        match expand_env_vars(parsed)? {
+            Value::Mapping(base) => Self::from_settings(base),
+            _ => Err(ConfigError::Invalid("configuration must be a mapping of settings".to_string())),
+        }
+    }
+
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::new()
+    }
+
+    fn from_settings(mut base: Mapping) -> Result<Self, ConfigError> {
+        let invalid = |e: serde_yaml::Error| ConfigError::Invalid(e.to_string());
+        let hosts = base.remove(&Value::from(VIRTUAL_HOSTS_KEY));
+        let invalid_hosts = || ConfigError::Invalid(format!("{} must map host names to settings", VIRTUAL_HOSTS_KEY));
+        let mut config = serde_yaml::from_value::<Config>(Value::Mapping(base.clone())).map_err(invalid)?;
+
+        match hosts {
+            Some(Value::Mapping(hosts)) => {
+                for (name, host) in hosts {
+                    let (name, host) = match (name, host) {
+                        (Value::String(name), Value::Mapping(host)) => (name, host),
+                        _ => return Err(invalid_hosts()),
+                    };
+
+                    let mut merged = base.clone();
+                    for (key, value) in host {
+                        merged.insert(key, value);
+                    }
+                    let host_config = serde_yaml::from_value::<Config>(Value::Mapping(merged))
+                        .map_err(|e| ConfigError::InvalidVirtualHost(name.clone(), e.to_string()))?;
+                    config.virtual_hosts.insert(normalize_host(&name), host_config);
+                }
+            }
+            Some(Value::Null) | None => {}
+            _ => return Err(invalid_hosts()),
+        }
+
+        config.validate()?;
+        config.load_geoip()?;
+        Ok(config)
+    }
+
+    fn load_geoip(&mut self) -> Result<(), ConfigError> {
+        let invalid = |geoip: &GeoIp, e| ConfigError::InvalidGeoIpDatabase(geoip.database.clone(), e);
+        if let Some(geoip) = &mut self.geoip {
+            geoip.load(None).map_err(|e| invalid(geoip, e))?;
+        }
+        for (_, host) in self.virtual_hosts.iter_mut() {
+            if let Some(geoip) = &mut host.geoip {
+                geoip.load(self.geoip.as_ref()).map_err(|e| invalid(geoip, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn new(file_root: &str, template_root: &str) -> Self {
+        let settings = ConfigBuilder::new().with_file_root(file_root).with_template_root(template_root).into_settings();
+        serde_yaml::from_value(Value::Mapping(settings)).unwrap()
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        match &self.default_host {
+            Some(host) if !self.virtual_hosts.contains_key(&normalize_host(host)) => {
+                Err(ConfigError::UndefinedDefaultHost(host.clone()))
+            }
+            _ if self.addresses.is_empty() && self.unix_socket.is_none() => Err(ConfigError::NoAddresses),
+            _ if self.admin.as_ref().map(|admin| !admin.is_local()) == Some(true) => Err(ConfigError::NonLocalAdmin),
+            _ if self.acme.is_some() && !cfg!(feature = "acme") => Err(ConfigError::AcmeUnavailable),
+            _ if self.acme.as_ref().map(|acme| acme.domains.is_empty()) == Some(true) => {
+                Err(ConfigError::NoAcmeDomains)
+            }
+            _ if self.acme.as_ref().map(|acme| !acme.agree_tos) == Some(true) => Err(ConfigError::AcmeTermsNotAgreed),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn parse_limits(&self) -> ParseLimits {
+        ParseLimits {
+            max_uri_length: self.request_limits.max_uri_length,
+            max_header_length: self.request_limits.max_header_length,
+            max_header_count: self.request_limits.max_header_count,
+            max_header_bytes: self.request_limits.max_header_bytes,
+            read_timeout: Duration::from_secs(self.timeouts.read),
+            head_timeout: Duration::from_secs(self.timeouts.head),
+            body_timeout: self.timeouts.body.map(Duration::from_secs),
+        }
+    }
+
+    pub fn response_headers_for(&self, target: &str) -> LinkedHashMap<String, String> {
+        let mut matching = self
+            .response_headers
+            .iter()
+            .filter_map(|(RouteSpec(rule_regex), headers)| Some((rule_regex.find(target)?.end(), headers)))
+            .collect::<Vec<_>>();
+        matching.sort_by_key(|(specificity, _)| *specificity);
+
+        let mut merged = LinkedHashMap::new();
+        if self.security_headers {
+            merged.extend(consts::SECURITY_HEADERS.iter().map(|(name, value)| (name.to_string(), value.to_string())));
+        }
+        for (_, headers) in matching {
+            for (name, value) in headers {
+                merged.insert(name.to_ascii_lowercase(), value.clone());
+            }
+        }
+        merged
+    }
+
+    /// Finds the auth rule for `target`. Under `most-specific` precedence, the rule whose pattern matches the longest
+    /// prefix of the target wins, and ties go to the rule defined first; under `definition-order`, the first matching
+    /// rule wins.
+    pub fn auth_rule_for<'a, T>(&self, rules: &'a LinkedHashMap<RouteSpec, T>, target: &str) -> Option<&'a T> {
+        let mut matching =
+            rules.iter().filter_map(|(RouteSpec(rule_regex), rule)| Some((rule_regex.find(target)?.end(), rule)));
+        match self.auth_precedence {
+            AuthPrecedence::MostSpecific => matching.min_by_key(|(specificity, _)| Reverse(*specificity)),
+            AuthPrecedence::DefinitionOrder => matching.next(),
+        }
+        .map(|(_, rule)| rule)
+    }
+
+    pub fn webdav_enabled(&self, target: &str) -> bool {
+        self.webdav.iter().find(|(RouteSpec(rule_regex), _)| rule_regex.is_match(target)).map(|(_, enabled)| *enabled)
+            == Some(true)
+    }
+
+    pub fn writable_route(&self, target: &str) -> Option<WritableRoute> {
+        self.writable_routes.iter().find(|(RouteSpec(rule_regex), _)| rule_regex.is_match(target)).map(|(_, w)| *w)
+    }
+
+    pub fn max_body_length(&self, method: Method, target: &str) -> usize {
+        let method_name = method.to_string();
+        for (RouteSpec(rule_regex), limits) in &self.body_limits {
+            if rule_regex.is_match(target) {
+                if let Some(limit) = limits.get(&method_name).or_else(|| limits.get("*")) {
+                    return *limit;
+                }
+            }
+        }
+
+        if method == Method::Get {
+            self.request_limits.max_get_body_length
+        } else {
+            self.request_limits.max_other_body_length
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CREDENTIALS: &str = "user:$apr1$lucent$3Uj84dTWxhQiAMOF2m7t/.";
+
+    fn realm_for(precedence: &str, rules: &[(&str, &str)], target: &str) -> Option<String> {
+        let builder = ConfigBuilder::new().with_file_root("www").with_template_root("templates");
+        let builder = rules
+            .iter()
+            .fold(builder, |builder, (route, realm)| builder.with_basic_auth(route, realm, CREDENTIALS))
+            .with("auth_precedence", precedence)
+            .with_address("127.0.0.1:0");
+        let config = builder.build().ok().unwrap();
+        config.auth_rule_for(&config.basic_auth, target).map(|rule| rule.realm.clone())
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let rules = &[("/files", "Files"), ("/files/staff", "Staff")];
+        assert_eq!(realm_for("most-specific", rules, "/files/staff/a.txt").as_deref(), Some("Staff"));
+        assert_eq!(realm_for("most-specific", rules, "/files/public/a.txt").as_deref(), Some("Files"));
+        assert_eq!(realm_for("most-specific", rules, "/other").as_deref(), None);
+
+        let rules = &[("/files", "Files"), ("@/files/secret.txt", "Secret")];
+        assert_eq!(realm_for("most-specific", rules, "/files/secret.txt").as_deref(), Some("Secret"));
+        assert_eq!(realm_for("most-specific", rules, "/files/public.txt").as_deref(), Some("Files"));
+    }
+
+    #[test]
+    fn equal_matches_go_to_the_first_rule() {
+        let rules = &[("/docs/{page}", "First"), ("/docs/{page:.*}", "Second")];
+        assert_eq!(realm_for("most-specific", rules, "/docs/index.html").as_deref(), Some("First"));
+        let rules = &[("/docs/{page:.*}", "First"), ("/docs/{page}", "Second")];
+        assert_eq!(realm_for("most-specific", rules, "/docs/index.html").as_deref(), Some("First"));
+    }
+
+    #[test]
+    fn definition_order_takes_the_first_match() {
+        let rules = &[("/files", "Files"), ("/files/staff", "Staff")];
+        assert_eq!(realm_for("definition-order", rules, "/files/staff/a.txt").as_deref(), Some("Files"));
+        let rules = &[("/files/staff", "Staff"), ("/files", "Files")];
+        assert_eq!(realm_for("definition-order", rules, "/files/staff/a.txt").as_deref(), Some("Staff"));
     }
 }
@@ -0,0 +1,25 @@
+use std::net::IpAddr;
+
+use serde::Deserialize;
+
+use crate::server::config::ip_range::IpRange;
+
+#[derive(Clone, Deserialize)]
+pub struct ProxyProtocol {
+    pub listeners: Vec<String>,
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpRange>,
+}
+
+impl ProxyProtocol {
+    pub fn is_enabled(&self, listener: &str) -> bool {
+        self.listeners.iter().any(|l| l == listener)
+    }
+
+    pub fn is_trusted(&self, peer: Option<IpAddr>) -> bool {
+        match peer {
+            Some(peer) => self.trusted_proxies.is_empty() || self.trusted_proxies.iter().any(|range| range.contains(peer)),
+            _ => true,
+        }
+    }
+}
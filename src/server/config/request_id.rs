@@ -0,0 +1,32 @@
+use serde::Deserialize;
+
+use crate::http::request::Request;
+use crate::util;
+
+const DEFAULT_HEADER: &str = "x-request-id";
+const MAX_INBOUND_LENGTH: usize = 200;
+
+#[derive(Clone, Deserialize)]
+pub struct RequestId {
+    #[serde(default = "default_header")]
+    pub header: String,
+    #[serde(default)]
+    pub trust_inbound: bool,
+}
+
+fn default_header() -> String {
+    DEFAULT_HEADER.to_string()
+}
+
+impl RequestId {
+    pub fn id_for(&self, request: &Request) -> String {
+        let inbound = request.headers.get(&self.header).map(|values| &values[0]).filter(|id| {
+            self.trust_inbound && !id.is_empty() && id.len() <= MAX_INBOUND_LENGTH && id.chars().all(util::is_visible_char)
+        });
+
+        match inbound {
+            Some(id) => id.clone(),
+            _ => util::to_hex(&rand::random::<[u8; 16]>()),
+        }
+    }
+}
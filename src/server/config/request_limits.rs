@@ -0,0 +1,56 @@
+use serde::Deserialize;
+
+use crate::consts;
+
+#[derive(Clone, Deserialize)]
+pub struct RequestLimits {
+    #[serde(default = "default_max_uri_length")]
+    pub max_uri_length: usize,
+    #[serde(default = "default_max_header_length")]
+    pub max_header_length: usize,
+    #[serde(default = "default_max_header_count")]
+    pub max_header_count: usize,
+    #[serde(default = "default_max_header_bytes")]
+    pub max_header_bytes: usize,
+    #[serde(default = "default_max_get_body_length")]
+    pub max_get_body_length: usize,
+    #[serde(default = "default_max_other_body_length")]
+    pub max_other_body_length: usize,
+}
+
+fn default_max_uri_length() -> usize {
+    consts::MAX_URI_LENGTH
+}
+
+fn default_max_header_length() -> usize {
+    consts::MAX_HEADER_LENGTH
+}
+
+fn default_max_header_count() -> usize {
+    consts::MAX_HEADER_COUNT
+}
+
+fn default_max_header_bytes() -> usize {
+    consts::MAX_HEADERS_LENGTH
+}
+
+fn default_max_get_body_length() -> usize {
+    consts::MAX_GET_BODY_LENGTH
+}
+
+fn default_max_other_body_length() -> usize {
+    consts::MAX_OTHER_BODY_LENGTH
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits {
+            max_uri_length: default_max_uri_length(),
+            max_header_length: default_max_header_length(),
+            max_header_count: default_max_header_count(),
+            max_header_bytes: default_max_header_bytes(),
+            max_get_body_length: default_max_get_body_length(),
+            max_other_body_length: default_max_other_body_length(),
+        }
+    }
+}
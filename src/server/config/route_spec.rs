@@ -42,15 +42,16 @@ impl<'a> Visitor<'a> for RouteSpecStringVisitor {
     fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
         where E: de::Error
     {
-        match value.chars().next() {
-            Some('@') => Ok(RouteSpec(convert_to_regex(&value[1..], true))),
-            Some('/') => Ok(RouteSpec(convert_to_regex(value, false))),
-            _ => Err(E::custom(format!("Route specifier invalid: {}", value))),
-        }
+        let regex = match value.chars().next() {
+            Some('@') => convert_to_regex(&value[1..], true),
+            Some('/') => convert_to_regex(value, false),
+            _ => return Err(E::custom(format!("Route specifier invalid: {}", value))),
+        };
+        regex.map(RouteSpec).map_err(|_| E::custom(format!("Route specifier pattern invalid: {}", value)))
     }
 }
 
-fn convert_to_regex(route: &str, must_match_entire: bool) -> Regex {
+fn convert_to_regex(route: &str, must_match_entire: bool) -> Result<Regex, regex::Error> {
     let mut is_var = false;
     let partitioned = route.chars().map(|c| {
         is_var = if c == '{' { true } else if c == '}' && is_var { false } else { is_var };
@@ -79,5 +80,5 @@ fn convert_to_regex(route: &str, must_match_entire: bool) -> Regex {
         .collect::<String>();
 
     regex_str = if must_match_entire { format!("^{}$", regex_str) } else { format!("^{}", regex_str) };
-    Regex::new(&regex_str).unwrap()
+    Regex::new(&regex_str)
 }
@@ -0,0 +1,23 @@
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct TcpOptions {
+    #[serde(default = "default_nodelay")]
+    pub nodelay: bool,
+    #[serde(default)]
+    pub keepalive: Option<u64>,
+    #[serde(default)]
+    pub send_buffer_size: Option<usize>,
+    #[serde(default)]
+    pub recv_buffer_size: Option<usize>,
+}
+
+fn default_nodelay() -> bool {
+    true
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        TcpOptions { nodelay: default_nodelay(), keepalive: None, send_buffer_size: None, recv_buffer_size: None }
+    }
+}
@@ -0,0 +1,22 @@
+use linked_hash_map::LinkedHashMap;
+use serde::Deserialize;
+
+use crate::server::config::route_spec::RouteSpec;
+
+#[derive(Clone, Default, Deserialize)]
+pub struct Throttle {
+    #[serde(default)]
+    pub rate: Option<u64>,
+    #[serde(default)]
+    pub routes: LinkedHashMap<RouteSpec, u64>,
+}
+
+impl Throttle {
+    pub fn rate_for(&self, target: &str) -> Option<u64> {
+        let rate = match self.routes.iter().find(|(RouteSpec(rule_regex), _)| rule_regex.is_match(target)) {
+            Some((_, rate)) => Some(*rate),
+            _ => self.rate,
+        };
+        rate.filter(|rate| *rate > 0)
+    }
+}
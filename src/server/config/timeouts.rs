@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::consts;
+
+#[derive(Clone, Deserialize)]
+pub struct Timeouts {
+    #[serde(default = "default_read")]
+    pub read: u64,
+    #[serde(default = "default_head")]
+    pub head: u64,
+    #[serde(default)]
+    pub body: Option<u64>,
+    #[serde(default)]
+    pub write: Option<u64>,
+}
+
+fn default_read() -> u64 {
+    consts::MAX_READ_TIMEOUT.as_secs()
+}
+
+fn default_head() -> u64 {
+    consts::MAX_HEAD_TIMEOUT.as_secs()
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts { read: default_read(), head: default_head(), body: None, write: None }
+    }
+}
+
+impl Timeouts {
+    pub fn write_timeout(&self) -> Option<Duration> {
+        self.write.map(Duration::from_secs)
+    }
+
+    pub fn single_write_timeout(&self) -> Duration {
+        self.write_timeout().unwrap_or(consts::MAX_WRITE_TIMEOUT)
+    }
+}
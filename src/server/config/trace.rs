@@ -0,0 +1,21 @@
+use serde::Deserialize;
+
+use crate::consts;
+
+#[derive(Clone, Deserialize)]
+pub struct Trace {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_strip_headers")]
+    pub strip_headers: Vec<String>,
+}
+
+fn default_strip_headers() -> Vec<String> {
+    [consts::H_AUTHORIZATION, consts::H_PROXY_AUTHORIZATION, consts::H_COOKIE].iter().map(|h| h.to_string()).collect()
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Trace { enabled: false, strip_headers: default_strip_headers() }
+    }
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct UnixSocket {
+    pub path: String,
+    #[serde(default, deserialize_with = "deserialize_mode")]
+    pub mode: Option<u32>,
+}
+
+fn deserialize_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    let mode = String::deserialize(deserializer)?;
+    match u32::from_str_radix(&mode, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(Some(mode)),
+        _ => Err(D::Error::custom("invalid socket mode")),
+    }
+}
@@ -0,0 +1,25 @@
+use serde::Deserialize;
+
+use crate::consts;
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+pub struct Workers {
+    #[serde(default = "default_count")]
+    pub count: usize,
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+}
+
+fn default_count() -> usize {
+    consts::WORKER_COUNT
+}
+
+fn default_queue_size() -> usize {
+    consts::WORKER_QUEUE_SIZE
+}
+
+impl Default for Workers {
+    fn default() -> Self {
+        Workers { count: default_count(), queue_size: default_queue_size() }
+    }
+}
@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Default, Deserialize)]
+pub struct WritableRoute {
+    #[serde(default)]
+    pub create_dirs: bool,
+}
@@ -0,0 +1,54 @@
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+
+use crate::log;
+use crate::server::config::connection_limit::ConnectionLimit;
+
+pub struct ConnectionPermit(UnboundedSender<()>);
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let _ = self.0.unbounded_send(());
+    }
+}
+
+pub struct ConnectionLimiter {
+    pub limit: ConnectionLimit,
+    releases: UnboundedSender<()>,
+    permits: UnboundedReceiver<()>,
+    at_limit: bool,
+}
+
+impl ConnectionLimiter {
+    pub fn new(limit: ConnectionLimit) -> Self {
+        let (releases, permits) = mpsc::unbounded();
+        for _ in 0..limit.max {
+            let _ = releases.unbounded_send(());
+        }
+        ConnectionLimiter { limit, releases, permits, at_limit: false }
+    }
+
+    pub fn try_acquire(&mut self) -> Option<ConnectionPermit> {
+        match self.permits.try_next() {
+            Ok(Some(_)) => {
+                self.at_limit = false;
+                Some(ConnectionPermit(self.releases.clone()))
+            }
+            _ => {
+                if !self.at_limit {
+                    log::warn(format!("Connection limit of {} reached!", self.limit.max));
+                    self.at_limit = true;
+                }
+                None
+            }
+        }
+    }
+
+    pub async fn acquire(&mut self) -> ConnectionPermit {
+        if let Some(permit) = self.try_acquire() {
+            return permit;
+        }
+        self.permits.next().await;
+        ConnectionPermit(self.releases.clone())
+    }
+}
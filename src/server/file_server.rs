@@ -1,145 +1,746 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::pin::Pin;
+use std::time::{Duration, Instant};
 use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
 
-use async_std::io::{self, BufReader, BufWriter};
-use async_std::net::{SocketAddr, TcpListener, TcpStream};
+use async_std::fs::{self, Permissions};
+use async_std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use async_std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use async_std::os::unix::net::{UnixListener, UnixStream};
 use async_std::path::Path;
 use async_std::prelude::StreamExt;
-use async_std::sync::{self, Receiver, Sender};
+use async_std::stream::Stream;
+use async_std::sync::{self, Arc, Receiver, Sender};
 use async_std::task;
-use futures::{FutureExt, select};
+use futures::{future, FutureExt, select, stream};
+use futures::future::BoxFuture;
+#[cfg(feature = "acme")]
+use futures::future::Either;
 use futures::io::ErrorKind;
 
-use crate::consts;
+use crate::http::parser::ParseLimits;
+use crate::http::proxy_protocol;
 use crate::http::request::{HttpVersion, Request};
-use crate::log;
-use crate::server::config::Config;
+use crate::http::message::MessageBuilder;
+use crate::http::response::{Response, Status};
+use crate::{consts, log};
+use crate::server::config::{Config, ConnectionLimitAction, Http09Mode};
+use crate::server::config::proxy_protocol::ProxyProtocol;
+use crate::server::config::unix_socket::UnixSocket;
+use crate::server::connection_limiter::{ConnectionLimiter, ConnectionPermit};
+use crate::server::{file_source, socket_options};
+#[cfg(feature = "acme")]
+use crate::server::acme::AcmeManager;
+use crate::server::middleware::admin_api::AdminApi;
+use crate::server::middleware::auth_backend::AuthBackend;
+use crate::server::middleware::auth_limiter::AuthFailures;
+use crate::server::middleware::digest_auth::NonceCache;
+use crate::server::middleware::file_cache::CachedFiles;
+use crate::server::middleware::htpasswd::HtpasswdFiles;
+use crate::server::middleware::listing_cache::CachedListings;
+use crate::server::middleware::{Middleware, MiddlewareOutput, MiddlewareResult, MiddlewareStage};
 use crate::server::middleware::output_processor::OutputProcessor;
 use crate::server::middleware::request_verifier::RequestVerifier;
 use crate::server::middleware::response_gen::ResponseGenerator;
+use crate::server::metrics::{MeteredWriter, Metrics};
 use crate::server::Server;
 use crate::server::template::templates::Templates;
+use crate::util::Timing;
 
+const DEFAULT_HOST_NAME: &str = "default";
+
+#[derive(Clone, Copy)]
 pub struct ConnInfo {
     pub remote_addr: SocketAddr,
     pub local_addr: SocketAddr,
+    pub secure: bool,
+}
+
+impl ConnInfo {
+    fn new(remote_addr: io::Result<SocketAddr>, local_addr: io::Result<SocketAddr>) -> Self {
+        ConnInfo {
+            remote_addr: remote_addr.unwrap_or(SocketAddr::from_str("0.0.0.0:80").unwrap()),
+            local_addr: local_addr.unwrap_or(SocketAddr::from_str("127.0.0.1:80").unwrap()),
+            secure: false,
+        }
+    }
+
+    fn for_request(&self, request: &Request, config: &Config) -> Self {
+        let trusted = |ip: IpAddr| config.trusted_proxies.iter().any(|range| range.contains(ip));
+        if !trusted(self.remote_addr.ip()) {
+            return *self;
+        }
+
+        let secure = request.forwarded_proto().map(|proto| proto.eq_ignore_ascii_case("https")) == Some(true);
+        match request.forwarded_for() {
+            Some(entries) if !entries.is_empty() => {
+                let client = entries.iter().rev().find(|ip| !trusted(**ip)).unwrap_or(&entries[0]);
+                ConnInfo { remote_addr: SocketAddr::new(*client, 0), secure, ..*self }
+            }
+            _ => ConnInfo { secure, ..*self },
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
+enum Connection {
+    Tcp(TcpStream, bool),
+    Unix(UnixStream, bool),
+    Admin(TcpStream),
+}
+
+type Incoming<'a> = Pin<Box<dyn Stream<Item = io::Result<Connection>> + Send + 'a>>;
+type Job = BoxFuture<'static, ()>;
+
+#[derive(Clone)]
+pub struct VirtualHost {
+    pub name: String,
+    pub config: Config,
+    pub templates: Templates,
+}
+
+pub struct VirtualHosts {
+    base: VirtualHost,
+    hosts: HashMap<String, VirtualHost>,
+}
+
+impl VirtualHosts {
+    fn resolve(&self, request: &Request) -> Option<&VirtualHost> {
+        if self.hosts.is_empty() {
+            return Some(&self.base);
+        }
+
+        let host = request.host().and_then(|host| self.lookup(&host));
+        host.or_else(|| self.lookup(&self.base.config.default_host.as_ref()?.to_ascii_lowercase()))
+    }
+
+    fn lookup(&self, host: &str) -> Option<&VirtualHost> {
+        let host = host.trim_end_matches('.');
+        self.hosts.get(host).or_else(|| {
+            let parent = &host[host.find('.').filter(|&index| index > 0)?..];
+            self.hosts.get(&format!("*{}", parent))
+        })
+    }
+}
+
+pub struct ServerState {
+    pub digest_nonces: Mutex<NonceCache>,
+    pub auth_failures: Mutex<AuthFailures>,
+    pub metrics: Metrics,
+    pub started: Instant,
+    pub htpasswd_files: HtpasswdFiles,
+    pub listings: CachedListings,
+    pub files: CachedFiles,
+    pub acme_challenges: Mutex<HashMap<String, String>>,
+    middleware: RwLock<Vec<(MiddlewareStage, Arc<dyn Middleware>)>>,
+    auth_backends: RwLock<HashMap<String, Arc<dyn AuthBackend>>>,
+}
+
+impl ServerState {
+    pub fn middleware_for(&self, stage: MiddlewareStage) -> Vec<Arc<dyn Middleware>> {
+        let middleware = self.middleware.read().unwrap();
+        middleware.iter().filter(|(s, _)| *s == stage).map(|(_, middleware)| Arc::clone(middleware)).collect()
+    }
+
+    pub fn auth_backend(&self, name: &str) -> Option<Arc<dyn AuthBackend>> {
+        self.auth_backends.read().unwrap().get(name).cloned()
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum FileServerStartError {
     InvalidFileRoot,
     InvalidTemplates,
 
-    AddressInUse,
-    AddressUnavailable,
-    CannotBindAddress,
+    AddressInUse(String),
+    AddressUnavailable(String),
+    CannotBindAddress(String),
 }
 
 pub struct FileServer {
-    config: Config,
-    templates: Templates,
+    hosts: RwLock<Arc<VirtualHosts>>,
+    state: Arc<ServerState>,
 
-    listener: TcpListener,
+    listeners: Vec<TcpListener>,
+    unix_listener: Option<UnixListener>,
+    admin_listener: Option<TcpListener>,
     stop_sender: Sender<()>,
     stop_receiver: Receiver<()>,
 }
 
+impl Display for FileServerStartError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FileServerStartError::InvalidFileRoot => write!(f, "File directory invalid!"),
+            FileServerStartError::InvalidTemplates => write!(f, "Template directory invalid or incomplete!"),
+            FileServerStartError::AddressInUse(address) => write!(f, "The address {} is in use!", address),
+            FileServerStartError::AddressUnavailable(address) => write!(f, "The address {} is unavailable!", address),
+            FileServerStartError::CannotBindAddress(address) => write!(f, "Cannot bind to the address {}!", address),
+        }
+    }
+}
+
 impl FileServer {
     pub async fn new(config: Config) -> Result<Self, FileServerStartError> {
-        let file_root = config.file_root.strip_suffix('/').unwrap_or(&config.file_root).to_string();
-        let templates = Templates::new(config.template_root.strip_suffix('/').unwrap_or(&config.template_root))
-            .await
-            .ok_or(FileServerStartError::InvalidTemplates)?;
+        let hosts = Self::load_hosts(config).await?;
+        let base = &hosts.base;
 
         let (stop_sender, stop_receiver) = sync::channel(1);
-        let listener = match TcpListener::bind(&config.address).await {
-            Ok(listener) => listener,
-            Err(e) => return Err(match e.kind() {
-                ErrorKind::AddrInUse => FileServerStartError::AddressInUse,
-                ErrorKind::AddrNotAvailable => FileServerStartError::AddressUnavailable,
-                _ => FileServerStartError::CannotBindAddress,
-            }),
+        let mut listeners = vec![];
+        for address in &base.config.addresses {
+            listeners.push(Self::bind(address, base.config.reuse_port).await?);
+        }
+        let admin_listener = match &base.config.admin {
+            Some(admin) => Some(Self::bind(&admin.address.to_string(), false).await?),
+            _ => None,
         };
 
-        if !Path::new(&file_root).is_dir().await {
+        let unix_listener = match &base.config.unix_socket {
+            Some(socket) => Some(Self::bind_unix(socket).await?),
+            _ => None,
+        };
+
+        let state = Arc::new(ServerState {
+            digest_nonces: Mutex::new(NonceCache::new()),
+            auth_failures: Mutex::new(AuthFailures::new()),
+            metrics: Metrics::new(),
+            started: Instant::now(),
+            htpasswd_files: HtpasswdFiles::new(),
+            listings: CachedListings::new(),
+            files: CachedFiles::new(),
+            acme_challenges: Mutex::new(HashMap::new()),
+            middleware: RwLock::new(vec![]),
+            auth_backends: RwLock::new(HashMap::new()),
+        });
+        Ok(FileServer {
+            hosts: RwLock::new(Arc::new(hosts)),
+            state,
+            listeners,
+            unix_listener,
+            admin_listener,
+            stop_sender,
+            stop_receiver,
+        })
+    }
+
+    pub fn add_middleware(&self, stage: MiddlewareStage, middleware: impl Middleware + 'static) {
+        self.state.middleware.write().unwrap().push((stage, Arc::new(middleware)));
+    }
+
+    pub fn add_auth_backend(&self, name: &str, backend: impl AuthBackend + 'static) {
+        self.state.auth_backends.write().unwrap().insert(name.to_string(), Arc::new(backend));
+    }
+
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners.iter().filter_map(|listener| listener.local_addr().ok()).collect()
+    }
+
+    pub async fn run(&self) -> io::Result<()> {
+        let mut addresses = self.local_addrs().iter().map(SocketAddr::to_string).collect::<Vec<_>>();
+        let hosts = self.hosts();
+        if let Some(socket) = &hosts.base.config.unix_socket {
+            addresses.push(socket.path.clone());
+        }
+        log::info(format!("Starting server on {}.", addresses.join(", ")));
+        if let Some(admin) = &hosts.base.config.admin {
+            log::info(format!("Starting admin API on {}.", admin.address));
+        }
+        #[cfg(feature = "acme")]
+        if let Some(acme) = &hosts.base.config.acme {
+            let renewals = AcmeManager::new(acme.clone(), Arc::clone(&self.state)).run();
+            return match future::select(self.main_loop().boxed(), renewals.boxed()).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => Ok(()),
+            };
+        }
+        self.main_loop().await
+    }
+
+    pub async fn shutdown(&self) {
+        log::info("Stopping server.");
+        self.stop_sender.send(()).await;
+    }
+
+    async fn load_hosts(mut config: Config) -> Result<VirtualHosts, FileServerStartError> {
+        let mut hosts = HashMap::new();
+        for (name, host_config) in mem::take(&mut config.virtual_hosts) {
+            hosts.insert(name.clone(), Self::load_host(name, host_config).await?);
+        }
+        let base = Self::load_host(DEFAULT_HOST_NAME.to_string(), config).await?;
+        Ok(VirtualHosts { base, hosts })
+    }
+
+    pub async fn check(config: Config) -> Result<(), FileServerStartError> {
+        Self::load_hosts(config).await.map(|_| ())
+    }
+
+    fn hosts(&self) -> Arc<VirtualHosts> {
+        Arc::clone(&self.hosts.read().unwrap())
+    }
+
+    async fn bind(address: &str, reuse_port: bool) -> Result<TcpListener, FileServerStartError> {
+        let listener = if reuse_port {
+            socket_options::bind_reusing_port(address).map(TcpListener::from)
+        } else {
+            TcpListener::bind(address).await
+        };
+        listener.map_err(|e| match e.kind() {
+            ErrorKind::AddrInUse => FileServerStartError::AddressInUse(address.to_string()),
+            ErrorKind::AddrNotAvailable => FileServerStartError::AddressUnavailable(address.to_string()),
+            _ => FileServerStartError::CannotBindAddress(address.to_string()),
+        })
+    }
+
+    async fn bind_unix(socket: &UnixSocket) -> Result<UnixListener, FileServerStartError> {
+        let path = Path::new(&socket.path);
+        let cannot_bind = || FileServerStartError::CannotBindAddress(socket.path.clone());
+
+        if let Ok(metadata) = fs::symlink_metadata(path).await {
+            if !metadata.file_type().is_socket() {
+                return Err(cannot_bind());
+            } else if UnixStream::connect(path).await.is_ok() {
+                return Err(FileServerStartError::AddressInUse(socket.path.clone()));
+            }
+            fs::remove_file(path).await.map_err(|_| cannot_bind())?;
+        }
+
+        let listener = UnixListener::bind(path).await.map_err(|_| cannot_bind())?;
+        if let Some(mode) = socket.mode {
+            fs::set_permissions(path, Permissions::from_mode(mode)).await.map_err(|_| cannot_bind())?;
+        }
+        Ok(listener)
+    }
+
+    async fn load_host(name: String, config: Config) -> Result<VirtualHost, FileServerStartError> {
+        let template_root = config.template_root.strip_suffix('/').unwrap_or(&config.template_root);
+        let templates = Templates::new(template_root, config.reload_templates)
+            .await
+            .ok_or(FileServerStartError::InvalidTemplates)?;
+
+        let root_is_dir = file_source::for_config(&config).metadata(&config.file_root).await.map(|root| root.is_dir);
+        if root_is_dir != Some(true) {
             Err(FileServerStartError::InvalidFileRoot)
         } else {
-            Ok(FileServer {
-                config,
-                templates,
-                listener,
-                stop_sender,
-                stop_receiver,
-            })
+            Ok(VirtualHost { name, config, templates })
         }
     }
 
     async fn main_loop(&self) -> io::Result<()> {
-        let mut incoming = self.listener.incoming();
+        let hosts = self.hosts();
+        let config = &hosts.base.config;
+        let proxied = |listener: &str| config.proxy_protocol.as_ref().map(|p| p.is_enabled(listener)) == Some(true);
+
+        let mut incoming = self.listeners
+            .iter()
+            .zip(&config.addresses)
+            .map(|(listener, address)| {
+                let proxied = proxied(address);
+                Box::pin(listener.incoming().map(move |s| s.map(|s| Connection::Tcp(s, proxied)))) as Incoming
+            })
+            .collect::<Vec<_>>();
+        if let (Some(listener), Some(socket)) = (&self.unix_listener, &config.unix_socket) {
+            let proxied = proxied(&socket.path);
+            incoming.push(Box::pin(listener.incoming().map(move |s| s.map(|s| Connection::Unix(s, proxied)))));
+        }
+        if let Some(listener) = &self.admin_listener {
+            incoming.push(Box::pin(listener.incoming().map(|s| s.map(Connection::Admin))));
+        }
+        let mut incoming = stream::select_all(incoming);
+        let mut limiter = config.connection_limit.map(ConnectionLimiter::new);
+
+        let (job_sender, job_receiver) = sync::channel(config.workers.queue_size.max(1));
+        for _ in 0..config.workers.count.max(1) {
+            task::spawn(Self::run_worker(job_receiver.clone(), Arc::clone(&self.state)));
+        }
         log::info("Server started.");
 
         loop {
-            select! {
+            let waited_permit = match &mut limiter {
+                Some(limiter) if limiter.limit.on_limit == ConnectionLimitAction::Wait => select! {
+                    _ = self.stop_receiver.recv().fuse() => break,
+                    permit = limiter.acquire().fuse() => Some(permit),
+                },
+                _ => None,
+            };
+
+            let job = select! {
                 _ = self.stop_receiver.recv().fuse() => break,
                 stream = incoming.next().fuse() => match stream {
-                    Some(stream) => {
-                        let stream = stream?;
-                        let config = self.config.clone();
-                        let templates = self.templates.clone();
-                        task::spawn(Self::handle_incoming(stream, config, templates));
-                    }
+                    Some(stream) => self.admit_connection(stream?, limiter.as_mut(), waited_permit, &job_sender),
                     _ => break,
                 }
+            };
+
+            if let Some(job) = job {
+                self.state.metrics.queue_connection();
+                select! {
+                    _ = self.stop_receiver.recv().fuse() => break,
+                    _ = job_sender.send(job).fuse() => {}
+                }
             }
         }
+
+        if let Some(socket) = &self.hosts().base.config.unix_socket {
+            let _ = fs::remove_file(&socket.path).await;
+        }
         log::info("Server stopped.");
         Ok(())
     }
 
-    async fn handle_incoming(stream: TcpStream, config: Config, templates: Templates) {
+    fn admit_connection(
+        &self,
+        connection: Connection,
+        limiter: Option<&mut ConnectionLimiter>,
+        permit: Option<ConnectionPermit>,
+        jobs: &Sender<Job>,
+    ) -> Option<Job> {
+        let permit = match (limiter, permit) {
+            (_, Some(permit)) => Some(permit),
+            (Some(limiter), _) if !matches!(connection, Connection::Admin(_)) => match limiter.try_acquire() {
+                Some(permit) => Some(permit),
+                _ => {
+                    Self::refuse_connection(connection, limiter.limit.on_limit);
+                    return None;
+                }
+            },
+            _ => None,
+        };
+        self.connection_job(connection, permit, jobs)
+    }
+
+    fn refuse_connection(connection: Connection, action: ConnectionLimitAction) {
+        if action != ConnectionLimitAction::Reject {
+            return;
+        }
+
+        let response = MessageBuilder::<Response>::new()
+            .with_status(Status::ServiceUnavailable)
+            .with_header(consts::H_CONNECTION, consts::H_CONN_CLOSE)
+            .build();
+        match connection {
+            Connection::Tcp(stream, _) => task::spawn(async move {
+                let _ = response.send(&mut &stream).await;
+            }),
+            Connection::Unix(stream, _) => task::spawn(async move {
+                let _ = response.send(&mut &stream).await;
+            }),
+            Connection::Admin(_) => return,
+        };
+    }
+
+    async fn run_worker(jobs: Receiver<Job>, state: Arc<ServerState>) {
+        while let Some(job) = jobs.recv().await {
+            state.metrics.dequeue_connection();
+            job.await;
+        }
+    }
+
+    fn connection_job(
+        &self,
+        connection: Connection,
+        permit: Option<ConnectionPermit>,
+        jobs: &Sender<Job>,
+    ) -> Option<Job> {
+        let hosts = self.hosts();
+        let state = Arc::clone(&self.state);
+        let trusted = |peer: Option<IpAddr>| match &hosts.base.config.proxy_protocol {
+            Some(proxy_protocol) => proxy_protocol.is_trusted(peer),
+            _ => false,
+        };
+
+        match connection {
+            Connection::Tcp(stream, proxied) => {
+                if let Err(err) = socket_options::apply(&stream, &hosts.base.config.tcp) {
+                    log::warn(format!("Could not set TCP options: {}!", err));
+                }
+                let proxied = proxied && trusted(stream.peer_addr().ok().map(|addr| addr.ip()));
+                let conn_info = ConnInfo::new(stream.peer_addr(), stream.local_addr());
+                let (job, worker) = Self::worker_job();
+                let handler = Self::handle_incoming(stream, conn_info, proxied, hosts, state, jobs.clone(), worker);
+                task::spawn(handler.map(move |_| drop(permit)));
+                Some(job)
+            }
+            Connection::Unix(stream, proxied) => {
+                let proxied = proxied && trusted(None);
+                let unknown = || Err(ErrorKind::AddrNotAvailable.into());
+                let conn_info = ConnInfo::new(unknown(), unknown());
+                let (job, worker) = Self::worker_job();
+                let handler = Self::handle_incoming(stream, conn_info, proxied, hosts, state, jobs.clone(), worker);
+                task::spawn(handler.map(move |_| drop(permit)));
+                Some(job)
+            }
+            Connection::Admin(stream) => {
+                task::spawn(Self::handle_admin(stream, hosts, state));
+                None
+            }
+        }
+    }
+
+    fn worker_job() -> (Job, Receiver<Sender<()>>) {
+        let (grant_sender, grant_receiver) = sync::channel(1);
+        let job = async move {
+            let (release_sender, release_receiver) = sync::channel::<()>(1);
+            grant_sender.send(release_sender).await;
+            drop(grant_sender);
+            release_receiver.recv().await;
+        };
+        (job.boxed(), grant_receiver)
+    }
+
+    async fn next_worker(
+        reader: &mut (impl BufRead + Unpin),
+        timeout: Duration,
+        jobs: &Sender<Job>,
+        state: &ServerState,
+    ) -> Option<Sender<()>> {
+        let at_end = future::poll_fn(|cx| Pin::new(&mut *reader).poll_fill_buf(cx).map_ok(<[u8]>::is_empty));
+        if io::timeout(timeout, at_end).await.unwrap_or(true) {
+            return None;
+        }
+
+        let (job, worker) = Self::worker_job();
+        state.metrics.queue_connection();
+        jobs.send(job).await;
+        worker.recv().await
+    }
+
+    async fn handle_incoming<S>(
+        stream: S,
+        mut conn_info: ConnInfo,
+        proxied: bool,
+        hosts: Arc<VirtualHosts>,
+        state: Arc<ServerState>,
+        jobs: Sender<Job>,
+        worker: Receiver<Sender<()>>,
+    ) where S: Send + Sync + 'static, for<'s> &'s S: Read + Write + Unpin + Send {
+        let mut release = match worker.recv().await {
+            Some(release) => release,
+            _ => return,
+        };
+        let connection = state.metrics.connection();
         let mut reader = BufReader::new(&stream);
-        let mut writer = BufWriter::new(&stream);
+        let mut writer = BufWriter::new(MeteredWriter::new(&stream, &state.metrics));
 
-        let remote_addr = stream.peer_addr().unwrap_or(SocketAddr::from_str("0.0.0.0:80").unwrap());
-        let local_addr = stream.local_addr().unwrap_or(SocketAddr::from_str("127.0.0.1:80").unwrap());
-        let conn_info = ConnInfo { remote_addr, local_addr };
-
-        while !match RequestVerifier::new(&mut reader, &mut writer).verify_request().await {
-            Err(output) => OutputProcessor::new(&mut writer, &templates, None).process(output).await,
-            Ok(mut request) => {
-                let output = ResponseGenerator::new(&config, &templates, &mut request, &conn_info)
-                    .get_response()
-                    .await;
-
-                client_intends_to_close(&request) || match output {
-                    Err(output) => OutputProcessor::new(&mut writer, &templates, Some(&request))
-                        .process(output)
-                        .await,
-                    _ => true,
+        if proxied {
+            match proxy_protocol::read_header(&mut reader).await {
+                Ok(Some(source)) => conn_info.remote_addr = source,
+                Ok(_) => {}
+                _ => {
+                    log::warn(format!("Invalid PROXY header from {}.", conn_info.remote_addr));
+                    return;
                 }
             }
+        }
+
+        let limits = hosts.base.config.parse_limits();
+        let mut upgraded = None;
+        loop {
+            let result = RequestVerifier::new(&mut reader, &mut writer).verify_request(limits).await;
+            let start = Instant::now();
+            let close = match result {
+                Err(output) => Self::respond(&mut writer, &hosts.base, None, output, &state, start).await,
+                Ok(mut request) => match hosts.resolve(&request) {
+                    Some(host)
+                        if request.http_version == HttpVersion::Http09 && host.config.http_09 == Http09Mode::Reject => {
+                        let output = MiddlewareOutput::Error(Status::HttpVersionUnsupported, true);
+                        Self::respond(&mut writer, host, Some(&request), output, &state, start).await
+                    }
+                    Some(host) => {
+                        let VirtualHost { config, templates, .. } = host;
+                        let templates = &*templates.current().await;
+                        let conn_info = conn_info.for_request(&request, &hosts.base.config);
+                        request.secure = conn_info.secure;
+                        request.id = config.request_id.as_ref().map(|request_id| request_id.id_for(&request));
+                        if config.request_timing {
+                            request.timing = Some(Timing::new());
+                        }
+
+                        let phase_start = Instant::now();
+                        let output = match Self::accept_body(&mut reader, &mut writer, config, templates, &state,
+                            &mut request, &conn_info).await {
+                            Ok(_) => {
+                                Self::record_timing(&mut request, consts::TIMING_PARSE, phase_start);
+                                let phase_start = Instant::now();
+                                let output = ResponseGenerator::new(config, templates, &state, &mut request, &conn_info)
+                                    .get_response()
+                                    .await;
+                                Self::record_timing(&mut request, consts::TIMING_MIDDLEWARE, phase_start);
+                                output
+                            }
+                            output => output,
+                        };
+
+                        match output {
+                            Err(MiddlewareOutput::Upgrade(response, backend)) => {
+                                let output = MiddlewareOutput::Response(response, false);
+                                if !Self::respond(&mut writer, host, Some(&request), output, &state, start).await {
+                                    upgraded = Some((reader.buffer().to_vec(), backend));
+                                }
+                                true
+                            }
+                            Err(output) => {
+                                Self::respond(&mut writer, host, Some(&request), output, &state, start).await
+                            }
+                            _ => true,
+                        }
+                    }
+                    _ => {
+                        let output = MiddlewareOutput::Error(Status::NotFound, true);
+                        Self::respond(&mut writer, &hosts.base, Some(&request), output, &state, start).await
+                    }
+                },
+            };
+            if close {
+                break;
+            }
+            drop(release);
+            release = match Self::next_worker(&mut reader, limits.read_timeout, &jobs, &state).await {
+                Some(release) => release,
+                _ => return,
+            };
+        }
+
+        drop((reader, writer, connection, release));
+        if let Some((buffered, backend)) = upgraded {
+            let _connection = state.metrics.connection();
+            let mut reader = io::ReadExt::chain(io::Cursor::new(buffered), &stream);
+            Self::tunnel(&mut reader, &mut &stream, &backend).await;
+        }
+    }
+
+    async fn respond(
+        writer: &mut (impl Write + Unpin),
+        host: &VirtualHost,
+        request: Option<&Request>,
+        output: MiddlewareOutput,
+        state: &ServerState,
+        start: Instant,
+    ) -> bool {
+        let status = output.status();
+        let templates = host.templates.current().await;
+        let close = OutputProcessor::new(writer, &host.config, &templates, request).process(output).await;
+        if let Some(status) = status {
+            state.metrics.record(&host.name, status, start.elapsed());
+        }
+        close
+    }
+
+    async fn handle_admin(stream: TcpStream, hosts: Arc<VirtualHosts>, state: Arc<ServerState>) {
+        let mut reader = BufReader::new(&stream);
+        let mut writer = BufWriter::new(&stream);
+        let VirtualHost { config, templates, .. } = &hosts.base;
+
+        while !match RequestVerifier::new(&mut reader, &mut writer).verify_request(ParseLimits::default()).await {
+            Err(output) => OutputProcessor::new(&mut writer, config, templates, None).process(output).await,
+            Ok(request) => {
+                let output = AdminApi::new(&request, &state).get_response();
+                OutputProcessor::new(&mut writer, config, templates, Some(&request)).process(output).await
+            }
         } {}
     }
+
+    async fn tunnel(reader: &mut (impl Read + Unpin), writer: &mut (impl Write + Unpin), backend: &TcpStream) {
+        let (mut backend_reader, mut backend_writer) = (backend, backend);
+        let upstream = Box::pin(io::copy(reader, &mut backend_writer));
+        let downstream = Box::pin(io::copy(&mut backend_reader, writer));
+        future::select(upstream, downstream).await;
+    }
+
+    fn record_timing(request: &mut Request, phase: &'static str, since: Instant) {
+        if let Some(timing) = &mut request.timing {
+            timing.record(phase, since);
+        }
+    }
+
+    async fn accept_body(
+        reader: &mut (impl BufRead + Unpin),
+        writer: &mut (impl Write + Unpin),
+        config: &Config,
+        templates: &Templates,
+        state: &ServerState,
+        request: &mut Request,
+        conn_info: &ConnInfo,
+    ) -> MiddlewareResult<()> {
+        if request.expect_continue {
+            ResponseGenerator::check_expectation(config, templates, state, request, conn_info).await?;
+        }
+        let max_length = config.max_body_length(request.method, &request.uri.to_string());
+        RequestVerifier::new(reader, writer).verify_body(request, max_length, config.parse_limits()).await
+    }
 }
 
 impl Server for FileServer {
     fn start(&self) {
-        log::info(format!("Starting server on {}.", self.listener.local_addr().unwrap()));
-        if let Err(e) = task::block_on(self.main_loop()) {
+        if let Err(e) = task::block_on(self.run()) {
             log::warn(format!("Unexpected error during normal operation: {}", e));
         }
     }
 
     fn stop(&self) {
-        log::info("Stopping server.");
-        task::block_on(self.stop_sender.send(()));
+        task::block_on(self.shutdown());
     }
-}
 
-fn client_intends_to_close(request: &Request) -> bool {
-    if let Some(conn_options) = request.headers.get(consts::H_CONNECTION) {
-        request.http_version != HttpVersion::Http11 || conn_options[0] != consts::H_CONN_KEEP_ALIVE ||
-            conn_options[0] == consts::H_CONN_CLOSE
-    } else {
-        request.http_version != HttpVersion::Http11
+    fn reload(&self, mut config: Config) {
+        let current = self.hosts();
+        let running = &current.base.config;
+
+        let mut restart_required = vec![];
+        if config.addresses != running.addresses {
+            restart_required.push("addresses");
+        }
+        if config.reuse_port != running.reuse_port {
+            restart_required.push("reuse_port");
+        }
+        if config.unix_socket != running.unix_socket {
+            restart_required.push("unix_socket");
+        }
+        if config.admin != running.admin {
+            restart_required.push("admin");
+        }
+        if config.acme != running.acme {
+            restart_required.push("acme");
+        }
+        let proxy_listeners = |config: &Config| config.proxy_protocol.as_ref().map(|p| p.listeners.clone());
+        if proxy_listeners(&config) != proxy_listeners(running) {
+            restart_required.push("proxy_protocol");
+        }
+        if config.workers != running.workers {
+            restart_required.push("workers");
+        }
+        if config.connection_limit != running.connection_limit {
+            restart_required.push("connection_limit");
+        }
+        if !restart_required.is_empty() {
+            log::warn(format!("Changes to {} require a restart to take effect.", restart_required.join(", ")));
+            config.addresses = running.addresses.clone();
+            config.reuse_port = running.reuse_port;
+            config.unix_socket = running.unix_socket.clone();
+            config.admin = running.admin.clone();
+            config.acme = running.acme.clone();
+            config.proxy_protocol = match (config.proxy_protocol.take(), &running.proxy_protocol) {
+                (Some(proxy_protocol), Some(running)) => {
+                    Some(ProxyProtocol { listeners: running.listeners.clone(), ..proxy_protocol })
+                }
+                (_, running) => running.clone(),
+            };
+            config.workers = running.workers;
+            config.connection_limit = running.connection_limit;
+        }
+
+        match task::block_on(Self::load_hosts(config)) {
+            Ok(hosts) => {
+                *self.hosts.write().unwrap() = Arc::new(hosts);
+                self.state.listings.clear();
+                self.state.files.clear();
+                log::info("Configuration reloaded.");
+            }
+            Err(FileServerStartError::InvalidTemplates) => {
+                log::warn("Template directory invalid or incomplete; keeping the running configuration.");
+            }
+            Err(_) => log::warn("File directory invalid; keeping the running configuration."),
+        }
     }
 }
@@ -0,0 +1,101 @@
+use async_std::fs::{self, File, Metadata};
+use async_std::io;
+use async_std::path::Path;
+use async_std::task;
+use futures::future::{BoxFuture, FutureExt};
+use futures::StreamExt;
+
+use crate::http::mapped_file::MappedFile;
+use crate::http::message::Body;
+use crate::server::config::SymlinkPolicy;
+use crate::server::file_source::{DirEntry, FileInfo, FileSource};
+use crate::util::Range;
+
+pub struct DiskSource<'a> {
+    root: &'a str,
+    symlinks: SymlinkPolicy,
+    mmap_threshold: Option<u64>,
+}
+
+impl<'a> DiskSource<'a> {
+    pub fn new(root: &'a str, symlinks: SymlinkPolicy, mmap_threshold: Option<u64>) -> Self {
+        DiskSource { root, symlinks, mmap_threshold }
+    }
+
+    async fn is_allowed(&self, path: &Path) -> bool {
+        if self.symlinks == SymlinkPolicy::Follow {
+            return true;
+        }
+
+        let (root, resolved) = match (fs::canonicalize(self.root).await, fs::canonicalize(path).await) {
+            (Ok(root), Ok(resolved)) => (root, resolved),
+            _ => return false,
+        };
+        match self.symlinks {
+            SymlinkPolicy::Never => match path.strip_prefix(self.root) {
+                Ok(relative) => resolved == root.join(relative),
+                _ => false,
+            },
+            _ => resolved.starts_with(&root),
+        }
+    }
+}
+
+impl FileSource for DiskSource<'_> {
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Option<FileInfo>> {
+        async move {
+            if !self.is_allowed(Path::new(path)).await {
+                return None;
+            }
+            file_info(fs::metadata(path).await.ok()?)
+        }.boxed()
+    }
+
+    fn open<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Body>> {
+        async move {
+            if !self.is_allowed(Path::new(path)).await {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            let file = File::open(path).await?;
+            let len = file.metadata().await?.len();
+            match self.mmap_threshold {
+                Some(threshold) if len > 0 && len >= threshold => {
+                    let mapped_path = path.to_string();
+                    match task::spawn_blocking(move || MappedFile::open(&mapped_path)).await {
+                        Ok(mapped) => {
+                            let len = mapped.len();
+                            Ok(Body::Mapped(mapped, Range { low: 0, high: len }))
+                        }
+                        _ => Ok(Body::File(file, Range { low: 0, high: len as usize })),
+                    }
+                }
+                _ => Ok(Body::File(file, Range { low: 0, high: len as usize })),
+            }
+        }.boxed()
+    }
+
+    fn read_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Option<Vec<DirEntry>>> {
+        async move {
+            if !self.is_allowed(Path::new(path)).await {
+                return None;
+            }
+            let entries = fs::read_dir(path).await.ok()?;
+            let entries = entries
+                .filter_map(|entry| async {
+                    let entry = entry.ok()?;
+                    if !self.is_allowed(&entry.path()).await {
+                        return None;
+                    }
+                    let info = file_info(fs::metadata(entry.path()).await.ok()?)?;
+                    Some(DirEntry { name: entry.file_name().to_string_lossy().to_string(), info })
+                })
+                .collect()
+                .await;
+            Some(entries)
+        }.boxed()
+    }
+}
+
+fn file_info(metadata: Metadata) -> Option<FileInfo> {
+    Some(FileInfo { is_dir: metadata.is_dir(), len: metadata.len(), modified: metadata.modified().ok()? })
+}
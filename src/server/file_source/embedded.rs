@@ -0,0 +1,67 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use async_std::io;
+use futures::future::{self, BoxFuture, FutureExt};
+
+use crate::http::message::Body;
+use crate::server::file_source::{DirEntry, FileInfo, FileSource};
+
+include!(concat!(env!("OUT_DIR"), "/embedded_files.rs"));
+
+pub struct EmbeddedSource<'a> {
+    root: &'a str,
+}
+
+impl<'a> EmbeddedSource<'a> {
+    pub fn new(root: &'a str) -> Self {
+        EmbeddedSource { root }
+    }
+
+    fn relative_path<'b>(&self, path: &'b str) -> Option<&'b str> {
+        Some(path.strip_prefix(self.root)?.trim_matches('/'))
+    }
+}
+
+impl FileSource for EmbeddedSource<'_> {
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Option<FileInfo>> {
+        let info = self.relative_path(path).and_then(|path| {
+            let dir_prefix = format!("{}/", path);
+            match embedded_file(path) {
+                Some(file) => Some(file_info(false, file.len())),
+                _ if path.is_empty() && !EMBEDDED_PATHS.is_empty() => Some(file_info(true, 0)),
+                _ if EMBEDDED_PATHS.iter().any(|p| p.starts_with(&dir_prefix)) => Some(file_info(true, 0)),
+                _ => None,
+            }
+        });
+        future::ready(info).boxed()
+    }
+
+    fn open<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Body>> {
+        let file = self.relative_path(path).and_then(embedded_file);
+        let body = file.map(|file| Body::Bytes(file.to_vec())).ok_or_else(|| io::ErrorKind::NotFound.into());
+        future::ready(body).boxed()
+    }
+
+    fn read_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Option<Vec<DirEntry>>> {
+        let entries = self.relative_path(path).and_then(|path| {
+            let dir_prefix = if path.is_empty() { String::new() } else { format!("{}/", path) };
+            let mut entries = Vec::<DirEntry>::new();
+            for rest in EMBEDDED_PATHS.iter().filter_map(|p| p.strip_prefix(&dir_prefix)) {
+                let name = rest.split('/').next().unwrap_or(rest);
+                if entries.iter().all(|entry| entry.name != name) {
+                    let info = match embedded_file(&format!("{}{}", dir_prefix, name)) {
+                        Some(file) if name == rest => file_info(false, file.len()),
+                        _ => file_info(true, 0),
+                    };
+                    entries.push(DirEntry { name: name.to_string(), info });
+                }
+            }
+            if entries.is_empty() { None } else { Some(entries) }
+        });
+        future::ready(entries).boxed()
+    }
+}
+
+fn file_info(is_dir: bool, len: usize) -> FileInfo {
+    FileInfo { is_dir, len: len as u64, modified: UNIX_EPOCH + Duration::from_secs(EMBEDDED_MODIFIED) }
+}
@@ -0,0 +1,56 @@
+use std::time::SystemTime;
+
+use async_std::io::{self, ReadExt};
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::http::message::Body;
+use crate::server::config::{Config, FileSourceMode};
+use crate::server::file_source::disk::DiskSource;
+use crate::server::file_source::embedded::EmbeddedSource;
+
+pub mod disk;
+pub mod embedded;
+pub mod writer;
+
+pub struct FileInfo {
+    pub is_dir: bool,
+    pub len: u64,
+    pub modified: SystemTime,
+}
+
+pub struct DirEntry {
+    pub name: String,
+    pub info: FileInfo,
+}
+
+pub trait FileSource: Send + Sync {
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Option<FileInfo>>;
+    fn open<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Body>>;
+    fn read_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Option<Vec<DirEntry>>>;
+
+    fn is_file<'a>(&'a self, path: &'a str) -> BoxFuture<'a, bool> {
+        self.metadata(path).map(|info| matches!(info, Some(info) if !info.is_dir)).boxed()
+    }
+
+    fn read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Vec<u8>>> {
+        async move {
+            match self.open(path).await? {
+                Body::Bytes(bytes) => Ok(bytes),
+                Body::File(mut file, _) => {
+                    let mut bytes = vec![];
+                    file.read_to_end(&mut bytes).await?;
+                    Ok(bytes)
+                }
+                Body::Mapped(file, range) => file.read(&range),
+                Body::Stream(_) => Err(io::ErrorKind::InvalidData.into()),
+            }
+        }.boxed()
+    }
+}
+
+pub fn for_config(config: &Config) -> Box<dyn FileSource + '_> {
+    match config.file_source {
+        FileSourceMode::Disk => Box::new(DiskSource::new(&config.file_root, config.symlinks, config.mmap_threshold)),
+        FileSourceMode::Embedded => Box::new(EmbeddedSource::new(&config.file_root)),
+    }
+}
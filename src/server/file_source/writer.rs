@@ -0,0 +1,89 @@
+use async_std::fs::{self, File};
+use async_std::io;
+use async_std::io::prelude::WriteExt;
+use async_std::path::{Path, PathBuf};
+
+use crate::util;
+
+pub struct FileWriter<'a> {
+    root: &'a str,
+}
+
+impl<'a> FileWriter<'a> {
+    pub fn new(root: &'a str) -> Self {
+        FileWriter { root }
+    }
+
+    pub async fn write(&self, path: &str, content: &[u8]) -> io::Result<bool> {
+        let (parent, name) = self.confine(Path::new(path)).await?;
+        let path = parent.join(&name);
+        let existed = match fs::symlink_metadata(&path).await {
+            Ok(metadata) if metadata.is_dir() => return Err(io::ErrorKind::IsADirectory.into()),
+            Ok(_) => true,
+            _ => false,
+        };
+
+        let temp_path = parent.join(format!(".{}.{}.tmp", name, util::to_hex(&rand::random::<[u8; 8]>())));
+        let result = async {
+            let mut file = File::create(&temp_path).await?;
+            file.write_all(content).await?;
+            file.sync_all().await?;
+            fs::rename(&temp_path, &path).await
+        }.await;
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path).await;
+        }
+        result.map(|_| !existed)
+    }
+
+    pub async fn create_parent_dirs(&self, path: &str) -> io::Result<()> {
+        let relative = match Path::new(path).strip_prefix(self.root) {
+            Ok(relative) => relative.parent().map(Path::to_path_buf).unwrap_or_default(),
+            _ => return Err(io::ErrorKind::PermissionDenied.into()),
+        };
+
+        let root = fs::canonicalize(self.root).await?;
+        let mut dir = root.clone();
+        for component in relative.components() {
+            match fs::create_dir(dir.join(component)).await {
+                Err(err) if err.kind() != io::ErrorKind::AlreadyExists => return Err(err),
+                _ => dir = fs::canonicalize(dir.join(component)).await?,
+            }
+            if !dir.starts_with(&root) {
+                return Err(io::ErrorKind::PermissionDenied.into());
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn remove(&self, path: &str) -> io::Result<()> {
+        let (parent, name) = self.confine(Path::new(path)).await?;
+        let path = parent.join(name);
+        if fs::symlink_metadata(&path).await?.is_dir() {
+            fs::remove_dir_all(path).await
+        } else {
+            fs::remove_file(path).await
+        }
+    }
+
+    pub async fn create_dir(&self, path: &str) -> io::Result<()> {
+        let (parent, name) = self.confine(Path::new(path)).await?;
+        fs::create_dir(parent.join(name)).await
+    }
+
+    async fn confine(&self, path: &Path) -> io::Result<(PathBuf, String)> {
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if path.strip_prefix(self.root).is_ok() => name.to_string(),
+            _ => return Err(io::ErrorKind::PermissionDenied.into()),
+        };
+
+        let root = fs::canonicalize(self.root).await?;
+        let parent = fs::canonicalize(path.parent().unwrap_or(path)).await?;
+        if parent.starts_with(&root) {
+            Ok((parent, name))
+        } else {
+            Err(io::ErrorKind::PermissionDenied.into())
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::net::IpAddr;
+
+#[cfg(feature = "geoip")]
+use maxminddb::{geoip2, Reader};
+
+pub struct GeoIpDatabase {
+    #[cfg(feature = "geoip")]
+    reader: Reader<Vec<u8>>,
+}
+
+#[cfg(feature = "geoip")]
+impl GeoIpDatabase {
+    pub fn open(path: &str) -> Result<Self, String> {
+        Reader::open_readfile(path).map(|reader| GeoIpDatabase { reader }).map_err(|e| e.to_string())
+    }
+
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let country = self.reader.lookup::<geoip2::Country>(ip).ok()?.country?;
+        country.iso_code.map(str::to_ascii_uppercase)
+    }
+}
+
+#[cfg(not(feature = "geoip"))]
+impl GeoIpDatabase {
+    pub fn open(_path: &str) -> Result<Self, String> {
+        Err("GeoIP support is not enabled in this build (build with `--features geoip`)".to_string())
+    }
+
+    pub fn country(&self, _ip: IpAddr) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_std::io::{self, Write};
+
+use crate::http::response::Status;
+
+const DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+pub const STATUS_CLASSES: &[&str] = &["1xx", "2xx", "3xx", "4xx", "5xx"];
+
+pub struct Metrics {
+    requests: AtomicU64,
+    responses: [AtomicU64; 5],
+    duration_buckets: Vec<AtomicU64>,
+    duration_micros: AtomicU64,
+    sent_bytes: AtomicU64,
+    active_connections: AtomicUsize,
+    queued_connections: AtomicUsize,
+    hosts: Mutex<HashMap<String, HostStats>>,
+}
+
+#[derive(Clone, Default)]
+pub struct HostStats {
+    pub requests: u64,
+    pub responses: [u64; 5],
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            requests: AtomicU64::new(0),
+            responses: Default::default(),
+            duration_buckets: DURATION_BUCKETS.iter().map(|_| AtomicU64::new(0)).collect(),
+            duration_micros: AtomicU64::new(0),
+            sent_bytes: AtomicU64::new(0),
+            active_connections: AtomicUsize::new(0),
+            queued_connections: AtomicUsize::new(0),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, host: &str, status: Status, duration: Duration) {
+        let class = status as usize / 100 - 1;
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if let Some(count) = self.responses.get(class) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let seconds = duration.as_secs_f64();
+        if let Some(index) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.duration_buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+        self.duration_micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+
+        let mut hosts = self.hosts.lock().unwrap();
+        let stats = hosts.entry(host.to_string()).or_default();
+        stats.requests += 1;
+        if let Some(count) = stats.responses.get_mut(class) {
+            *count += 1;
+        }
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn queued_connections(&self) -> usize {
+        self.queued_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn host_stats(&self) -> Vec<(String, HostStats)> {
+        let mut stats = self.hosts.lock().unwrap().iter().map(|(h, s)| (h.clone(), s.clone())).collect::<Vec<_>>();
+        stats.sort_by(|(a, _), (b, _)| a.cmp(b));
+        stats
+    }
+
+    pub fn connection(&self) -> ActiveConnection<'_> {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        ActiveConnection(self)
+    }
+
+    pub fn queue_connection(&self) {
+        self.queued_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dequeue_connection(&self) {
+        self.queued_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let requests = self.requests();
+        let mut out = String::new();
+
+        out.push_str("# HELP lucent_requests_total Total number of requests handled.\n");
+        out.push_str("# TYPE lucent_requests_total counter\n");
+        out.push_str(&format!("lucent_requests_total {}\n", requests));
+
+        out.push_str("# HELP lucent_responses_total Responses sent, by status class.\n");
+        out.push_str("# TYPE lucent_responses_total counter\n");
+        for (class, count) in STATUS_CLASSES.iter().zip(&self.responses) {
+            out.push_str(&format!("lucent_responses_total{{class=\"{}\"}} {}\n", class, count.load(Ordering::Relaxed)));
+        }
+
+        out.push_str("# HELP lucent_request_duration_seconds Time taken to handle and respond to requests.\n");
+        out.push_str("# TYPE lucent_request_duration_seconds histogram\n");
+        let mut cumulative = 0;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(&self.duration_buckets) {
+            cumulative += count.load(Ordering::Relaxed);
+            out.push_str(&format!("lucent_request_duration_seconds_bucket{{le=\"{}\"}} {}\n", bound, cumulative));
+        }
+        out.push_str(&format!("lucent_request_duration_seconds_bucket{{le=\"+Inf\"}} {}\n", requests));
+        let seconds = self.duration_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        out.push_str(&format!("lucent_request_duration_seconds_sum {}\n", seconds));
+        out.push_str(&format!("lucent_request_duration_seconds_count {}\n", requests));
+
+        out.push_str("# HELP lucent_sent_bytes_total Total number of bytes sent to clients.\n");
+        out.push_str("# TYPE lucent_sent_bytes_total counter\n");
+        out.push_str(&format!("lucent_sent_bytes_total {}\n", self.sent_bytes.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP lucent_active_connections Number of currently open client connections.\n");
+        out.push_str("# TYPE lucent_active_connections gauge\n");
+        out.push_str(&format!("lucent_active_connections {}\n", self.active_connections()));
+
+        out.push_str("# HELP lucent_queued_connections Number of accepted connections waiting for a free worker.\n");
+        out.push_str("# TYPE lucent_queued_connections gauge\n");
+        out.push_str(&format!("lucent_queued_connections {}\n", self.queued_connections()));
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct ActiveConnection<'a>(&'a Metrics);
+
+impl Drop for ActiveConnection<'_> {
+    fn drop(&mut self) {
+        self.0.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub struct MeteredWriter<'a, W: Write + Unpin> {
+    writer: W,
+    metrics: &'a Metrics,
+}
+
+impl<'a, W: Write + Unpin> MeteredWriter<'a, W> {
+    pub fn new(writer: W, metrics: &'a Metrics) -> Self {
+        MeteredWriter { writer, metrics }
+    }
+}
+
+impl<W: Write + Unpin> Write for MeteredWriter<'_, W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.writer).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = result {
+            self.metrics.sent_bytes.fetch_add(written as u64, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_close(cx)
+    }
+}
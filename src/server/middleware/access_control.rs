@@ -0,0 +1,42 @@
+use crate::http::request::Request;
+use crate::http::response::Status;
+use crate::server::config::Config;
+use crate::server::config::route_spec::RouteSpec;
+use crate::server::file_server::ConnInfo;
+use crate::server::middleware::{MiddlewareOutput, MiddlewareResult};
+
+pub struct AccessController<'a> {
+    request: &'a Request,
+    config: &'a Config,
+    conn_info: &'a ConnInfo,
+}
+
+impl<'a> AccessController<'a> {
+    pub fn new(request: &'a Request, config: &'a Config, conn_info: &'a ConnInfo) -> Self {
+        AccessController { request, config, conn_info }
+    }
+
+    pub fn check(&self) -> MiddlewareResult<()> {
+        let target = self.request.uri.to_string();
+        let rule = self.config.access_control.iter().find(|(RouteSpec(rule_regex), _)| rule_regex.is_match(&target));
+        match rule {
+            Some((_, rule)) if !rule.allows(self.conn_info.remote_addr.ip()) => {
+                Err(MiddlewareOutput::Error(Status::Forbidden, false))
+            }
+            _ => self.check_country(),
+        }
+    }
+
+    fn check_country(&self) -> MiddlewareResult<()> {
+        let geoip = match &self.config.geoip {
+            Some(geoip) => geoip,
+            _ => return Ok(()),
+        };
+        let country = geoip.reader.as_ref().and_then(|reader| reader.country(self.conn_info.remote_addr.ip()));
+        if geoip.allows(country.as_deref()) {
+            Ok(())
+        } else {
+            Err(MiddlewareOutput::Error(Status::Forbidden, false))
+        }
+    }
+}
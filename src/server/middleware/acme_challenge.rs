@@ -0,0 +1,59 @@
+use async_std::fs;
+use async_std::path::Path;
+
+use crate::consts;
+use crate::http::message::{Body, MessageBuilder};
+use crate::http::request::{Method, Request};
+use crate::http::response::{Response, Status};
+use crate::http::uri::Uri;
+use crate::server::config::Config;
+use crate::server::file_server::ServerState;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+
+pub struct AcmeChallengeResponder<'a> {
+    request: &'a Request,
+    config: &'a Config,
+    state: &'a ServerState,
+}
+
+impl<'a> AcmeChallengeResponder<'a> {
+    pub fn new(request: &'a Request, config: &'a Config, state: &'a ServerState) -> Self {
+        AcmeChallengeResponder { request, config, state }
+    }
+
+    pub async fn check(&self) -> MiddlewareResult<()> {
+        let path = match &self.request.uri {
+            Uri::OriginForm { path } => format!("/{}", path.path_as_string()),
+            _ => return Ok(()),
+        };
+        let token = match path.strip_prefix(consts::ACME_CHALLENGE_PATH) {
+            Some(token) => token,
+            _ => return Ok(()),
+        };
+        let pending = self.state.acme_challenges.lock().unwrap().get(token).cloned();
+        let key_authorization = match (pending, &self.config.acme_challenge_dir) {
+            (Some(key_authorization), _) => key_authorization.into_bytes(),
+            (_, Some(dir)) if is_valid_token(token) => match fs::read(Path::new(dir).join(token)).await {
+                Ok(key_authorization) => key_authorization,
+                _ => return Err(MiddlewareOutput::Error(Status::NotFound, false)),
+            },
+            (_, Some(_)) => return Err(MiddlewareOutput::Error(Status::NotFound, false)),
+            _ => return Ok(()),
+        };
+
+        if self.request.method != Method::Get && self.request.method != Method::Head {
+            return Err(MiddlewareOutput::Status(Status::MethodNotAllowed, false));
+        }
+        let response = MessageBuilder::<Response>::new()
+            .with_header(consts::H_CACHE_CONTROL, consts::H_CACHE_NO_STORE)
+            .with_body(Body::Bytes(key_authorization), consts::H_MEDIA_TEXT)
+            .build();
+
+        middleware::log_request(self.request, response.status, &self.request.uri);
+        Err(MiddlewareOutput::Response(Box::new(response), false))
+    }
+}
+
+fn is_valid_token(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
@@ -0,0 +1,65 @@
+use crate::{consts, util};
+use crate::http::message::{Body, MessageBuilder};
+use crate::http::request::{Method, Request};
+use crate::http::response::{Response, Status};
+use crate::http::uri::Uri;
+use crate::server::file_server::ServerState;
+use crate::server::metrics;
+use crate::server::middleware::{self, MiddlewareOutput};
+
+const STATS_PATH: &str = "stats";
+
+pub struct AdminApi<'a> {
+    request: &'a Request,
+    state: &'a ServerState,
+}
+
+impl<'a> AdminApi<'a> {
+    pub fn new(request: &'a Request, state: &'a ServerState) -> Self {
+        AdminApi { request, state }
+    }
+
+    pub fn get_response(&self) -> MiddlewareOutput {
+        match &self.request.uri {
+            Uri::OriginForm { path } if path.path_as_string() == STATS_PATH => {}
+            _ => return MiddlewareOutput::Error(Status::NotFound, false),
+        }
+        if self.request.method != Method::Get && self.request.method != Method::Head {
+            return MiddlewareOutput::Status(Status::MethodNotAllowed, true);
+        }
+
+        let response = MessageBuilder::<Response>::new()
+            .with_header(consts::H_CACHE_CONTROL, consts::H_CACHE_NO_STORE)
+            .with_body(Body::Bytes(self.stats_json().into_bytes()), consts::H_MEDIA_JSON)
+            .build();
+
+        middleware::log_request(self.request, response.status, &self.request.uri);
+        MiddlewareOutput::Response(Box::new(response), false)
+    }
+
+    fn stats_json(&self) -> String {
+        let metrics = &self.state.metrics;
+        let hosts = metrics
+            .host_stats()
+            .iter()
+            .map(|(name, stats)| {
+                let responses = metrics::STATUS_CLASSES
+                    .iter()
+                    .zip(&stats.responses)
+                    .map(|(class, count)| format!("\"{}\":{}", class, count))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let name = util::escape_json(name);
+                format!("\"{}\":{{\"requests\":{},\"responses\":{{{}}}}}", name, stats.requests, responses)
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "{{\"server\":\"{}\",\"uptime\":{},\"active_connections\":{},\"queued_connections\":{},\
+            \"requests\":{},\"hosts\":{{{}}}}}",
+            consts::SERVER_NAME_VERSION, self.state.started.elapsed().as_secs(), metrics.active_connections(),
+            metrics.queued_connections(), metrics.requests(), hosts,
+        )
+    }
+}
@@ -0,0 +1,37 @@
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::server::config::auth_info::Credentials;
+use crate::server::middleware::htpasswd;
+use crate::util;
+
+pub trait AuthBackend: Send + Sync {
+    fn verify<'a>(&'a self, user: &'a str, password: &'a str) -> BoxFuture<'a, bool>;
+}
+
+pub struct ConfigAuthBackend<'a> {
+    credentials: &'a [Credentials],
+}
+
+impl<'a> ConfigAuthBackend<'a> {
+    pub fn new(credentials: &'a [Credentials]) -> Self {
+        ConfigAuthBackend { credentials }
+    }
+}
+
+impl AuthBackend for ConfigAuthBackend<'_> {
+    fn verify<'a>(&'a self, user: &'a str, password: &'a str) -> BoxFuture<'a, bool> {
+        let matching = self
+            .credentials
+            .iter()
+            .filter(|c| util::constant_time_eq(c.user.as_bytes(), user.as_bytes()))
+            .collect::<Vec<_>>();
+
+        let verified = if matching.is_empty() {
+            let _ = self.credentials.first().map(|c| htpasswd::verify_hash(password, &c.password_hash));
+            false
+        } else {
+            matching.iter().any(|c| htpasswd::verify_hash(password, &c.password_hash))
+        };
+        async move { verified }.boxed()
+    }
+}
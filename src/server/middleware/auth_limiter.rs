@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{consts, log};
+use crate::http::request::Request;
+use crate::http::response::Status;
+use crate::server::config::auth_rate_limit::AuthRateLimit;
+use crate::server::middleware::{MiddlewareOutput, MiddlewareResult};
+
+struct FailureRecord {
+    count: u32,
+    window_start: Instant,
+    locked_until: Option<Instant>,
+}
+
+pub struct AuthFailures {
+    records: HashMap<IpAddr, FailureRecord>,
+}
+
+impl AuthFailures {
+    pub fn new() -> Self {
+        AuthFailures { records: HashMap::new() }
+    }
+
+    fn prune(&mut self, now: Instant, window: Duration) {
+        if self.records.len() >= consts::AUTH_FAILURE_CACHE_SIZE {
+            self.records.retain(|_, record| match record.locked_until {
+                Some(until) => until > now,
+                _ => now.duration_since(record.window_start) < window,
+            });
+        }
+    }
+}
+
+impl Default for AuthFailures {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct AuthLimiter<'a> {
+    request: &'a Request,
+    limits: AuthRateLimit,
+    failures: &'a Mutex<AuthFailures>,
+    addr: IpAddr,
+}
+
+impl<'a> AuthLimiter<'a> {
+    pub fn new(request: &'a Request, limits: AuthRateLimit, failures: &'a Mutex<AuthFailures>, addr: IpAddr) -> Self {
+        AuthLimiter { request, limits, failures, addr }
+    }
+
+    pub fn check(&self) -> MiddlewareResult<()> {
+        let mut failures = self.failures.lock().unwrap();
+        let locked_until = failures.records.get(&self.addr).and_then(|record| record.locked_until);
+        match locked_until {
+            Some(until) if until > Instant::now() => Err(MiddlewareOutput::Error(Status::TooManyRequests, false)),
+            Some(_) => {
+                failures.records.remove(&self.addr);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn record(&self, result: &MiddlewareResult<Option<&str>>) {
+        match result {
+            Ok(Some(_)) => {
+                self.failures.lock().unwrap().records.remove(&self.addr);
+            }
+            Err(MiddlewareOutput::Response(response, _)) if response.status == Status::Unauthorized
+                && self.request.headers.contains(consts::H_AUTHORIZATION) => self.record_failure(),
+            _ => {}
+        }
+    }
+
+    fn record_failure(&self) {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.limits.window);
+        let mut failures = self.failures.lock().unwrap();
+        failures.prune(now, window);
+
+        let record = failures.records.entry(self.addr).or_insert(FailureRecord {
+            count: 0,
+            window_start: now,
+            locked_until: None,
+        });
+        if now.duration_since(record.window_start) >= window {
+            record.count = 0;
+            record.window_start = now;
+        }
+
+        record.count += 1;
+        if record.count >= self.limits.max_failures {
+            record.locked_until = Some(now + Duration::from_secs(self.limits.lockout));
+            log::warn(format!(
+                "Locking out {} for {}s after {} failed auth attempts.",
+                self.addr, self.limits.lockout, record.count,
+            ));
+        }
+    }
+}
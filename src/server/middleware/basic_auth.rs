@@ -1,72 +1,97 @@
-use pwhash::bcrypt;
+use std::sync::Arc;
 
-use crate::{consts, log};
+use crate::consts;
 use crate::http::message::MessageBuilder;
 use crate::http::request::Request;
 use crate::http::response::Response;
 use crate::http::response::Status;
+use crate::log;
 use crate::server::config::auth_info::AuthInfo;
 use crate::server::config::Config;
-use crate::server::config::route_spec::RouteSpec;
-use crate::server::middleware::{MiddlewareOutput, MiddlewareResult};
+use crate::server::file_server::ServerState;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+use crate::server::middleware::auth_backend::{AuthBackend, ConfigAuthBackend};
 
 pub struct BasicAuthChecker<'a> {
     request: &'a Request,
     config: &'a Config,
+    state: &'a ServerState,
 }
 
 impl<'a> BasicAuthChecker<'a> {
-    pub fn new(request: &'a Request, config: &'a Config) -> Self {
-        BasicAuthChecker { request, config }
+    pub fn new(request: &'a Request, config: &'a Config, state: &'a ServerState) -> Self {
+        BasicAuthChecker { request, config, state }
     }
 
-    pub fn check(&self) -> MiddlewareResult<bool> {
+    pub async fn check(&self) -> MiddlewareResult<bool> {
         let target = self.request.uri.to_string();
-        for (RouteSpec(rule_regex), auth_info) in &self.config.basic_auth {
-            if rule_regex.captures(&target).is_some() {
-                return match self.request.headers.get(consts::H_AUTHORIZATION) {
-                    Some(auth) => self.check_auth_header(&auth, &auth_info),
-                    _ => self.www_authenticate_output(&auth_info),
-                };
-            }
+        let auth_info = match self.config.auth_rule_for(&self.config.basic_auth, &target) {
+            Some(auth_info) => auth_info,
+            _ => return Ok(false),
+        };
+        match self.request.headers.get(consts::H_AUTHORIZATION) {
+            Some(auth) => self.check_auth_header(auth, auth_info).await,
+            _ => self.www_authenticate_output(auth_info),
         }
-        Ok(false)
     }
 
-    fn check_auth_header(&self, auth: &Vec<String>, auth_info: &AuthInfo) -> MiddlewareResult<bool> {
-        let challenge = self.www_authenticate_output(auth_info);
-
+    async fn check_auth_header(&self, auth: &[String], auth_info: &AuthInfo) -> MiddlewareResult<bool> {
         let auth = auth[0].splitn(2, ' ').collect::<Vec<_>>();
         if auth.len() > 1 && auth[0].eq_ignore_ascii_case(consts::H_AUTH_BASIC) {
             let encoded_credentials = &auth[1];
-            let maybe_credentials = base64::decode(encoded_credentials).map(|c| String::from_utf8(c));
+            let maybe_credentials = base64::decode(encoded_credentials).map(String::from_utf8);
             let credentials = match maybe_credentials {
                 Ok(Ok(c)) => c,
-                _ => return challenge,
+                _ => return self.www_authenticate_output(auth_info),
             };
 
             let credentials = credentials.splitn(2, ':').collect::<Vec<_>>();
-            if credentials.len() > 1 {
-                let user = credentials[0];
-                let password = credentials[1];
-                for c in &auth_info.credentials {
-                    if c.user == user && bcrypt::verify(password, &c.password_hash) {
-                        return Ok(true);
-                    }
-                }
+            if credentials.len() > 1 && self.backend_verify(auth_info, credentials[0], credentials[1]).await? {
+                return Ok(true);
             }
         }
-        challenge
+        self.www_authenticate_output(auth_info)
+    }
+
+    async fn backend_verify(&self, auth_info: &AuthInfo, user: &str, password: &str) -> MiddlewareResult<bool> {
+        let registered: Arc<dyn AuthBackend>;
+        let file_credentials;
+        let inline;
+        let backend: &dyn AuthBackend = match (&auth_info.backend, &auth_info.htpasswd) {
+            (Some(name), _) => match self.state.auth_backend(name) {
+                Some(backend) => {
+                    registered = backend;
+                    &*registered
+                }
+                _ => {
+                    log::warn(format!("Authentication backend `{}` is not registered!", name));
+                    return Err(MiddlewareOutput::Error(Status::InternalServerError, false));
+                }
+            },
+            (_, Some(path)) => match self.state.htpasswd_files.credentials(path).await {
+                Some(credentials) => {
+                    file_credentials = credentials;
+                    inline = ConfigAuthBackend::new(&file_credentials);
+                    &inline
+                }
+                _ => return Err(MiddlewareOutput::Error(Status::InternalServerError, false)),
+            },
+            _ => {
+                inline = ConfigAuthBackend::new(&auth_info.credentials);
+                &inline
+            }
+        };
+        Ok(backend.verify(user, password).await)
     }
 
     fn www_authenticate_output(&self, auth_info: &AuthInfo) -> MiddlewareResult<bool> {
-        log::info(format!("({}) {} {}", Status::Unauthorized, self.request.method, self.request.uri));
+        middleware::log_request(self.request, Status::Unauthorized, &self.request.uri);
 
         let auth = format!("{} {}=\"{}\"", consts::H_AUTH_BASIC, consts::H_AUTH_REALM, auth_info.realm);
         let response = MessageBuilder::<Response>::new()
             .with_status(Status::Unauthorized)
             .with_header(consts::H_WWW_AUTHENTICATE, &auth)
             .build();
-        Err(MiddlewareOutput::Response(response, false))
+        Err(MiddlewareOutput::Response(Box::new(response), false))
     }
 }
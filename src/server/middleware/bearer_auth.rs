@@ -0,0 +1,61 @@
+use pwhash::bcrypt;
+
+use crate::consts;
+use crate::http::message::MessageBuilder;
+use crate::http::request::Request;
+use crate::http::response::{Response, Status};
+use crate::server::config::auth_info::BearerAuthInfo;
+use crate::server::config::Config;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+
+pub struct BearerAuthChecker<'a> {
+    request: &'a Request,
+    config: &'a Config,
+}
+
+impl<'a> BearerAuthChecker<'a> {
+    pub fn new(request: &'a Request, config: &'a Config) -> Self {
+        BearerAuthChecker { request, config }
+    }
+
+    pub fn check(&self) -> MiddlewareResult<bool> {
+        let target = self.request.uri.to_string();
+        let auth_info = match self.config.auth_rule_for(&self.config.bearer_auth, &target) {
+            Some(auth_info) => auth_info,
+            _ => return Ok(false),
+        };
+        match self.request.headers.get(consts::H_AUTHORIZATION) {
+            Some(auth) => self.check_auth_header(&auth[0], auth_info),
+            _ => self.www_authenticate_output(auth_info, false),
+        }
+    }
+
+    fn check_auth_header(&self, auth: &str, auth_info: &BearerAuthInfo) -> MiddlewareResult<bool> {
+        let auth = auth.splitn(2, ' ').collect::<Vec<_>>();
+        if auth.len() < 2 || !auth[0].eq_ignore_ascii_case(consts::H_AUTH_BEARER) {
+            return self.www_authenticate_output(auth_info, false);
+        }
+
+        let token = auth[1].trim_matches(consts::OPTIONAL_WHITESPACE);
+        if auth_info.token_hashes.iter().any(|hash| bcrypt::verify(token, hash)) {
+            Ok(true)
+        } else {
+            self.www_authenticate_output(auth_info, true)
+        }
+    }
+
+    fn www_authenticate_output(&self, auth_info: &BearerAuthInfo, invalid_token: bool) -> MiddlewareResult<bool> {
+        middleware::log_request(self.request, Status::Unauthorized, &self.request.uri);
+
+        let mut auth = format!("{} {}=\"{}\"", consts::H_AUTH_BEARER, consts::H_AUTH_REALM, auth_info.realm);
+        if invalid_token {
+            auth.push_str(&format!(", {}=\"{}\"", consts::H_AUTH_ERROR, consts::H_AUTH_INVALID_TOKEN));
+        }
+
+        let response = MessageBuilder::<Response>::new()
+            .with_status(Status::Unauthorized)
+            .with_header(consts::H_WWW_AUTHENTICATE, &auth)
+            .build();
+        Err(MiddlewareOutput::Response(Box::new(response), false))
+    }
+}
@@ -1,9 +1,17 @@
-use std::io::Write;
+use std::convert::TryFrom;
+use std::io::{BufRead, Read, Write};
+use std::mem;
+use std::os::unix::process::CommandExt;
 use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use async_std::io::{self, SeekFrom};
+use async_std::io::prelude::SeekExt;
 use async_std::path::Path;
-use async_std::process::Output;
-use futures::AsyncReadExt;
+use async_std::task;
+use futures::{AsyncReadExt, SinkExt, StreamExt};
+use futures::channel::{mpsc, oneshot};
 
 use crate::{consts, log};
 use crate::http::message::{Body, Message};
@@ -12,9 +20,13 @@ use crate::http::response::{Response, Status};
 use crate::http::uri::Uri;
 use crate::server::config::Config;
 use crate::server::file_server::ConnInfo;
-use crate::server::middleware::{MiddlewareOutput, MiddlewareResult};
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+use crate::server::middleware::digest_auth;
 
-pub const VAR_EXCLUDED_HEADERS: &[&str] = &[consts::H_CONTENT_LENGTH, consts::H_CONTENT_TYPE, consts::H_CONNECTION];
+pub const VAR_EXCLUDED_HEADERS: &[&str] = &[
+    consts::H_CONTENT_LENGTH, consts::H_CONTENT_TYPE, consts::H_CONNECTION, consts::H_AUTHORIZATION,
+    consts::H_PROXY_AUTHORIZATION, consts::H_PROXY,
+];
 pub const CGI_VARS: &[&str] = &[
     consts::CGI_VAR_AUTH_TYPE, consts::CGI_VAR_CONTENT_LENGTH, consts::CGI_VAR_CONTENT_TYPE,
     consts::CGI_VAR_GATEWAY_INTERFACE, consts::CGI_VAR_PATH_INFO, consts::CGI_VAR_PATH_TRANSLATED,
@@ -26,127 +38,329 @@ pub const CGI_VARS: &[&str] = &[
 
 pub struct CgiRunner<'a> {
     script_path: &'a str,
+    path_info: &'a str,
     request: &'a mut Request,
     conn_info: &'a ConnInfo,
     config: &'a Config,
     is_nph: bool,
+    auth_scheme: Option<&'a str>,
+    timed_out: Arc<AtomicBool>,
 }
 
 impl<'a> CgiRunner<'a> {
-    pub fn new(path: &'a str, request: &'a mut Request, conn: &'a ConnInfo, config: &'a Config, is_nph: bool) -> Self {
+    pub fn new(
+        path: &'a str,
+        path_info: &'a str,
+        request: &'a mut Request,
+        conn: &'a ConnInfo,
+        config: &'a Config,
+        is_nph: bool,
+        auth_scheme: Option<&'a str>,
+    ) -> Self {
         CgiRunner {
             script_path: path,
+            path_info,
             request,
             conn_info: conn,
             config,
             is_nph,
+            auth_scheme,
+            timed_out: Arc::new(AtomicBool::new(false)),
         }
     }
 
-    pub async fn get_response(&mut self) -> MiddlewareResult<()> {
-        match self.get_script_output().await {
-            Some(output) if output.status.success() => {
-                if self.is_nph {
-                    return Err(MiddlewareOutput::Bytes(output.stdout, false));
-                } else if output.stdout.is_empty() {
-                    log::warn(format!("CGI script `{}` returned empty response!", self.script_path));
-                } else {
-                    let mut res = format!("{} {} \r\n", HttpVersion::Http11, Status::Ok).into_bytes();
-                    let out = Self::replace_crlf_nl(output.stdout);
-                    res.extend(out);
-
-                    let mut null = vec![];
-                    if let Ok(response) = Response::new(&mut res.as_slice(), &mut null).await {
-                        log::info(format!("({}) {} {}", response.status, self.request.method, self.request.uri));
-                        return Err(MiddlewareOutput::Response(response, false));
-                    }
+    pub async fn get_response(&mut self) -> MiddlewareResult<String> {
+        let executor = match self.command_by_extension() {
+            Ok(executor) => executor,
+            Err(ext) => {
+                log::warn(format!("CGI scripts with file extension `.{}` are not allowed!", ext));
+                return Err(MiddlewareOutput::Error(Status::Forbidden, false));
+            }
+        };
+
+        let (head, mut body) = match self.spawn_script(&executor).await {
+            Some(output) => output,
+            _ => return Err(MiddlewareOutput::Error(Status::InternalServerError, false)),
+        };
+
+        if self.is_nph {
+            let mut bytes = vec![];
+            while let Some(chunk) = body.next().await {
+                if chunk.is_err() && self.timed_out.load(Ordering::SeqCst) {
+                    return Err(MiddlewareOutput::Error(Status::GatewayTimeout, false));
                 }
+                bytes.extend(chunk?);
             }
-            Some(output) => {
-                log::warn(format!("Error in execution of CGI script `{}`:", self.script_path));
-                for line in String::from_utf8_lossy(&output.stderr).lines() {
-                    log::warn(format!("| {}", line));
+            return Err(MiddlewareOutput::Bytes(bytes, false));
+        }
+
+        let head = match head.await {
+            _ if self.timed_out.load(Ordering::SeqCst) => {
+                return Err(MiddlewareOutput::Error(Status::GatewayTimeout, false));
+            }
+            Ok(head) if !head.is_empty() => head,
+            _ => {
+                log::warn(format!("CGI script `{}` returned empty response!", self.script_path));
+                return Err(MiddlewareOutput::Error(Status::InternalServerError, false));
+            }
+        };
+
+        let mut status = None;
+        let mut location = None;
+        let mut fields = String::new();
+        for line in &head {
+            let (name, value) = match line.find(':') {
+                Some(index) => (line[..index].trim().to_ascii_lowercase(), line[index + 1..].trim()),
+                _ => (String::new(), ""),
+            };
+            match name.as_str() {
+                consts::H_CGI_STATUS => status = Some(value),
+                consts::H_CONTENT_LENGTH | consts::H_TRANSFER_ENCODING => {}
+                _ => {
+                    if name == consts::H_LOCATION {
+                        location = Some(value);
+                    }
+                    fields.push_str(&format!("{}\r\n", line));
                 }
             }
-            _ => {}
         }
-        Err(MiddlewareOutput::Error(Status::InternalServerError, false))
+
+        let status = match (status, location) {
+            (None, Some(location)) if location.starts_with('/') => return Ok(location.to_string()),
+            (None, Some(_)) => Status::Found,
+            (None, None) => Status::Ok,
+            (Some(status), _) => match status.get(..3).and_then(|code| code.parse::<usize>().ok()) {
+                Some(code) if code >= 200 => match Status::try_from(code) {
+                    Ok(status) => status,
+                    _ => return Err(self.invalid_status_output(status)),
+                },
+                _ => return Err(self.invalid_status_output(status)),
+            },
+        };
+        let raw = format!("{} {} \r\n{}\r\n", HttpVersion::Http11, status, fields);
+        let mut response = match Response::new(&mut raw.as_bytes()).await {
+            Ok(response) => response,
+            _ => {
+                log::warn(format!("CGI script `{}` returned malformed headers!", self.script_path));
+                return Err(MiddlewareOutput::Error(Status::InternalServerError, false));
+            }
+        };
+
+        response.headers.set_one(consts::H_TRANSFER_ENCODING, consts::H_T_ENC_CHUNKED);
+        response.headers.set_one(consts::H_ACCEPT_RANGES, consts::H_RANGE_UNIT_NONE);
+        response.set_chunked();
+        response.body = Some(Body::Stream(body));
+        middleware::log_request(self.request, response.status, &self.request.uri);
+        Err(MiddlewareOutput::Response(Box::new(response), false))
     }
 
-    async fn get_script_output(&mut self) -> Option<Output> {
-        let uri = self.request.uri.to_string();
-        let uri_no_file = &uri[..uri.rfind('/')?];
-        let remote_addr = &self.conn_info.remote_addr.to_string();
-        let local_addr = &self.conn_info.local_addr.to_string();
-        let query_string = match &self.request.uri {
-            Uri::OriginForm { path, .. } => path.query_as_string(),
-            Uri::AbsoluteForm { path, .. } => path.query_as_string(),
-            _ => String::new(),
+    async fn spawn_script(
+        &mut self,
+        executor: &str,
+    ) -> Option<(oneshot::Receiver<Vec<String>>, mpsc::Receiver<io::Result<Vec<u8>>>)> {
+        let mut body = vec![];
+        match &mut self.request.get_body_mut() {
+            Some(Body::Bytes(bytes)) => body = mem::take(bytes),
+            Some(Body::File(file, range)) => {
+                body.resize(range.high - range.low, 0);
+                file.seek(SeekFrom::Start(range.low as u64)).await.ok()?;
+                file.read_exact(&mut body).await.ok()?;
+            }
+            _ => {}
+        };
+
+        let (script_name, query_string) = match &self.request.uri {
+            Uri::OriginForm { path } | Uri::AbsoluteForm { path, .. } => {
+                let uri = path.to_string();
+                let query_string = uri.find('?').map(|index| uri[index + 1..].to_string()).unwrap_or_default();
+                let path = format!("/{}", path.path_as_string());
+                let script_name = path.strip_suffix(self.path_info.trim_end_matches('/')).unwrap_or(&path);
+                (script_name.to_string(), query_string)
+            }
+            _ => return None,
+        };
+        let content_length = if body.is_empty() { String::new() } else { body.len().to_string() };
+        let path_translated = match self.path_info {
+            "" => String::new(),
+            path_info => format!("{}{}", self.config.file_root, path_info),
         };
+        let remote_addr = &self.conn_info.remote_addr.ip().to_string();
+        let server_name = self.request.host().unwrap_or_else(|| self.conn_info.local_addr.ip().to_string());
+        let (auth_type, remote_user) = self.authenticated_user();
 
         let cgi_var_values = &[
-            "", &self.header_or_empty(consts::H_CONTENT_LENGTH), &self.header_or_empty(consts::H_CONTENT_TYPE),
-            "CGI/1.1", uri_no_file, uri_no_file, &query_string, &remote_addr, &remote_addr, "", "",
-            &self.request.method.to_string(), &uri, &local_addr, &self.conn_info.local_addr.port().to_string(),
-            &HttpVersion::Http11.to_string(), consts::SERVER_NAME_VERSION,
+            auth_type, &content_length, &self.header_or_empty(consts::H_CONTENT_TYPE), consts::CGI_GATEWAY_INTERFACE,
+            self.path_info, &path_translated, &query_string, remote_addr, remote_addr, "", &remote_user,
+            &self.request.method.to_string(), &script_name,
+            &server_name, &self.conn_info.local_addr.port().to_string(), &self.request.http_version.to_string(),
+            consts::SERVER_NAME_VERSION,
         ];
 
-        let command = match self.command_by_extension() {
-            Ok(command) => command,
-            Err(ext) => {
-                log::warn(format!("No CGI script executor found for file extension `.{}`!", ext));
-                return None;
+        let mut command = match executor {
+            "" => Command::new(self.script_path),
+            executor => {
+                let mut command = Command::new(executor);
+                command.arg(self.script_path);
+                command
             }
         };
-
-        let mut command = Command::new(command);
         let script = command
-            .arg(self.script_path)
-            .envs(CGI_VARS.iter().zip(cgi_var_values))
+            .envs(CGI_VARS.iter().zip(cgi_var_values).filter(|(name, value)| {
+                !value.is_empty() || **name == consts::CGI_VAR_QUERY_STRING
+            }))
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .stderr(Stdio::piped())
+            .process_group(0);
 
         for (header_name, header_values) in self.request.headers.get_all() {
             if !VAR_EXCLUDED_HEADERS.contains(&&**header_name) {
-                let env_var_name = "HTTP_".to_string() + &header_name.to_ascii_uppercase().replace('_', "-");
+                let env_var_name = "HTTP_".to_string() + &header_name.to_ascii_uppercase().replace('-', "_");
                 script.env(&env_var_name, header_values.join(", "));
             }
         }
 
         let mut script = script.spawn().ok()?;
-        let mut body = vec![];
-        match &mut self.request.get_body_mut() {
-            Some(Body::Bytes(bytes)) => body = bytes.to_vec(),
-            Some(Body::Stream(file, len)) => {
-                body.reserve(*len);
-                file.read_exact(&mut body).await.ok()?;
+
+        let mut stdin = script.stdin.take()?;
+        let script_path = self.script_path.to_string();
+        task::spawn_blocking(move || match stdin.write_all(&body) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                log::warn(format!("Request body could not be written to CGI script `{}`!", script_path));
             }
             _ => {}
-        };
-        &script.stdin.as_mut()?.write(&body).ok()?;
-        script.wait_with_output().ok()
+        });
+        let mut stderr = script.stderr.take()?;
+        let stderr = task::spawn_blocking(move || {
+            let mut errors = String::new();
+            stderr.read_to_string(&mut errors).map(|_| errors).unwrap_or_default()
+        });
+
+        let (head_sender, head) = oneshot::channel();
+        let (mut body_sender, body) = mpsc::channel(consts::CGI_OUTPUT_BUFFER_CHUNKS);
+        let script_path = self.script_path.to_string();
+        let is_nph = self.is_nph;
+        let limits = self.config.cgi_limits;
+        let finished = Arc::new((Mutex::new(false), Condvar::new()));
+        let timed_out = Arc::clone(&self.timed_out);
+
+        if let Some(timeout) = limits.timeout() {
+            let finished = Arc::clone(&finished);
+            let timed_out = Arc::clone(&timed_out);
+            let pid = script.id();
+            let script_path = script_path.clone();
+            task::spawn_blocking(move || {
+                let (lock, condvar) = &*finished;
+                let (finished, _) = condvar.wait_timeout_while(lock.lock().unwrap(), timeout, |f| !*f).unwrap();
+                if !*finished {
+                    timed_out.store(true, Ordering::SeqCst);
+                    kill_process_group(pid);
+                    log::warn(format!("CGI script `{}` timed out!", script_path));
+                }
+            });
+        }
+
+        task::spawn_blocking(move || {
+            let output_limit = limits.max_output.map(|max| max as u64 + 1).unwrap_or(u64::MAX);
+            let mut stdout = std::io::BufReader::new(script.stdout.take().unwrap().take(output_limit));
+            if !is_nph {
+                let _ = head_sender.send(Self::read_head(&mut stdout));
+            }
+
+            loop {
+                let mut chunk = vec![0; consts::READ_CHUNK_SIZE];
+                let result = match stdout.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(len) => {
+                        chunk.truncate(len);
+                        Ok(chunk)
+                    }
+                    Err(e) => Err(e),
+                };
+                let failed = result.is_err();
+                if task::block_on(body_sender.send(result)).is_err() || failed {
+                    kill_process_group(script.id());
+                    break;
+                }
+            }
+
+            if stdout.get_ref().limit() == 0 {
+                kill_process_group(script.id());
+                log::warn(format!("CGI script `{}` exceeded the output limit!", script_path));
+                let _ = task::block_on(body_sender.send(Err(io::ErrorKind::InvalidData.into())));
+            } else if timed_out.load(Ordering::SeqCst) {
+                let _ = task::block_on(body_sender.send(Err(io::ErrorKind::TimedOut.into())));
+            }
+            drop(body_sender);
+
+            let (lock, condvar) = &*finished;
+            *lock.lock().unwrap() = true;
+            condvar.notify_one();
+            let success = script.wait().map(|status| status.success()).unwrap_or(false);
+            let errors = task::block_on(stderr);
+            if !success {
+                log::warn(format!("Error in execution of CGI script `{}`:", script_path));
+                for line in errors.lines() {
+                    log::warn(format!("| {}", line));
+                }
+            }
+        });
+        Some((head, body))
+    }
+
+    fn read_head(stdout: &mut impl BufRead) -> Vec<String> {
+        let mut head = vec![];
+        let mut line = String::new();
+        while matches!(stdout.read_line(&mut line), Ok(len) if len > 0) {
+            let trimmed = line.trim_end_matches(&['\r', '\n'][..]);
+            if trimmed.is_empty() {
+                break;
+            }
+            head.push(trimmed.to_string());
+            line.clear();
+        }
+        head
+    }
+
+    fn invalid_status_output(&self, status: &str) -> MiddlewareOutput {
+        log::warn(format!("CGI script `{}` returned invalid status `{}`!", self.script_path, status));
+        MiddlewareOutput::Error(Status::InternalServerError, false)
     }
 
     fn header_or_empty(&self, name: &str) -> String {
         self.request.headers.get(name).map(|header| &header[0]).cloned().unwrap_or(String::new())
     }
 
-    fn replace_crlf_nl(res: Vec<u8>) -> Vec<u8> {
-        let body_index = res.windows(2).position(|a| a[0] == b'\n' && a[1] == b'\n').unwrap_or(res.len() - 2) + 2;
-        let mut fixed = res[..body_index]
-            .iter()
-            .flat_map(|b| if *b == b'\n' { vec![b'\r', b'\n'] } else { vec![*b] })
-            .collect::<Vec<_>>();
-        fixed.extend(&res[body_index..]);
-        fixed
+    fn authenticated_user(&self) -> (&'static str, String) {
+        let credentials = self.request.headers.get(consts::H_AUTHORIZATION).and_then(|auth| {
+            auth[0].split_once(' ').map(|(_, credentials)| credentials.trim_matches(consts::OPTIONAL_WHITESPACE))
+        });
+        match (self.auth_scheme, credentials) {
+            (Some(consts::H_AUTH_BASIC), Some(credentials)) => {
+                let credentials = base64::decode(credentials).ok().and_then(|c| String::from_utf8(c).ok());
+                let user = credentials.and_then(|c| c.split(':').next().map(str::to_string));
+                ("Basic", user.unwrap_or_default())
+            }
+            (Some(consts::H_AUTH_DIGEST), Some(credentials)) => {
+                let user = digest_auth::parse_auth_params(credentials).remove("username");
+                ("Digest", user.unwrap_or_default())
+            }
+            (Some(consts::H_AUTH_BEARER), _) => ("Bearer", String::new()),
+            _ => ("", String::new()),
+        }
     }
 
-    fn command_by_extension(&self) -> Result<&str, &str> {
-        let ext = &*Path::new(self.script_path).extension().and_then(|s| s.to_str()).unwrap_or("");
+    fn command_by_extension(&self) -> Result<String, &str> {
+        let ext = Path::new(self.script_path).extension().and_then(|s| s.to_str()).unwrap_or("");
         match self.config.cgi_executors.get(ext) {
-            Some(command) => Ok(command),
+            Some(command) => Ok(command.clone()),
             _ => Err(ext),
         }
     }
 }
+
+fn kill_process_group(pid: u32) {
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
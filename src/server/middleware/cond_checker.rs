@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Timelike, Utc};
 
 use crate::consts;
 use crate::http::headers::Headers;
@@ -6,6 +6,7 @@ use crate::http::response::Status;
 use crate::server::middleware::{MiddlewareOutput, MiddlewareResult};
 use crate::util;
 
+#[derive(Clone)]
 pub struct CondInfo {
     pub etag: Option<String>,
     pub last_modified: Option<DateTime<Utc>>,
@@ -13,6 +14,7 @@ pub struct CondInfo {
 
 impl CondInfo {
     pub fn new(etag: Option<String>, last_modified: Option<DateTime<Utc>>) -> Self {
+        let last_modified = last_modified.and_then(|time| time.with_nanosecond(0));
         CondInfo { etag, last_modified }
     }
 }
@@ -46,7 +48,7 @@ impl<'a> ConditionalChecker<'a> {
             }
         } else if let Some(since) = self.headers.get(consts::H_IF_UNMODIFIED_SINCE) {
             if let Some(last_modified) = self.info.last_modified {
-                return match util::parse_time_imf(&since[0]) {
+                return match util::parse_time_http(&since[0]) {
                     Some(since) => last_modified <= since,
                     _ => true,
                 };
@@ -62,8 +64,8 @@ impl<'a> ConditionalChecker<'a> {
             }
         } else if let Some(since) = self.headers.get(consts::H_IF_MODIFIED_SINCE) {
             if let Some(last_modified) = self.info.last_modified {
-                return match util::parse_time_imf(&since[0]) {
-                    Some(since) => last_modified > since,
+                return match util::parse_time_http(&since[0]) {
+                    Some(since) if since <= util::get_time_utc() => last_modified > since,
                     _ => true,
                 };
             }
@@ -72,20 +74,15 @@ impl<'a> ConditionalChecker<'a> {
     }
 
     fn check_range_header(&self) -> bool {
-        if self.headers.contains(consts::H_RANGE) {
-            if let Some(etag_or_date) = self.headers.get(consts::H_IF_RANGE) {
-                let etag_or_date = &etag_or_date[0];
-                if let Some(since) = util::parse_time_imf(etag_or_date) {
-                    if let Some(last_modified) = self.info.last_modified {
-                        return last_modified <= since;
-                    }
-                } else if etag_or_date.starts_with("\"") && etag_or_date.ends_with("\"") {
-                    if let Some(etag) = &self.info.etag {
-                        return etag_or_date == etag;
-                    }
+        match (self.headers.contains(consts::H_RANGE), self.headers.get(consts::H_IF_RANGE)) {
+            (true, Some(validator)) => {
+                let validator = &validator[0];
+                match util::parse_time_http(validator) {
+                    Some(date) => self.info.last_modified == Some(date),
+                    _ => validator.starts_with('"') && self.info.etag.as_ref() == Some(validator),
                 }
             }
+            _ => true,
         }
-        true
     }
 }
@@ -0,0 +1,48 @@
+use async_std::io;
+use async_std::net::TcpStream;
+
+use crate::{consts, log};
+use crate::http::message::MessageBuilder;
+use crate::http::request::{Method, Request};
+use crate::http::response::{Response, Status};
+use crate::http::uri::Uri;
+use crate::server::config::connect_proxy::ConnectProxy;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+
+pub struct ConnectTunneler<'a> {
+    request: &'a Request,
+    connect_proxy: &'a ConnectProxy,
+}
+
+impl<'a> ConnectTunneler<'a> {
+    pub fn new(request: &'a Request, connect_proxy: &'a ConnectProxy) -> Self {
+        ConnectTunneler { request, connect_proxy }
+    }
+
+    pub async fn check(&self) -> MiddlewareResult<()> {
+        if self.request.method != Method::Connect {
+            return Ok(());
+        } else if !self.connect_proxy.enabled {
+            return Err(MiddlewareOutput::Status(Status::MethodNotAllowed, false));
+        }
+
+        let (host, port) = match &self.request.uri {
+            Uri::AuthorityForm { authority } => (&authority.host, authority.port.unwrap_or_default()),
+            _ => return Err(MiddlewareOutput::Error(Status::BadRequest, true)),
+        };
+        let destination = format!("{}:{}", host, port);
+        if !self.connect_proxy.allows(host, port) {
+            log::warn(format!("CONNECT to `{}` is not allowed!", destination));
+            return Err(MiddlewareOutput::Error(Status::Forbidden, false));
+        }
+
+        let stream = match io::timeout(consts::MAX_READ_TIMEOUT, TcpStream::connect(&destination)).await {
+            Ok(stream) => stream,
+            _ => return Err(MiddlewareOutput::Error(Status::BadGateway, false)),
+        };
+        let response = MessageBuilder::<Response>::new().without_header(consts::H_CONTENT_LENGTH).build();
+
+        middleware::log_request(self.request, response.status, &destination);
+        Err(MiddlewareOutput::Upgrade(Box::new(response), stream))
+    }
+}
@@ -0,0 +1,98 @@
+use async_std::path::Path;
+
+use crate::consts;
+use crate::http::headers::Headers;
+use crate::http::response::Status;
+use crate::server::file_source::FileSource;
+use crate::server::middleware::{MiddlewareOutput, MiddlewareResult};
+use crate::util;
+
+pub struct MediaRange {
+    pub media_type: String,
+    pub quality: f32,
+}
+
+pub struct ContentNegotiator<'a> {
+    target: &'a str,
+    headers: &'a Headers,
+    file_source: &'a dyn FileSource,
+}
+
+impl<'a> ContentNegotiator<'a> {
+    pub fn new(target: &'a str, headers: &'a Headers, file_source: &'a dyn FileSource) -> Self {
+        ContentNegotiator { target, headers, file_source }
+    }
+
+    pub async fn get_target(&self) -> MiddlewareResult<Option<String>> {
+        let candidates = self.get_candidates().await;
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+
+        let ranges = match self.headers.get(consts::H_ACCEPT) {
+            Some(accept) => accept.iter().filter_map(|range| parse_media_range(range)).collect::<Vec<_>>(),
+            _ => return Ok(Some(candidates[0].0.clone())),
+        };
+
+        let mut best: Option<(&String, f32)> = None;
+        for (path, media_type) in &candidates {
+            let quality = get_quality(&ranges, media_type);
+            if quality > 0.0 && best.map(|(_, best_quality)| quality > best_quality).unwrap_or(true) {
+                best = Some((path, quality));
+            }
+        }
+
+        match best {
+            Some((path, _)) => Ok(Some(path.clone())),
+            _ => Err(MiddlewareOutput::Error(Status::NotAcceptable, false)),
+        }
+    }
+
+    async fn get_candidates(&self) -> Vec<(String, String)> {
+        let path = Path::new(self.target);
+        let (dir, base_name) = match (path.parent(), path.file_name().and_then(|s| s.to_str())) {
+            (Some(dir), Some(base_name)) => (dir, format!("{}.", base_name)),
+            _ => return vec![],
+        };
+
+        let dir = dir.to_string_lossy();
+        let mut candidates = match self.file_source.read_dir(&dir).await {
+            Some(files) => files
+                .iter()
+                .filter_map(|file| {
+                    let ext = file.name.strip_prefix(&base_name)?;
+                    if ext.is_empty() || ext.contains('.') || file.info.is_dir {
+                        return None;
+                    }
+                    Some((format!("{}/{}", dir, file.name), util::media_type_by_ext(ext).to_string()))
+                })
+                .collect::<Vec<_>>(),
+            _ => return vec![],
+        };
+        candidates.sort();
+        candidates
+    }
+}
+
+pub fn parse_media_range(range: &str) -> Option<MediaRange> {
+    let mut parts = range.split(';').map(|part| part.trim_matches(consts::OPTIONAL_WHITESPACE));
+    let media_type = parts.next()?.to_ascii_lowercase();
+    if !media_type.contains('/') {
+        return None;
+    }
+
+    let quality = parts
+        .filter_map(|param| param.strip_prefix("q="))
+        .next()
+        .map(|q| q.parse::<f32>().ok())
+        .unwrap_or(Some(1.0))?;
+    Some(MediaRange { media_type, quality })
+}
+
+pub fn get_quality(ranges: &[MediaRange], media_type: &str) -> f32 {
+    let type_wildcard = format!("{}/*", &media_type[..media_type.find('/').unwrap_or(0)]);
+    let exact = ranges.iter().find(|r| r.media_type == media_type);
+    let partial = ranges.iter().find(|r| r.media_type == type_wildcard);
+    let any = ranges.iter().find(|r| r.media_type == "*/*");
+    exact.or(partial).or(any).map(|r| r.quality).unwrap_or(0.0)
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use linked_hash_map::LinkedHashMap;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::{consts, util};
+use crate::http::message::MessageBuilder;
+use crate::http::request::Request;
+use crate::http::response::{Response, Status};
+use crate::http::uri::Uri;
+use crate::server::config::auth_info::{DigestAlgorithm, DigestAuthInfo};
+use crate::server::config::Config;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+
+#[derive(PartialEq)]
+enum NonceStatus {
+    Valid,
+    Stale,
+    Invalid,
+}
+
+pub struct NonceCache {
+    secret: String,
+    nonces: LinkedHashMap<String, u32>,
+}
+
+impl NonceCache {
+    pub fn new() -> Self {
+        NonceCache {
+            secret: util::to_hex(&rand::random::<[u8; 32]>()),
+            nonces: LinkedHashMap::new(),
+        }
+    }
+
+    fn issue(&mut self) -> String {
+        let data = format!("{}:{}", util::get_time_utc().timestamp(), util::to_hex(&rand::random::<[u8; 8]>()));
+        let nonce = base64::encode(format!("{}:{}", data, self.sign(&data)));
+
+        if self.nonces.len() >= consts::DIGEST_NONCE_CACHE_SIZE {
+            self.nonces.pop_front();
+        }
+        self.nonces.insert(nonce.clone(), 0);
+        nonce
+    }
+
+    fn verify(&mut self, nonce: &str, count: u32, record: bool) -> NonceStatus {
+        let decoded = match base64::decode(nonce).map(String::from_utf8) {
+            Ok(Ok(decoded)) => decoded,
+            _ => return NonceStatus::Invalid,
+        };
+
+        let parts = decoded.rsplitn(2, ':').collect::<Vec<_>>();
+        if parts.len() != 2 || parts[0] != self.sign(parts[1]) {
+            return NonceStatus::Invalid;
+        }
+
+        let issued = parts[1].split(':').next().and_then(|t| t.parse::<i64>().ok()).unwrap_or(0);
+        if util::get_time_utc().timestamp() - issued > consts::DIGEST_NONCE_LIFETIME.as_secs() as i64 {
+            self.nonces.remove(nonce);
+            return NonceStatus::Stale;
+        }
+
+        match self.nonces.get_mut(nonce) {
+            Some(last_count) if count > *last_count => {
+                if record {
+                    *last_count = count;
+                }
+                NonceStatus::Valid
+            }
+            Some(_) => NonceStatus::Invalid,
+            _ => NonceStatus::Stale,
+        }
+    }
+
+    fn sign(&self, data: &str) -> String {
+        util::to_hex(&Sha256::digest(format!("{}:{}", data, self.secret).as_bytes()))
+    }
+}
+
+impl Default for NonceCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct DigestAuthChecker<'a> {
+    request: &'a Request,
+    config: &'a Config,
+    raw_target: &'a str,
+    nonces: &'a Mutex<NonceCache>,
+    record_count: bool,
+}
+
+impl<'a> DigestAuthChecker<'a> {
+    pub fn new(request: &'a Request, config: &'a Config, raw_target: &'a str, nonces: &'a Mutex<NonceCache>) -> Self {
+        DigestAuthChecker { request, config, raw_target, nonces, record_count: true }
+    }
+
+    pub fn without_recording(mut self) -> Self {
+        self.record_count = false;
+        self
+    }
+
+    pub fn check(&self) -> MiddlewareResult<bool> {
+        let target = self.request.uri.to_string();
+        let auth_info = match self.config.auth_rule_for(&self.config.digest_auth, &target) {
+            Some(auth_info) => auth_info,
+            _ => return Ok(false),
+        };
+        match self.request.headers.get(consts::H_AUTHORIZATION) {
+            Some(auth) => self.check_auth_header(&auth[0], auth_info),
+            _ => self.www_authenticate_output(auth_info, false),
+        }
+    }
+
+    fn check_auth_header(&self, auth: &str, auth_info: &DigestAuthInfo) -> MiddlewareResult<bool> {
+        let auth = auth.splitn(2, ' ').collect::<Vec<_>>();
+        if auth.len() < 2 || !auth[0].eq_ignore_ascii_case(consts::H_AUTH_DIGEST) {
+            return self.www_authenticate_output(auth_info, false);
+        }
+
+        let params = parse_auth_params(auth[1]);
+        let param = |name: &str| params.get(name).map(|value| value.as_str());
+        let (user, nonce, uri, response, count, cnonce) = match (
+            param("username"), param(consts::H_AUTH_NONCE), param("uri"), param("response"), param("nc"),
+            param("cnonce"),
+        ) {
+            (Some(user), Some(nonce), Some(uri), Some(response), Some(count), Some(cnonce)) => {
+                (user, nonce, uri, response, count, cnonce)
+            }
+            _ => return self.www_authenticate_output(auth_info, false),
+        };
+
+        let algorithm_matches = match param(consts::H_AUTH_ALGORITHM) {
+            Some(algorithm) => algorithm.eq_ignore_ascii_case(algorithm_name(auth_info.algorithm)),
+            _ => auth_info.algorithm == DigestAlgorithm::Md5,
+        };
+        let uri_matches = Uri::from(&self.request.method, uri).map(|u| u.to_string() == self.raw_target).ok();
+        let count = u32::from_str_radix(count, 16);
+        if param(consts::H_AUTH_REALM) != Some(&auth_info.realm) || param(consts::H_AUTH_QOP) !=
+            Some(consts::H_AUTH_QOP_AUTH) || !algorithm_matches || uri_matches != Some(true) || count.is_err() {
+            return self.www_authenticate_output(auth_info, false);
+        }
+
+        let credentials = match auth_info.credentials.iter().find(|c| c.user == user) {
+            Some(credentials) => credentials,
+            _ => return self.www_authenticate_output(auth_info, false),
+        };
+
+        let ha2 = hash(auth_info.algorithm, &format!("{}:{}", self.request.method, uri));
+        let expected = hash(auth_info.algorithm, &format!(
+            "{}:{}:{}:{}:{}:{}",
+            credentials.password_hash, nonce, param("nc").unwrap(), cnonce, consts::H_AUTH_QOP_AUTH, ha2,
+        ));
+        if !util::constant_time_eq(expected.as_bytes(), response.to_ascii_lowercase().as_bytes()) {
+            return self.www_authenticate_output(auth_info, false);
+        }
+
+        let status = self.nonces.lock().unwrap().verify(nonce, count.unwrap(), self.record_count);
+        match status {
+            NonceStatus::Valid => Ok(true),
+            status => self.www_authenticate_output(auth_info, status == NonceStatus::Stale),
+        }
+    }
+
+    fn www_authenticate_output(&self, auth_info: &DigestAuthInfo, stale: bool) -> MiddlewareResult<bool> {
+        middleware::log_request(self.request, Status::Unauthorized, &self.request.uri);
+
+        let nonce = self.nonces.lock().unwrap().issue();
+        let mut auth = format!(
+            "{} {}=\"{}\", {}=\"{}\", {}={}, {}=\"{}\"",
+            consts::H_AUTH_DIGEST, consts::H_AUTH_REALM, auth_info.realm, consts::H_AUTH_QOP,
+            consts::H_AUTH_QOP_AUTH, consts::H_AUTH_ALGORITHM, algorithm_name(auth_info.algorithm),
+            consts::H_AUTH_NONCE, nonce,
+        );
+        if stale {
+            auth.push_str(&format!(", {}=true", consts::H_AUTH_STALE));
+        }
+
+        let response = MessageBuilder::<Response>::new()
+            .with_status(Status::Unauthorized)
+            .with_header(consts::H_WWW_AUTHENTICATE, &auth)
+            .build();
+        Err(MiddlewareOutput::Response(Box::new(response), false))
+    }
+}
+
+pub fn parse_auth_params(params: &str) -> HashMap<String, String> {
+    let separators: &[char] = &[',', ' ', '\t'];
+    let mut parsed = HashMap::new();
+    let mut rest = params.trim_start_matches(separators);
+
+    while let Some(index) = rest.find('=') {
+        let name = rest[..index].trim_matches(consts::OPTIONAL_WHITESPACE).to_ascii_lowercase();
+        rest = rest[index + 1..].trim_start_matches(consts::OPTIONAL_WHITESPACE);
+
+        let mut value = String::new();
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let mut escaped = false;
+            let mut end = quoted.len();
+            for (index, ch) in quoted.char_indices() {
+                match ch {
+                    _ if escaped => {
+                        value.push(ch);
+                        escaped = false;
+                    }
+                    '\\' => escaped = true,
+                    '"' => {
+                        end = index + 1;
+                        break;
+                    }
+                    _ => value.push(ch),
+                }
+            }
+            rest = &quoted[end..];
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            value.push_str(rest[..end].trim_matches(consts::OPTIONAL_WHITESPACE));
+            rest = &rest[end..];
+        }
+
+        parsed.insert(name, value);
+        rest = rest.trim_start_matches(separators);
+    }
+    parsed
+}
+
+fn hash(algorithm: DigestAlgorithm, data: &str) -> String {
+    match algorithm {
+        DigestAlgorithm::Md5 => util::to_hex(&Md5::digest(data.as_bytes())),
+        DigestAlgorithm::Sha256 => util::to_hex(&Sha256::digest(data.as_bytes())),
+    }
+}
+
+fn algorithm_name(algorithm: DigestAlgorithm) -> &'static str {
+    match algorithm {
+        DigestAlgorithm::Md5 => "MD5",
+        DigestAlgorithm::Sha256 => "SHA-256",
+    }
+}
@@ -1,82 +1,154 @@
-use std::time::{self, Duration};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{self, Duration, SystemTime};
 
-use async_std::fs;
-use async_std::fs::DirEntry;
 use async_std::path::Path;
 use chrono::{TimeZone, Utc};
-use futures::StreamExt;
 
-use crate::consts;
+use crate::{consts, util};
 use crate::http::response::Status;
+use crate::server::config::Config;
+use crate::server::config::route_spec::RouteSpec;
+use crate::server::file_source::{DirEntry, FileSource};
 use crate::server::middleware::{MiddlewareOutput, MiddlewareResult};
+use crate::server::middleware::cond_checker::CondInfo;
 use crate::server::template::{SubstitutionMap, TemplateSubstitution};
 use crate::server::template::templates::Templates;
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListingFormat {
+    Html,
+    Json,
+}
+
+struct ListingEntry {
+    file: DirEntry,
+    name: String,
+    path: String,
+    modified: Duration,
+}
+
 pub struct DirectoryLister<'a> {
     target: &'a str,
     dir: &'a str,
     templates: &'a Templates,
+    config: &'a Config,
+    file_source: &'a dyn FileSource,
 }
 
 impl<'a> DirectoryLister<'a> {
-    pub fn new(target: &'a str, dir: &'a str, templates: &'a Templates) -> Self {
-        DirectoryLister { target, dir, templates }
+    pub fn new(
+        target: &'a str,
+        dir: &'a str,
+        templates: &'a Templates,
+        config: &'a Config,
+        file_source: &'a dyn FileSource,
+    ) -> Self {
+        DirectoryLister { target, dir, templates, config, file_source }
     }
 
-    pub async fn get_listing_body(&self) -> MiddlewareResult<String> {
-        let mut files = match fs::read_dir(self.dir).await {
-            Ok(files) => files
-                .filter_map(|f| async {
-                    let file = f.ok()?;
-                    let is_file = file.metadata().await.ok()?.is_file();
-                    Some((file, is_file))
-                })
-                .collect::<Vec<_>>().await,
+    pub async fn read_files(&self) -> MiddlewareResult<Vec<DirEntry>> {
+        let files = match self.file_source.read_dir(self.dir).await {
+            Some(files) => files,
             _ => return Err(MiddlewareOutput::Error(Status::NotFound, false)),
         };
 
-        let custom_message = match files.iter().find(|(f, _)| f.file_name() == consts::DIR_LISTING_VIEWABLE) {
-            Some((file, _)) => fs::read_to_string(file.path()).await?.replace('\n', "<br>"),
-            _ => return Err(MiddlewareOutput::Error(Status::Forbidden, false)),
+        let has_marker = files.iter().any(|f| f.name == consts::DIR_LISTING_VIEWABLE);
+        if !self.is_enabled(has_marker) {
+            return Err(MiddlewareOutput::Error(Status::Forbidden, false));
+        }
+        Ok(files)
+    }
+
+    pub fn get_cond_info(files: &[DirEntry], dir_modified: SystemTime, format: ListingFormat) -> CondInfo {
+        let mut hasher = DefaultHasher::new();
+        format.hash(&mut hasher);
+        let mut files = files.iter().collect::<Vec<_>>();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        for file in &files {
+            (&file.name, file.info.is_dir, file.info.len, file.info.modified).hash(&mut hasher);
+        }
+
+        let last_modified = files.iter().map(|f| f.info.modified).fold(dir_modified, SystemTime::max);
+        let etag = format!("\"{:x}-{:x}\"", hasher.finish(), files.len());
+        CondInfo::new(Some(etag), Some(last_modified.into()))
+    }
+
+    pub async fn get_listing_body(&self, mut files: Vec<DirEntry>, format: ListingFormat) -> MiddlewareResult<String> {
+        let marker = files.iter().find(|f| f.name == consts::DIR_LISTING_VIEWABLE);
+        let custom_message = match (marker, format) {
+            (Some(file), ListingFormat::Html) => {
+                let path = format!("{}/{}", self.dir.trim_end_matches('/'), file.name);
+                String::from_utf8(self.file_source.read(&path).await?)?.replace('\n', "<br>")
+            }
+            _ => String::new(),
         };
 
-        files.sort_by_key(|(f, is_file)| (is_file.clone(), f.file_name()));
-        let files = files
-            .into_iter()
-            .map(|(f, _)| f)
-            .filter(|f| !f.file_name().to_string_lossy().starts_with('.'))
-            .collect();
+        files.sort_by_key(|f| (!f.info.is_dir, f.name.clone()));
+        let files = files.into_iter().filter(|f| self.is_listed(&f.name)).collect();
 
-        return match self.get_substituted_template(files, custom_message).await {
+        let body = match format {
+            ListingFormat::Html => self.get_substituted_template(files, custom_message).await,
+            ListingFormat::Json => self.get_json(files),
+        };
+        match body {
             Some(body) => Ok(body),
             _ => Err(MiddlewareOutput::Error(Status::InternalServerError, false)),
+        }
+    }
+
+    pub fn is_enabled(&self, has_marker: bool) -> bool {
+        self.config
+            .dir_listings
+            .iter()
+            .find(|(RouteSpec(rule_regex), _)| rule_regex.is_match(self.target))
+            .map(|(_, enabled)| *enabled)
+            .unwrap_or(has_marker)
+    }
+
+    fn is_listed(&self, name: &str) -> bool {
+        !name.starts_with('.') || (self.config.dir_listing_show_hidden && name != consts::DIR_LISTING_VIEWABLE)
+    }
+
+    fn get_entries(&self, files: Vec<DirEntry>) -> Option<Vec<ListingEntry>> {
+        let path_root = match self.target.strip_prefix('/')? {
+            "" => String::new(),
+            path_root => format!("{}/", path_root),
         };
+        files
+            .into_iter()
+            .map(|file| {
+                let name = file.name.clone() + if file.info.is_dir { "/" } else { "" };
+                let path = format!("{}{}", path_root, name);
+                let modified = file.info.modified.duration_since(time::UNIX_EPOCH).ok()?;
+                Some(ListingEntry { file, name, path, modified })
+            })
+            .collect()
     }
 
     async fn get_substituted_template(&self, files: Vec<DirEntry>, custom_message: String) -> Option<String> {
         let mut sub = SubstitutionMap::new();
         sub.insert("dir".to_string(), TemplateSubstitution::Single(self.target.to_string()));
-        sub.insert("custom_message".to_string(), TemplateSubstitution::Single(custom_message));
+        sub.insert("custom_message".to_string(), TemplateSubstitution::Raw(custom_message));
 
         let mut entry_subs = vec![];
 
         if let Some(parent_path) = Path::new(self.target).parent() {
             let parent = parent_path.to_string_lossy().strip_prefix('/')?.to_string();
             let mut entry_sub = SubstitutionMap::new();
-            Self::insert_entry(&mut entry_sub, parent, "../".to_string(), String::new(), "-".to_string());
+            Self::insert_entry(&mut entry_sub, parent, "../".to_string(), String::new(), "-".to_string(), "folder");
             entry_subs.push(entry_sub);
         }
 
-        for file in files {
-            let metadata = file.metadata().await.ok()?;
-            let name = file.file_name().to_string_lossy().to_string() + if metadata.is_dir() { "/" } else { "" };
-            let path_root = self.target.strip_prefix('/')?.to_string();
-            let path = format!("{}{}", if path_root.is_empty() { String::new() } else { path_root + "/" }, &name);
-            let last_modified = Self::format_time(metadata.modified().ok()?.duration_since(time::UNIX_EPOCH).ok()?);
-            let size = if metadata.is_file() { Self::format_readable_size(metadata.len()) } else { "-".to_string() };
+        for entry in self.get_entries(files)? {
+            let metadata = &entry.file.info;
+            let last_modified = Self::format_time(entry.modified);
+            let size = if metadata.is_dir { "-".to_string() } else { Self::format_readable_size(metadata.len) };
+            let file_ext = Path::new(&entry.file.name).extension().and_then(|s| s.to_str()).unwrap_or("");
+            let icon = if metadata.is_dir { "folder" } else { util::file_category_by_ext(file_ext) };
 
             let mut entry_sub = SubstitutionMap::new();
-            Self::insert_entry(&mut entry_sub, path, name, last_modified, size);
+            Self::insert_entry(&mut entry_sub, entry.path, entry.name, last_modified, size, icon);
             entry_subs.push(entry_sub);
         }
 
@@ -84,11 +156,36 @@ impl<'a> DirectoryLister<'a> {
         self.templates.dir_listing.substitute(&sub)
     }
 
-    fn insert_entry(entry_sub: &mut SubstitutionMap, path: String, name: String, last_modified: String, size: String) {
+    fn get_json(&self, files: Vec<DirEntry>) -> Option<String> {
+        let entries = self
+            .get_entries(files)?
+            .iter()
+            .map(|entry| {
+                let info = &entry.file.info;
+                format!(
+                    "{{\"name\":\"{}\",\"path\":\"/{}\",\"size\":{},\"mtime\":{},\"is_dir\":{}}}",
+                    util::escape_json(&entry.file.name), util::escape_json(&entry.path),
+                    if info.is_dir { 0 } else { info.len }, entry.modified.as_secs(), info.is_dir,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        Some(format!("[{}]", entries))
+    }
+
+    fn insert_entry(
+        entry_sub: &mut SubstitutionMap,
+        path: String,
+        name: String,
+        last_modified: String,
+        size: String,
+        icon: &str,
+    ) {
         entry_sub.insert("path".to_string(), TemplateSubstitution::Single(path));
         entry_sub.insert("name".to_string(), TemplateSubstitution::Single(name));
         entry_sub.insert("last_modified".to_string(), TemplateSubstitution::Single(last_modified));
         entry_sub.insert("size".to_string(), TemplateSubstitution::Single(size));
+        entry_sub.insert("icon".to_string(), TemplateSubstitution::Single(icon.to_string()));
     }
 
     fn format_time(time: Duration) -> String {
@@ -0,0 +1,83 @@
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use linked_hash_map::LinkedHashMap;
+
+use crate::server::file_source::FileInfo;
+
+pub struct CachedFile {
+    pub modified: SystemTime,
+    pub etag: String,
+    pub content: Vec<u8>,
+}
+
+impl CachedFile {
+    fn is_current(&self, info: &FileInfo) -> bool {
+        self.modified == info.modified && self.content.len() as u64 == info.len
+    }
+}
+
+struct Files {
+    entries: LinkedHashMap<String, Arc<CachedFile>>,
+    size: usize,
+}
+
+pub struct CachedFiles {
+    files: Mutex<Files>,
+}
+
+impl CachedFiles {
+    pub fn new() -> Self {
+        CachedFiles { files: Mutex::new(Files { entries: LinkedHashMap::new(), size: 0 }) }
+    }
+
+    pub fn get(&self, path: &str, info: &FileInfo) -> Option<Arc<CachedFile>> {
+        let mut files = self.files.lock().unwrap();
+        let file = Arc::clone(files.entries.get_refresh(path)?);
+        if file.is_current(info) {
+            Some(file)
+        } else {
+            files.remove(path);
+            None
+        }
+    }
+
+    pub fn insert(&self, path: &str, file: CachedFile, max_size: usize) -> Arc<CachedFile> {
+        let file = Arc::new(file);
+        if file.content.len() > max_size {
+            return file;
+        }
+
+        let mut files = self.files.lock().unwrap();
+        files.remove(path);
+        files.size += file.content.len();
+        files.entries.insert(path.to_string(), Arc::clone(&file));
+        while files.size > max_size {
+            match files.entries.pop_front() {
+                Some((_, evicted)) => files.size -= evicted.content.len(),
+                _ => break,
+            }
+        }
+        file
+    }
+
+    pub fn clear(&self) {
+        let mut files = self.files.lock().unwrap();
+        files.entries.clear();
+        files.size = 0;
+    }
+}
+
+impl Default for CachedFiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Files {
+    fn remove(&mut self, path: &str) {
+        if let Some(file) = self.entries.remove(path) {
+            self.size -= file.content.len();
+        }
+    }
+}
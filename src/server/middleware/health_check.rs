@@ -0,0 +1,53 @@
+use crate::consts;
+use crate::http::message::{Body, MessageBuilder};
+use crate::http::request::{Method, Request};
+use crate::http::response::{Response, Status};
+use crate::http::uri::Uri;
+use crate::server::config::health_check::HealthCheck;
+use crate::server::file_server::ServerState;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+
+pub struct HealthChecker<'a> {
+    request: &'a Request,
+    health_check: &'a HealthCheck,
+    state: &'a ServerState,
+}
+
+impl<'a> HealthChecker<'a> {
+    pub fn new(request: &'a Request, health_check: &'a HealthCheck, state: &'a ServerState) -> Self {
+        HealthChecker { request, health_check, state }
+    }
+
+    pub fn check(&self) -> MiddlewareResult<()> {
+        let path = match &self.request.uri {
+            Uri::OriginForm { path } => format!("/{}", path.path_as_string()),
+            _ => return Ok(()),
+        };
+        if path != self.health_check.path.trim_end_matches('/') {
+            return Ok(());
+        }
+
+        if self.request.method != Method::Get && self.request.method != Method::Head {
+            return Err(MiddlewareOutput::Status(Status::MethodNotAllowed, false));
+        }
+
+        let response = if self.health_check.details {
+            let body = format!(
+                "{{\"status\":\"ok\",\"server\":\"{}\",\"uptime\":{}}}",
+                consts::SERVER_NAME_VERSION, self.state.started.elapsed().as_secs(),
+            );
+            MessageBuilder::<Response>::new()
+                .with_header(consts::H_CACHE_CONTROL, consts::H_CACHE_NO_STORE)
+                .with_body(Body::Bytes(body.into_bytes()), consts::H_MEDIA_JSON)
+                .build()
+        } else {
+            MessageBuilder::<Response>::new()
+                .with_header(consts::H_CACHE_CONTROL, consts::H_CACHE_NO_STORE)
+                .with_body(Body::Bytes(b"ok".to_vec()), consts::H_MEDIA_TEXT)
+                .build()
+        };
+
+        middleware::log_request(self.request, response.status, &self.request.uri);
+        Err(MiddlewareOutput::Response(Box::new(response), false))
+    }
+}
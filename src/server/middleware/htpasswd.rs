@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use async_std::fs;
+use md5::{Digest, Md5};
+use pwhash::bcrypt;
+
+use crate::log;
+use crate::server::config::auth_info::Credentials;
+use crate::util;
+
+const APR1_MAGIC: &str = "$apr1$";
+const APR1_SALT_LENGTH: usize = 8;
+const APR1_ROUNDS: usize = 1_000;
+const APR1_TRANSPOSE: &[usize] = &[12, 6, 0, 13, 7, 1, 14, 8, 2, 15, 9, 3, 5, 10, 4, 11];
+const BCRYPT_PREFIXES: &[&str] = &["$2a$", "$2b$", "$2y$"];
+const CRYPT_BASE64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+type FileVersion = (Option<SystemTime>, u64);
+type CachedFile = (FileVersion, Arc<Vec<Credentials>>);
+
+pub struct HtpasswdFiles {
+    files: Mutex<HashMap<String, CachedFile>>,
+}
+
+impl HtpasswdFiles {
+    pub fn new() -> Self {
+        HtpasswdFiles { files: Mutex::new(HashMap::new()) }
+    }
+
+    pub async fn credentials(&self, path: &str) -> Option<Arc<Vec<Credentials>>> {
+        let version = match fs::metadata(path).await {
+            Ok(metadata) => (metadata.modified().ok(), metadata.len()),
+            Err(e) => {
+                log::warn(format!("Could not read htpasswd file `{}`: {}!", path, e));
+                return None;
+            }
+        };
+        if let Some((cached_version, credentials)) = self.files.lock().unwrap().get(path) {
+            if *cached_version == version {
+                return Some(Arc::clone(credentials));
+            }
+        }
+
+        let credentials = match fs::read_to_string(path).await {
+            Ok(contents) => Arc::new(parse(path, &contents)),
+            Err(e) => {
+                log::warn(format!("Could not read htpasswd file `{}`: {}!", path, e));
+                return None;
+            }
+        };
+        self.files.lock().unwrap().insert(path.to_string(), (version, Arc::clone(&credentials)));
+        Some(credentials)
+    }
+}
+
+impl Default for HtpasswdFiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn verify_hash(password: &str, hash: &str) -> bool {
+    if hash.starts_with(APR1_MAGIC) {
+        verify_apr1(password, hash)
+    } else {
+        bcrypt::verify(password, hash)
+    }
+}
+
+fn parse(path: &str, contents: &str) -> Vec<Credentials> {
+    let mut credentials = vec![];
+    for line in contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let (user, hash) = match line.find(':') {
+            Some(index) => (&line[..index], &line[index + 1..]),
+            _ => {
+                log::warn(format!("Ignoring malformed line in htpasswd file `{}`!", path));
+                continue;
+            }
+        };
+
+        if hash.starts_with(APR1_MAGIC) || BCRYPT_PREFIXES.iter().any(|prefix| hash.starts_with(prefix)) {
+            credentials.push(Credentials { user: user.to_string(), password_hash: hash.to_string() });
+        } else {
+            log::warn(format!("Ignoring user `{}` with an unsupported hash in htpasswd file `{}`!", user, path));
+        }
+    }
+    credentials
+}
+
+fn verify_apr1(password: &str, hash: &str) -> bool {
+    let salt = hash[APR1_MAGIC.len()..].split('$').next().unwrap_or("");
+    let salt = &salt[..salt.len().min(APR1_SALT_LENGTH)];
+    util::constant_time_eq(apr1_hash(password.as_bytes(), salt.as_bytes()).as_bytes(), hash.as_bytes())
+}
+
+fn apr1_hash(password: &[u8], salt: &[u8]) -> String {
+    let alternate = Md5::new().chain(password).chain(salt).chain(password).result();
+
+    let mut digest = Md5::new().chain(password).chain(APR1_MAGIC).chain(salt);
+    digest.input(alternate.iter().cycle().take(password.len()).cloned().collect::<Vec<_>>());
+    let mut length = password.len();
+    while length > 0 {
+        digest.input(if length & 1 == 1 { &[0][..] } else { &password[..1] });
+        length >>= 1;
+    }
+
+    let mut result = digest.result();
+    for round in 0..APR1_ROUNDS {
+        let mut digest = Md5::new();
+        if round % 2 == 1 {
+            digest.input(password);
+        } else {
+            digest.input(result);
+        }
+        if round % 3 != 0 {
+            digest.input(salt);
+        }
+        if round % 7 != 0 {
+            digest.input(password);
+        }
+        if round % 2 == 1 {
+            digest.input(result);
+        } else {
+            digest.input(password);
+        }
+        result = digest.result();
+    }
+
+    let bytes = APR1_TRANSPOSE.iter().map(|index| result[*index]).collect::<Vec<_>>();
+    let mut encoded = String::new();
+    for group in bytes.chunks(3) {
+        let value = group.iter().rev().fold(0u32, |value, byte| value << 8 | *byte as u32);
+        for index in 0..(group.len() + 1) {
+            encoded.push(CRYPT_BASE64[(value >> (6 * index) & 0x3f) as usize] as char);
+        }
+    }
+    format!("{}{}${}", APR1_MAGIC, String::from_utf8_lossy(salt), encoded)
+}
@@ -0,0 +1,90 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use linked_hash_map::LinkedHashMap;
+
+use crate::server::middleware::cond_checker::CondInfo;
+use crate::server::middleware::dir_lister::ListingFormat;
+
+pub type ListingKey = (String, String, String, ListingFormat);
+
+#[derive(Clone)]
+pub struct CachedListing {
+    pub dir_modified: SystemTime,
+    pub has_marker: bool,
+    pub info: CondInfo,
+    pub body: Vec<u8>,
+    created: Instant,
+}
+
+impl CachedListing {
+    pub fn new(dir_modified: SystemTime, has_marker: bool, info: CondInfo, body: Vec<u8>) -> Self {
+        CachedListing { dir_modified, has_marker, info, body, created: Instant::now() }
+    }
+}
+
+struct Listings {
+    entries: LinkedHashMap<ListingKey, CachedListing>,
+    size: usize,
+}
+
+pub struct CachedListings {
+    listings: Mutex<Listings>,
+}
+
+impl CachedListings {
+    pub fn new() -> Self {
+        CachedListings { listings: Mutex::new(Listings { entries: LinkedHashMap::new(), size: 0 }) }
+    }
+
+    pub fn get(&self, key: &ListingKey, dir_modified: SystemTime, ttl: Duration) -> Option<CachedListing> {
+        let mut listings = self.listings.lock().unwrap();
+        let fresh = {
+            let listing = listings.entries.get_refresh(key)?;
+            listing.dir_modified == dir_modified && listing.created.elapsed() < ttl
+        };
+        if fresh {
+            listings.entries.get(key).cloned()
+        } else {
+            listings.remove(key);
+            None
+        }
+    }
+
+    pub fn insert(&self, key: ListingKey, listing: CachedListing, max_size: usize) {
+        if listing.body.len() > max_size {
+            return;
+        }
+
+        let mut listings = self.listings.lock().unwrap();
+        listings.remove(&key);
+        listings.size += listing.body.len();
+        listings.entries.insert(key, listing);
+        while listings.size > max_size {
+            match listings.entries.pop_front() {
+                Some((_, evicted)) => listings.size -= evicted.body.len(),
+                _ => break,
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut listings = self.listings.lock().unwrap();
+        listings.entries.clear();
+        listings.size = 0;
+    }
+}
+
+impl Default for CachedListings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Listings {
+    fn remove(&mut self, key: &ListingKey) {
+        if let Some(listing) = self.entries.remove(key) {
+            self.size -= listing.body.len();
+        }
+    }
+}
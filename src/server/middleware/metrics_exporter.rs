@@ -0,0 +1,50 @@
+use crate::consts;
+use crate::http::message::{Body, MessageBuilder};
+use crate::http::request::{Method, Request};
+use crate::http::response::{Response, Status};
+use crate::http::uri::Uri;
+use crate::server::config::metrics_endpoint::MetricsEndpoint;
+use crate::server::file_server::{ConnInfo, ServerState};
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+
+const MEDIA_PROMETHEUS: &str = "text/plain; version=0.0.4";
+
+pub struct MetricsExporter<'a> {
+    request: &'a Request,
+    endpoint: &'a MetricsEndpoint,
+    state: &'a ServerState,
+    conn_info: &'a ConnInfo,
+}
+
+impl<'a> MetricsExporter<'a> {
+    pub fn new(
+        request: &'a Request,
+        endpoint: &'a MetricsEndpoint,
+        state: &'a ServerState,
+        conn_info: &'a ConnInfo,
+    ) -> Self {
+        MetricsExporter { request, endpoint, state, conn_info }
+    }
+
+    pub fn check(&self) -> MiddlewareResult<()> {
+        let path = match &self.request.uri {
+            Uri::OriginForm { path } => format!("/{}", path.path_as_string()),
+            _ => return Ok(()),
+        };
+        if path != self.endpoint.path.trim_end_matches('/') {
+            return Ok(());
+        } else if !self.endpoint.is_allowed(self.conn_info.remote_addr.ip()) {
+            return Err(MiddlewareOutput::Error(Status::Forbidden, false));
+        } else if self.request.method != Method::Get && self.request.method != Method::Head {
+            return Err(MiddlewareOutput::Status(Status::MethodNotAllowed, false));
+        }
+
+        let response = MessageBuilder::<Response>::new()
+            .with_header(consts::H_CACHE_CONTROL, consts::H_CACHE_NO_STORE)
+            .with_body(Body::Bytes(self.state.metrics.render().into_bytes()), MEDIA_PROMETHEUS)
+            .build();
+
+        middleware::log_request(self.request, response.status, &self.request.uri);
+        Err(MiddlewareOutput::Response(Box::new(response), false))
+    }
+}
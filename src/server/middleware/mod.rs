@@ -1,6 +1,16 @@
 use std::error;
+use std::fmt::Display;
+use std::io;
+use std::time::Duration;
 
+use async_std::net::TcpStream;
+use futures::future::BoxFuture;
+
+use crate::http::request::Request;
 use crate::http::response::{Response, Status};
+use crate::log;
+use crate::server::config::Config;
+use crate::server::file_server::{ConnInfo, ServerState};
 
 pub mod output_processor;
 pub mod request_verifier;
@@ -8,19 +18,104 @@ pub mod response_gen;
 pub mod range_parser;
 pub mod cond_checker;
 pub mod dir_lister;
+pub mod listing_cache;
+pub mod file_cache;
 pub mod cgi_runner;
 pub mod basic_auth;
+pub mod digest_auth;
+pub mod bearer_auth;
+pub mod content_negotiator;
+pub mod auth_limiter;
+pub mod health_check;
+pub mod metrics_exporter;
+pub mod websocket;
+pub mod admin_api;
+pub mod webdav;
+pub mod upload_handler;
+pub mod trace_echo;
+pub mod connect_tunnel;
+pub mod auth_backend;
+pub mod htpasswd;
+pub mod access_control;
+pub mod acme_challenge;
 
 pub enum MiddlewareOutput {
     Error(Status, bool),
     Status(Status, bool),
-    Response(Response, bool),
+    Response(Box<Response>, bool),
     Bytes(Vec<u8>, bool),
+    Upgrade(Box<Response>, TcpStream),
     Terminate,
 }
 
+impl MiddlewareOutput {
+    pub fn closing(self) -> Self {
+        match self {
+            MiddlewareOutput::Error(status, _) => MiddlewareOutput::Error(status, true),
+            MiddlewareOutput::Status(status, _) => MiddlewareOutput::Status(status, true),
+            MiddlewareOutput::Response(response, _) => MiddlewareOutput::Response(response, true),
+            MiddlewareOutput::Bytes(bytes, _) => MiddlewareOutput::Bytes(bytes, true),
+            output => output,
+        }
+    }
+}
+
+impl MiddlewareOutput {
+    pub fn status(&self) -> Option<Status> {
+        match self {
+            MiddlewareOutput::Error(status, _) | MiddlewareOutput::Status(status, _) => Some(*status),
+            MiddlewareOutput::Response(response, _) | MiddlewareOutput::Upgrade(response, _) => Some(response.status),
+            MiddlewareOutput::Bytes(_, _) => Some(Status::Ok),
+            MiddlewareOutput::Terminate => None,
+        }
+    }
+}
+
 pub type MiddlewareResult<T> = Result<T, MiddlewareOutput>;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MiddlewareStage {
+    Request,
+    Authenticated,
+    Files,
+}
+
+pub struct MiddlewareContext<'a> {
+    pub request: &'a mut Request,
+    pub config: &'a Config,
+    pub state: &'a ServerState,
+    pub conn_info: &'a ConnInfo,
+    pub raw_target: &'a str,
+    pub target: &'a str,
+}
+
+pub trait Middleware: Send + Sync {
+    fn handle<'a>(&'a self, context: &'a mut MiddlewareContext<'_>) -> BoxFuture<'a, MiddlewareResult<()>>;
+}
+
+pub fn log_request(request: &Request, status: impl Display, target: impl Display) {
+    let id = request.id.as_ref().map(|id| format!(" [{}]", id)).unwrap_or_default();
+    let latency = request.timing.as_ref().map(|t| format!(" {:.3}ms", millis(t.elapsed()))).unwrap_or_default();
+    log::info(format!("({}) {} {}{}{}", status, request.method, target, id, latency));
+}
+
+pub fn write_error_output(request: &Request, err: io::Error, not_found_status: Status) -> MiddlewareOutput {
+    let status = match err.kind() {
+        io::ErrorKind::NotFound => not_found_status,
+        io::ErrorKind::PermissionDenied => Status::Forbidden,
+        io::ErrorKind::AlreadyExists | io::ErrorKind::IsADirectory => Status::MethodNotAllowed,
+        _ => {
+            log::warn(format!("Could not {} `{}`: {}!", request.method, request.uri, err));
+            Status::InternalServerError
+        }
+    };
+    MiddlewareOutput::Error(status, false)
+}
+
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000.0
+}
+
 impl<T: error::Error> From<T> for MiddlewareOutput {
     fn from(_: T) -> Self {
         MiddlewareOutput::Terminate
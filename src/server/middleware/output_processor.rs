@@ -1,90 +1,217 @@
+use async_std::future;
 use async_std::io::{self, Write};
 use async_std::io::prelude::WriteExt;
 
 use crate::consts;
+use crate::http::headers::Headers;
 use crate::http::message::{Body, MessageBuilder};
-use crate::http::request::{Method, Request};
+use crate::http::request::{HttpVersion, Method, Request};
 use crate::http::response::{Response, Status};
 use crate::log;
-use crate::server::middleware::MiddlewareOutput;
+use crate::server::config::{Config, Http09Mode};
+use crate::server::middleware::{self, MiddlewareOutput};
+use crate::server::throttle::ThrottledWriter;
 use crate::server::template::{SubstitutionMap, TemplateSubstitution};
 use crate::server::template::templates::Templates;
 
 pub struct OutputProcessor<'a, W: Write + Unpin> {
     writer: &'a mut W,
+    config: &'a Config,
     templates: &'a Templates,
     request: Option<&'a Request>,
 }
 
 impl<'a, W: Write + Unpin> OutputProcessor<'a, W> {
-    pub fn new(writer: &'a mut W, templates: &'a Templates, request: Option<&'a Request>) -> Self {
-        OutputProcessor { writer, templates, request }
+    pub fn new(writer: &'a mut W, config: &'a Config, templates: &'a Templates, request: Option<&'a Request>) -> Self {
+        OutputProcessor { writer, config, templates, request }
     }
 
     pub async fn process(&mut self, output: MiddlewareOutput) -> bool {
+        match self.config.timeouts.write_timeout() {
+            Some(timeout) => future::timeout(timeout, self.process_output(output)).await.unwrap_or(true),
+            _ => self.process_output(output).await,
+        }
+    }
+
+    async fn process_output(&mut self, output: MiddlewareOutput) -> bool {
+        let keep_alive = self.request.map(|request| request.keep_alive()).unwrap_or(true);
+        let version = self.request.map(|request| request.http_version);
+        if version == Some(HttpVersion::Http09) && self.config.http_09 == Http09Mode::Simple {
+            return self.respond_simple(output).await;
+        }
+
         match output {
-            MiddlewareOutput::Error(status, close) => self.respond_error(status, close).await,
-            MiddlewareOutput::Status(status, close) => self.respond_status(status, close).await,
-            MiddlewareOutput::Response(response, close) => self.respond_response(response, close).await,
-            MiddlewareOutput::Bytes(bytes, close) => self.respond_bytes(bytes, close).await,
+            MiddlewareOutput::Error(status, close) => self.respond_error(status, close || !keep_alive).await,
+            MiddlewareOutput::Status(status, close) => self.respond_status(status, close || !keep_alive).await,
+            MiddlewareOutput::Response(response, close) => {
+                self.respond_response(*response, close || !keep_alive).await
+            }
+            MiddlewareOutput::Bytes(bytes, close) => self.respond_bytes(bytes, close || !keep_alive).await,
             _ => true,
         }
     }
 
     async fn respond_error(&mut self, status: Status, close: bool) -> bool {
         self.log_request(Some(status));
-
-        let mut sub = SubstitutionMap::new();
-        sub.insert("server".to_string(), TemplateSubstitution::Single(consts::SERVER_NAME_VERSION.to_string()));
-        sub.insert("status".to_string(), TemplateSubstitution::Single(status.to_string()));
-        let body = self.templates.error.substitute(&sub).unwrap().into_bytes();
+        let body = self.error_body(status);
 
         let mut response = MessageBuilder::<Response>::new();
-        if close {
-            response.set_header(consts::H_CONNECTION, consts::H_CONN_CLOSE)
+        if let Some(connection) = self.connection_option(close) {
+            response.set_header(consts::H_CONNECTION, connection);
+        }
+        for (name, value) in self.context_headers() {
+            response.set_header(name, &value);
         }
-        response
+        let mut response = response
             .with_status(status)
             .with_header_multi(consts::H_ACCEPT, vec![&Method::Get.to_string(), &Method::Head.to_string()])
             .with_body(Body::Bytes(body), consts::H_MEDIA_HTML)
-            .build()
-            .send(self.writer)
-            .await
-            .is_err() || close
+            .with_header_casing(&self.config.header_casing)
+            .build();
+        self.add_route_headers(&mut response.headers);
+        let timeout = self.config.timeouts.single_write_timeout();
+        response.stream(self.writer, self.config.stream_chunk_size, timeout).await.is_err() || close
     }
 
     async fn respond_status(&mut self, status: Status, close: bool) -> bool {
         self.log_request(Some(status));
 
         let mut response = MessageBuilder::<Response>::new();
-        if close {
-            response.set_header(consts::H_CONNECTION, consts::H_CONN_CLOSE);
+        if let Some(connection) = self.connection_option(close) {
+            response.set_header(consts::H_CONNECTION, connection);
+        }
+        for (name, value) in self.context_headers() {
+            response.set_header(name, &value);
+        }
+        let mut response = response.with_status(status).with_header_casing(&self.config.header_casing).build();
+        self.add_route_headers(&mut response.headers);
+        let timeout = self.config.timeouts.single_write_timeout();
+        response.stream(self.writer, self.config.stream_chunk_size, timeout).await.is_err() || close
+    }
+
+    async fn respond_response(&mut self, mut response: Response, mut close: bool) -> bool {
+        if self.request.map(|request| request.method == Method::Head).unwrap_or(false) {
+            response.body = None;
+        }
+        if response.chunked && self.request.map(|r| r.http_version != HttpVersion::Http11).unwrap_or(false) {
+            response.chunked = false;
+            response.headers.remove(consts::H_TRANSFER_ENCODING);
+            response.headers.remove(consts::H_TRAILER);
+            close = true;
+        }
+        let mut connection = response.headers.get(consts::H_CONNECTION).cloned().unwrap_or_default();
+        let has_option = |options: &[String], option: &str| options.iter().any(|o| o.eq_ignore_ascii_case(option));
+        if response.headers.contains(consts::H_UPGRADE) && !has_option(&connection, consts::H_CONN_UPGRADE) {
+            connection.push(consts::H_CONN_UPGRADE.to_string());
+        }
+        match self.connection_option(close) {
+            Some(option) if response.status != Status::SwitchingProtocols && !has_option(&connection, option) => {
+                connection.push(option.to_string());
+            }
+            _ => {}
+        }
+        if !connection.is_empty() {
+            response.headers.set(consts::H_CONNECTION, connection.iter().map(|o| o.as_str()).collect());
+        }
+        for (name, value) in self.context_headers() {
+            response.headers.set_one(name, &value);
         }
-        response.with_status(status).build().send(self.writer).await.is_err() || close
+        self.add_route_headers(&mut response.headers);
+        response.headers.set_casing(&self.config.header_casing);
+
+        let (chunk_size, timeout) = (self.config.stream_chunk_size, self.config.timeouts.single_write_timeout());
+        let rate = self.request.and_then(|request| self.config.throttle.rate_for(&request.uri.to_string()));
+        match rate {
+            Some(rate) => {
+                let mut writer = ThrottledWriter::new(&mut *self.writer, rate);
+                response.stream(&mut writer, chunk_size, timeout).await
+            }
+            _ => response.stream(self.writer, chunk_size, timeout).await,
+        }.is_err() || close
     }
 
-    async fn respond_response(&mut self, response: Response, close: bool) -> bool {
-        response.send(self.writer).await.is_err() || close
+    async fn respond_simple(&mut self, output: MiddlewareOutput) -> bool {
+        let response = match output {
+            MiddlewareOutput::Error(status, _) => {
+                self.log_request(Some(status));
+                MessageBuilder::<Response>::new()
+                    .with_body(Body::Bytes(self.error_body(status)), consts::H_MEDIA_HTML)
+                    .build()
+            }
+            MiddlewareOutput::Status(status, _) => {
+                self.log_request(Some(status));
+                return true;
+            }
+            MiddlewareOutput::Response(response, _) => *response,
+            _ => return true,
+        };
+        let timeout = self.config.timeouts.single_write_timeout();
+        let _ = response.stream_body(self.writer, self.config.stream_chunk_size, timeout).await;
+        true
+    }
+
+    fn error_body(&self, status: Status) -> Vec<u8> {
+        let mut sub = SubstitutionMap::new();
+        sub.insert("server".to_string(), TemplateSubstitution::Single(consts::SERVER_NAME_VERSION.to_string()));
+        sub.insert("status".to_string(), TemplateSubstitution::Single(status.to_string()));
+        self.templates.error.substitute(&sub).unwrap().into_bytes()
     }
 
     async fn respond_bytes(&mut self, bytes: Vec<u8>, close: bool) -> bool {
         self.log_request(None);
 
-        io::timeout(consts::MAX_WRITE_TIMEOUT, async {
+        io::timeout(self.config.timeouts.single_write_timeout(), async {
             self.writer.write_all(&bytes).await?;
             self.writer.flush().await
         }).await.is_err() || close
     }
 
+    fn connection_option(&self, close: bool) -> Option<&'static str> {
+        match self.request {
+            _ if close => Some(consts::H_CONN_CLOSE),
+            Some(request) if request.http_version == HttpVersion::Http10 => Some(consts::H_CONN_KEEP_ALIVE),
+            _ => None,
+        }
+    }
+
+    fn context_headers(&self) -> Vec<(&'a str, String)> {
+        let mut headers = vec![];
+        if let (Some(request_id), Some(Some(id))) = (&self.config.request_id, self.request.map(|r| &r.id)) {
+            headers.push((request_id.header.as_str(), id.clone()));
+        }
+        if let Some(timing) = self.request.and_then(|request| request.timing.as_ref()) {
+            let metrics = timing.phases
+                .iter()
+                .map(|(name, duration)| format!("{};dur={:.3}", name, middleware::millis(*duration)))
+                .collect::<Vec<_>>();
+            headers.push((consts::H_SERVER_TIMING, metrics.join(", ")));
+        }
+        if let (Some(hsts), Some(true)) = (&self.config.hsts, self.request.map(|request| request.secure)) {
+            headers.push((consts::H_STRICT_TRANSPORT_SECURITY, hsts.header_value()));
+        }
+        headers
+    }
+
+    fn add_route_headers(&self, headers: &mut Headers) {
+        let target = self.request.map(|request| request.uri.to_string()).unwrap_or_default();
+        for (name, value) in self.config.response_headers_for(&target) {
+            if name.eq_ignore_ascii_case(consts::H_VARY) {
+                value.split(',').for_each(|field| headers.add_vary(field.trim()));
+            } else if !headers.contains(&name) {
+                headers.set_one(&name, &value);
+            }
+        }
+    }
+
     fn log_request(&self, status: Option<Status>) {
         let status = match status {
-            Some(status) if status == Status::RequestTimeout => return,
+            Some(Status::RequestTimeout) => return,
             Some(status) => status.to_string(),
             _ => " - ".to_string(),
         };
 
         match self.request {
-            Some(request) => log::info(format!("({}) {} {}", status, request.method, request.uri)),
+            Some(request) => middleware::log_request(request, status, &request.uri),
             _ => log::info(format!("({})", status)),
         }
     }
@@ -1,4 +1,5 @@
-use async_std::io::prelude::ReadExt;
+use async_std::io::prelude::{ReadExt, SeekExt};
+use async_std::io::SeekFrom;
 
 use crate::consts;
 use crate::http::headers::Headers;
@@ -49,7 +50,7 @@ impl<'a> RangeParser<'a> {
                         let time = util::get_time_utc();
                         let sep = format!("{:x}", time.timestamp_millis() + time.timestamp_nanos());
                         let content_type = format!("{}; boundary={}", consts::H_MEDIA_MULTIPART_RANGE, &sep);
-                        Ok(RangeBody::MultipartRange(self.multipart_range_body(ranges, sep).await, content_type))
+                        Ok(RangeBody::MultipartRange(self.multipart_range_body(ranges, sep).await?, content_type))
                     }
                 }
             }
@@ -74,16 +75,7 @@ impl<'a> RangeParser<'a> {
         if range.high <= self.body_len { Some(range) } else { None }
     }
 
-    async fn multipart_range_body(&mut self, ranges: Vec<Range>, sep: String) -> Vec<u8> {
-        let mut body = vec![];
-        match &mut self.body {
-            Body::Bytes(bytes) => body = bytes.to_vec(),
-            Body::Stream(reader, len) => {
-                body.reserve(*len);
-                reader.read_exact(&mut body).await.map(|_| ()).unwrap_or(());
-            }
-        }
-
+    async fn multipart_range_body(&mut self, ranges: Vec<Range>, sep: String) -> MiddlewareResult<Vec<u8>> {
         let mut new_body = vec![];
         for range in ranges {
             new_body.extend_from_slice(format!("--{}\r\n", sep).as_bytes());
@@ -92,11 +84,27 @@ impl<'a> RangeParser<'a> {
                 consts::H_CONTENT_TYPE, self.media_type,
                 consts::H_CONTENT_RANGE, self.get_content_range(&range)
             ).as_bytes());
-            new_body.extend_from_slice(&body[range.low..range.high]);
+            new_body.extend_from_slice(&self.read_range(&range).await?);
             new_body.extend_from_slice(b"\r\n");
         }
         new_body.extend_from_slice(format!("--{}--", sep).as_bytes());
-        new_body
+        Ok(new_body)
+    }
+
+    async fn read_range(&mut self, range: &Range) -> MiddlewareResult<Vec<u8>> {
+        match &mut self.body {
+            Body::Bytes(bytes) => Ok(bytes[range.low..range.high].to_vec()),
+            Body::File(file, file_range) => {
+                let mut slice = vec![0; range.high - range.low];
+                file.seek(SeekFrom::Start((file_range.low + range.low) as u64)).await?;
+                file.read_exact(&mut slice).await?;
+                Ok(slice)
+            }
+            Body::Mapped(file, file_range) => {
+                Ok(file.read(&Range { low: file_range.low + range.low, high: file_range.low + range.high })?)
+            }
+            Body::Stream(_) => Err(MiddlewareOutput::Status(Status::UnsatisfiableRange, false)),
+        }
     }
 
     fn get_content_range(&self, range: &Range) -> String {
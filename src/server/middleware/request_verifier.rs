@@ -1,31 +1,50 @@
-use async_std::io::prelude::Read;
-use async_std::io::Write;
+use async_std::io::{BufRead, Write};
 
-use crate::http::parser::MessageParseError;
+use crate::http::message::MessageBuilder;
+use crate::http::parser;
+use crate::http::parser::{ParseLimits, MessageParseError, MessageParseResult};
 use crate::http::request::Request;
-use crate::http::response::Status;
+use crate::http::response::{Response, Status};
 use crate::server::middleware::{MiddlewareOutput, MiddlewareResult};
 
-pub struct RequestVerifier<'a, R: Read + Unpin, W: Write + Unpin> {
+pub struct RequestVerifier<'a, R: BufRead + Unpin, W: Write + Unpin> {
     reader: &'a mut R,
     writer: &'a mut W,
 }
 
-impl<'a, R: Read + Unpin, W: Write + Unpin> RequestVerifier<'a, R, W> {
+impl<'a, R: BufRead + Unpin, W: Write + Unpin> RequestVerifier<'a, R, W> {
     pub fn new(reader: &'a mut R, writer: &'a mut W) -> Self {
         RequestVerifier { reader, writer }
     }
 
-    pub async fn verify_request(&mut self) -> MiddlewareResult<Request> {
-        match Request::new(self.reader, self.writer).await {
-            Ok(req) => Ok(req),
+    pub async fn verify_request(&mut self, limits: ParseLimits) -> MiddlewareResult<Request> {
+        Self::verify(Request::new(self.reader, limits).await)
+    }
+
+    pub async fn verify_body(
+        &mut self,
+        request: &mut Request,
+        max_length: usize,
+        limits: ParseLimits,
+    ) -> MiddlewareResult<()> {
+        if request.expect_continue {
+            Self::verify(parser::check_body_length(&request.headers, max_length))?;
+            MessageBuilder::<Response>::new().with_status(Status::Continue).build().send(self.writer).await?;
+        }
+        Self::verify(request.read_body(self.reader, max_length, limits).await)
+    }
+
+    fn verify<T>(result: MessageParseResult<T>) -> MiddlewareResult<T> {
+        match result {
+            Ok(result) => Ok(result),
+            Err(MessageParseError::BodyTooLarge) => Err(MiddlewareOutput::Error(Status::PayloadTooLarge, true)),
             Err(e) => Err(MiddlewareOutput::Status(match e {
                 MessageParseError::UriTooLong => Status::UriTooLong,
+                MessageParseError::ForbiddenPath => Status::Forbidden,
                 MessageParseError::UnsupportedVersion => Status::HttpVersionUnsupported,
-                MessageParseError::HeaderTooLong => Status::HeaderFieldsTooLarge,
+                MessageParseError::HeaderTooLong | MessageParseError::TooManyHeaders => Status::HeaderFieldsTooLarge,
                 MessageParseError::InvalidExpectHeader => Status::ExpectationFailed,
                 MessageParseError::UnsupportedTransferEncoding => Status::NotImplemented,
-                MessageParseError::BodyTooLarge => Status::PayloadTooLarge,
                 MessageParseError::TimedOut => Status::RequestTimeout,
                 MessageParseError::EndOfStream => return Err(MiddlewareOutput::Terminate),
                 _ => Status::BadRequest,
@@ -33,3 +52,54 @@ impl<'a, R: Read + Unpin, W: Write + Unpin> RequestVerifier<'a, R, W> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use async_std::io::Cursor;
+
+    use super::*;
+
+    async fn verify(raw: &str) -> MiddlewareResult<Request> {
+        let mut reader = Cursor::new(raw.as_bytes().to_vec());
+        let mut writer = vec![];
+        RequestVerifier::new(&mut reader, &mut writer).verify_request(ParseLimits::default()).await
+    }
+
+    async fn parse(raw: &str) -> MessageParseResult<Request> {
+        Request::new(&mut Cursor::new(raw.as_bytes().to_vec()), ParseLimits::default()).await
+    }
+
+    async fn assert_control_character(raw: &str) {
+        assert!(matches!(parse(raw).await, Err(MessageParseError::ControlCharacter)), "{:?} was accepted", raw);
+        assert!(matches!(verify(raw).await, Err(MiddlewareOutput::Status(Status::BadRequest, true))));
+    }
+
+    #[async_std::test]
+    async fn control_characters_in_path_are_bad_requests() {
+        assert_control_character("GET /a\x01b HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert_control_character("GET /a\x7fb HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert_control_character("GET /a%01b HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert_control_character("GET /a%00 HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert_control_character("GET /a%0d%0aSet-Cookie:x HTTP/1.1\r\nHost: a\r\n\r\n").await;
+    }
+
+    #[async_std::test]
+    async fn control_characters_in_query_are_bad_requests() {
+        assert_control_character("GET /a?b=\x01 HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert_control_character("GET /a?b=%07 HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert_control_character("GET /a?b%00=c HTTP/1.1\r\nHost: a\r\n\r\n").await;
+    }
+
+    #[async_std::test]
+    async fn control_characters_in_header_values_are_bad_requests() {
+        assert_control_character("GET / HTTP/1.1\r\nHost: a\r\nX-Test: a\x01b\r\n\r\n").await;
+        assert_control_character("GET / HTTP/1.1\r\nHost: a\r\nX-Test: a\x00b\r\n\r\n").await;
+        assert_control_character("GET / HTTP/1.1\r\nHost: a\r\nX-Test: a\x7f\r\n\r\n").await;
+    }
+
+    #[async_std::test]
+    async fn percent_encoding_in_header_values_is_not_decoded() {
+        let request = parse("GET / HTTP/1.1\r\nHost: a\r\nX-Test: a%00b\tc\r\n\r\n").await.ok().unwrap();
+        assert_eq!(request.headers.get("X-Test").unwrap(), &["a%00b\tc"]);
+    }
+}
@@ -1,95 +1,374 @@
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use async_std::fs::{File, Metadata};
-use async_std::io::{prelude::SeekExt, SeekFrom};
+use async_std::io::ReadExt;
 use async_std::path::Path;
 use chrono::{DateTime, Utc};
+use futures::future::{BoxFuture, FutureExt};
 
-use crate::{log, util};
-use crate::consts;
+use crate::{consts, log, util};
+use crate::http::headers::Headers;
 use crate::http::message::{Body, MessageBuilder};
 use crate::http::request::{Method, Request};
 use crate::http::response::{Response, Status};
 use crate::http::uri::Uri;
-use crate::server::config::Config;
+use crate::server::config::{AuthCacheControl, Config, FileSourceMode};
+use crate::server::config::cache_policy::CachePolicy;
 use crate::server::config::route_replacement::RouteReplacement;
 use crate::server::config::route_spec::RouteSpec;
-use crate::server::file_server::ConnInfo;
-use crate::server::middleware::{MiddlewareOutput, MiddlewareResult};
+use crate::server::file_server::{ConnInfo, ServerState};
+use crate::server::file_source::{self, FileInfo, FileSource};
+use crate::server::middleware::{self, MiddlewareContext, MiddlewareOutput, MiddlewareResult, MiddlewareStage};
+use crate::server::middleware::access_control::AccessController;
+use crate::server::middleware::acme_challenge::AcmeChallengeResponder;
+use crate::server::middleware::auth_limiter::AuthLimiter;
 use crate::server::middleware::basic_auth::BasicAuthChecker;
+use crate::server::middleware::bearer_auth::BearerAuthChecker;
 use crate::server::middleware::cgi_runner::CgiRunner;
+use crate::server::middleware::connect_tunnel::ConnectTunneler;
+use crate::server::middleware::content_negotiator::{self, ContentNegotiator};
 use crate::server::middleware::cond_checker::{CondInfo, ConditionalChecker};
-use crate::server::middleware::dir_lister::DirectoryLister;
+use crate::server::middleware::digest_auth::DigestAuthChecker;
+use crate::server::middleware::file_cache::CachedFile;
+use crate::server::middleware::dir_lister::{DirectoryLister, ListingFormat};
+use crate::server::middleware::health_check::HealthChecker;
+use crate::server::middleware::listing_cache::CachedListing;
+use crate::server::middleware::metrics_exporter::MetricsExporter;
 use crate::server::middleware::range_parser::{RangeBody, RangeParser};
+use crate::server::middleware::trace_echo::TraceEchoer;
+use crate::server::middleware::upload_handler::UploadHandler;
+use crate::server::middleware::webdav::WebDavHandler;
+use crate::server::middleware::websocket::WebSocketUpgrader;
 use crate::server::template::{SubstitutionMap, TemplateSubstitution};
 use crate::server::template::templates::Templates;
+use crate::util::Range;
 
 pub struct ResponseGenerator<'a> {
     config: &'a Config,
     templates: &'a Templates,
+    state: &'a ServerState,
 
     request: &'a mut Request,
     conn_info: &'a ConnInfo,
     raw_target: String,
     routed_target: String,
     target: String,
+    path_info: String,
+    trailing_slash: bool,
 
     response: MessageBuilder<Response>,
     body: Body,
     media_type: String,
+    is_static_file: bool,
+    local_redirects: usize,
+    auth_scheme: Option<&'static str>,
+    file_source: Box<dyn FileSource + 'a>,
 }
 
 impl<'a> ResponseGenerator<'a> {
-    pub fn new(config: &'a Config, templates: &'a Templates, request: &'a mut Request, conn: &'a ConnInfo) -> Self {
+    pub fn new(
+        config: &'a Config,
+        templates: &'a Templates,
+        state: &'a ServerState,
+        request: &'a mut Request,
+        conn: &'a ConnInfo,
+    ) -> Self {
+        let trailing_slash = match &request.uri {
+            Uri::OriginForm { path } | Uri::AbsoluteForm { path, .. } => path.trailing_slash,
+            _ => true,
+        };
         let (raw_target, routed_target, target) = rewrite_url(request, config);
 
         ResponseGenerator {
             config,
             templates,
+            state,
 
             request,
             conn_info: conn,
             raw_target,
             routed_target,
             target,
+            path_info: String::new(),
+            trailing_slash,
 
             response: MessageBuilder::<Response>::new(),
             body: Body::Bytes(vec![]),
             media_type: consts::H_MEDIA_BINARY.to_string(),
+            is_static_file: false,
+            local_redirects: 0,
+            auth_scheme: None,
+            file_source: file_source::for_config(config),
+        }
+    }
+
+    pub async fn check_expectation(
+        config: &Config,
+        templates: &Templates,
+        state: &ServerState,
+        request: &mut Request,
+        conn: &ConnInfo,
+    ) -> MiddlewareResult<()> {
+        let uri = request.uri.clone();
+        let result = ResponseGenerator::new(config, templates, state, request, conn).check_acceptable().await;
+        request.uri = uri;
+        result.map_err(MiddlewareOutput::closing)
+    }
+
+    async fn check_acceptable(mut self) -> MiddlewareResult<()> {
+        AccessController::new(self.request, self.config, self.conn_info).check()?;
+        self.check_auth(true).await?;
+        let uri = self.request.uri.to_string();
+        let is_upload = self.request.method == Method::Put || self.request.method == Method::Delete;
+        if self.config.webdav_enabled(&uri) || is_upload && self.config.writable_route(&uri).is_some() {
+            return Ok(());
+        }
+        self.resolve_target().await?;
+        if self.file_source.metadata(&self.target).await.is_none() {
+            Err(MiddlewareOutput::Error(Status::NotFound, true))
+        } else if self.request.method != Method::Get && self.request.method != Method::Head
+            && !is_cgi_script(Path::new(&self.target)) {
+            Err(MiddlewareOutput::Status(Status::MethodNotAllowed, true))
+        } else {
+            Ok(())
         }
     }
 
-    pub async fn get_response(mut self) -> MiddlewareResult<()> {
-        let required_auth = BasicAuthChecker::new(self.request, self.config).check()?;
+    pub async fn get_response(self) -> MiddlewareResult<()> {
+        let cache_control = match self.config.auth_cache_control {
+            Some(_) if !self.request.headers.contains(consts::H_AUTHORIZATION) => None,
+            Some(AuthCacheControl::Private) => Some(consts::H_CACHE_PRIVATE),
+            Some(AuthCacheControl::NoStore) => Some(consts::H_CACHE_NO_STORE),
+            _ => None,
+        };
+
+        match (self.generate_response().await, cache_control) {
+            (Err(MiddlewareOutput::Response(mut response, close)), Some(cache_control)) => {
+                response.headers.set_one(consts::H_CACHE_CONTROL, cache_control);
+                response.headers.add_vary(consts::H_AUTHORIZATION);
+                Err(MiddlewareOutput::Response(response, close))
+            }
+            (output, _) => output,
+        }
+    }
+
+    async fn generate_response(mut self) -> MiddlewareResult<()> {
+        AcmeChallengeResponder::new(self.request, self.config, self.state).check().await?;
+        self.run_middleware(MiddlewareStage::Request).await?;
+        AccessController::new(self.request, self.config, self.conn_info).check()?;
+        if let Some(health_check) = &self.config.health_check {
+            HealthChecker::new(self.request, health_check, self.state).check()?;
+        }
+        if let Some(endpoint) = &self.config.metrics {
+            MetricsExporter::new(self.request, endpoint, self.state, self.conn_info).check()?;
+        }
+
+        TraceEchoer::new(self.request, &self.config.trace, &self.raw_target).check()?;
+        ConnectTunneler::new(self.request, &self.config.connect_proxy).check().await?;
 
-        let file = match File::open(&self.target).await {
-            Ok(file) => file,
+        let required_auth = self.check_auth(false).await?;
+        self.auth_scheme = required_auth;
+        self.run_middleware(MiddlewareStage::Authenticated).await?;
+        WebSocketUpgrader::new(self.request, self.config).check().await?;
+        WebDavHandler::new(self.request, self.config, &self.raw_target, &self.target, &*self.file_source)
+            .check()
+            .await?;
+        UploadHandler::new(self.request, self.config, &self.target, &*self.file_source).check().await?;
+        self.resolve_target().await?;
+        self.run_middleware(MiddlewareStage::Files).await?;
+
+        let fs_start = Instant::now();
+        let mut metadata = match self.file_source.metadata(&self.target).await {
+            Some(metadata) => metadata,
             _ => return Err(MiddlewareOutput::Error(Status::NotFound, false)),
         };
+        if metadata.is_dir {
+            if !self.trailing_slash {
+                return Err(self.trailing_slash_redirect());
+            } else if let Some(index_metadata) = self.resolve_index_file().await {
+                metadata = index_metadata;
+            }
+        }
 
-        let metadata = file.metadata().await?;
-        let last_modified = Some(metadata.modified()?.into());
-        let etag = Some(Self::generate_etag(&last_modified.unwrap()));
-        let info = CondInfo::new(etag, last_modified);
-        self.set_body(&info, &metadata).await?;
+        let last_modified = metadata.modified.into();
+        let cached_etag = self.config.file_cache.and_then(|_| self.state.files.get(&self.target, &metadata));
+        let etag = cached_etag.map(|file| file.etag.clone()).unwrap_or_else(|| Self::generate_etag(&last_modified));
+        let mut info = CondInfo::new(Some(etag), Some(last_modified));
+        let body = self.set_body(&mut info, &metadata).await;
+        if !is_cgi_script(Path::new(&self.target)) {
+            self.record_timing(consts::TIMING_FS, fs_start);
+        }
+        match body {
+            Err(MiddlewareOutput::Status(Status::NotModified, close)) => {
+                let response = MessageBuilder::<Response>::new()
+                    .with_status(Status::NotModified)
+                    .with_header(consts::H_ETAG, info.etag.as_ref().unwrap())
+                    .with_header(consts::H_LAST_MODIFIED, &util::format_time_imf(&info.last_modified.unwrap()))
+                    .build();
+                middleware::log_request(self.request, response.status, &self.raw_target);
+                return Err(MiddlewareOutput::Response(Box::new(response), close));
+            }
+            result => result?,
+        }
 
-        let response = self
-            .response
+        if self.is_static_file {
+            self.set_cache_headers();
+            self.set_content_disposition();
+        }
+        let response = match self.body {
+            Body::File(file, range) => self.response.with_file_body(file, range, &self.media_type),
+            body => self.response.with_body(body, &self.media_type),
+        };
+        let response = response
             .with_header(consts::H_ETAG, &info.etag.unwrap())
-            .with_header(consts::H_LAST_MODIFIED, &util::format_time_imf(&info.last_modified.unwrap().into()))
-            .with_body(self.body, &self.media_type)
+            .with_header(consts::H_LAST_MODIFIED, &util::format_time_imf(&info.last_modified.unwrap()))
             .build();
 
         let routed = self.routed_target;
         let reroute = if self.raw_target != routed { format!(" -> {}", routed) } else { String::new() };
-        let auth = if required_auth { " (basic auth)" } else { "" };
-        log::info(format!("({}) {} {}{}{}", response.status, &self.request.method, &self.raw_target, reroute, auth));
+        let auth = required_auth.map(|scheme| format!(" ({} auth)", scheme)).unwrap_or_default();
+        middleware::log_request(self.request, response.status, format!("{}{}{}", self.raw_target, reroute, auth));
+
+        Err(MiddlewareOutput::Response(Box::new(response), false))
+    }
+
+    async fn run_middleware(&mut self, stage: MiddlewareStage) -> MiddlewareResult<()> {
+        for middleware in self.state.middleware_for(stage) {
+            let mut context = MiddlewareContext {
+                request: self.request,
+                config: self.config,
+                state: self.state,
+                conn_info: self.conn_info,
+                raw_target: &self.raw_target,
+                target: &self.target,
+            };
+            middleware.handle(&mut context).await?;
+        }
+        Ok(())
+    }
+
+    // With `preview` set, as when answering `Expect: 100-continue`, only a rejection is recorded, since the request
+    // ends there; an accepted request is checked and recorded again once its body has been read.
+    async fn check_auth(&self, preview: bool) -> MiddlewareResult<Option<&'static str>> {
+        let limiter = self.config.auth_rate_limit.map(|limits| {
+            AuthLimiter::new(self.request, limits, &self.state.auth_failures, self.conn_info.remote_addr.ip())
+        });
+        if let Some(limiter) = &limiter {
+            limiter.check()?;
+        }
+
+        let required_auth = self.check_credentials(preview).await;
+        match &limiter {
+            Some(limiter) if !preview || required_auth.is_err() => limiter.record(&required_auth),
+            _ => {}
+        }
+        required_auth
+    }
+
+    async fn check_credentials(&self, preview: bool) -> MiddlewareResult<Option<&'static str>> {
+        let digest = DigestAuthChecker::new(self.request, self.config, &self.raw_target, &self.state.digest_nonces);
+        let digest = if preview { digest.without_recording() } else { digest };
+        Ok(if BasicAuthChecker::new(self.request, self.config, self.state).check().await? {
+            Some(consts::H_AUTH_BASIC)
+        } else if digest.check()? {
+            Some(consts::H_AUTH_DIGEST)
+        } else if BearerAuthChecker::new(self.request, self.config).check()? {
+            Some(consts::H_AUTH_BEARER)
+        } else {
+            None
+        })
+    }
+
+    async fn resolve_target(&mut self) -> MiddlewareResult<()> {
+        if self.config.content_negotiation && self.file_source.metadata(&self.target).await.is_none() {
+            self.negotiate_target().await?;
+        }
+        if self.file_source.metadata(&self.target).await.is_none() {
+            self.resolve_path_info().await;
+        }
+        Ok(())
+    }
+
+    async fn negotiate_target(&mut self) -> MiddlewareResult<()> {
+        let negotiator = ContentNegotiator::new(&self.target, &self.request.headers, &*self.file_source);
+        if let Some(target) = negotiator.get_target().await? {
+            self.target = target;
+            self.response.add_vary(consts::H_ACCEPT);
+        }
+        Ok(())
+    }
+
+    async fn resolve_path_info(&mut self) {
+        let segments = match &self.request.uri {
+            Uri::OriginForm { path } | Uri::AbsoluteForm { path, .. } => &path.path,
+            _ => return,
+        };
+
+        for index in 1..segments.len() {
+            let target = format!("{}/{}", self.config.file_root, segments[..index].join("/"));
+            if is_cgi_script(Path::new(&target)) && self.file_source.is_file(&target).await {
+                let trailing_slash = if self.trailing_slash { "/" } else { "" };
+                self.path_info = format!("/{}{}", segments[index..].join("/"), trailing_slash);
+                self.target = target;
+                return;
+            }
+        }
+    }
+
+    async fn resolve_index_file(&mut self) -> Option<FileInfo> {
+        for name in &self.config.index_files {
+            let target = format!("{}/{}", self.target.trim_end_matches('/'), name);
+            match self.file_source.metadata(&target).await {
+                Some(metadata) if !metadata.is_dir => {
+                    self.target = target;
+                    return Some(metadata);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn trailing_slash_redirect(&self) -> MiddlewareOutput {
+        let (path, query) = self.raw_target.split_at(self.raw_target.find('?').unwrap_or(self.raw_target.len()));
+        let response = MessageBuilder::<Response>::new()
+            .with_status(Status::MovedPermanently)
+            .with_header(consts::H_LOCATION, &format!("{}/{}", path, query))
+            .build();
 
-        Err(MiddlewareOutput::Response(response, false))
+        middleware::log_request(self.request, response.status, &self.raw_target);
+        MiddlewareOutput::Response(Box::new(response), false)
     }
 
-    async fn set_body(&mut self, info: &CondInfo, metadata: &Metadata) -> MiddlewareResult<()> {
+    fn local_redirect(&mut self, location: String) -> BoxFuture<'_, MiddlewareOutput> {
+        async move {
+            let uri = match Uri::from(&Method::Get, &location) {
+                Ok(uri @ Uri::OriginForm { .. }) if self.local_redirects < consts::MAX_CGI_LOCAL_REDIRECTS => uri,
+                _ => {
+                    log::warn(format!("CGI script local redirect to `{}` could not be followed!", location));
+                    return MiddlewareOutput::Error(Status::InternalServerError, false);
+                }
+            };
+
+            self.request.method = Method::Get;
+            self.request.uri = uri;
+            self.request.body = None;
+            self.request.headers.remove(consts::H_CONTENT_LENGTH);
+            self.request.headers.remove(consts::H_CONTENT_TYPE);
+
+            let mut generator =
+                ResponseGenerator::new(self.config, self.templates, self.state, self.request, self.conn_info);
+            generator.local_redirects = self.local_redirects + 1;
+            match generator.generate_response().await {
+                Err(output) => output,
+                _ => MiddlewareOutput::Terminate,
+            }
+        }.boxed()
+    }
+
+    async fn set_body(&mut self, info: &mut CondInfo, metadata: &FileInfo) -> MiddlewareResult<()> {
         if self.request.method != Method::Get && self.request.method != Method::Head {
             return self
                 .set_file_body(true, info, metadata)
@@ -97,61 +376,256 @@ impl<'a> ResponseGenerator<'a> {
                 .and(Err(MiddlewareOutput::Status(Status::MethodNotAllowed, false)));
         }
 
-        if metadata.is_dir() {
-            self.media_type = consts::H_MEDIA_HTML.to_string();
-            self.body = Body::Bytes(DirectoryLister::new(&self.routed_target, &self.target, self.templates)
-                .get_listing_body()
-                .await?
-                .into_bytes());
+        if metadata.is_dir {
+            self.response.set_header(consts::H_ACCEPT_RANGES, consts::H_RANGE_UNIT_NONE);
+            let format = self.listing_format();
+            self.media_type = match format {
+                ListingFormat::Html => consts::H_MEDIA_HTML,
+                ListingFormat::Json => consts::H_MEDIA_JSON,
+            }.to_string();
+            self.set_content_language();
+            self.set_listing_body(format, info, metadata).await?;
         } else {
             self.set_file_body(false, info, metadata).await?;
         }
         Ok(())
     }
 
-    async fn set_file_body(&mut self, cgi: bool, info: &CondInfo, metadata: &Metadata) -> MiddlewareResult<()> {
-        let target = &self.target;
+    async fn set_listing_body(
+        &mut self,
+        format: ListingFormat,
+        info: &mut CondInfo,
+        metadata: &FileInfo,
+    ) -> MiddlewareResult<()> {
+        let listed_path = self.routed_target.split('?').next().unwrap_or_default();
+        let lister = DirectoryLister::new(listed_path, &self.target, self.templates, self.config, &*self.file_source);
+        let cache = self.config.listing_cache;
+        let key = (self.config.template_root.clone(), self.target.clone(), listed_path.to_string(), format);
+
+        let ttl = cache.map(|cache| Duration::from_secs(cache.ttl));
+        let cached = ttl.and_then(|ttl| self.state.listings.get(&key, metadata.modified, ttl));
+        let files = match cached {
+            Some(listing) if !lister.is_enabled(listing.has_marker) => {
+                return Err(MiddlewareOutput::Error(Status::Forbidden, false));
+            }
+            Some(listing) => {
+                *info = listing.info;
+                Self::check_listing_conditions(info, &self.request.headers)?;
+                self.body = Body::Bytes(listing.body);
+                return Ok(());
+            }
+            _ => lister.read_files().await?,
+        };
+
+        *info = DirectoryLister::get_cond_info(&files, metadata.modified, format);
+        Self::check_listing_conditions(info, &self.request.headers)?;
+        let has_marker = files.iter().any(|f| f.name == consts::DIR_LISTING_VIEWABLE);
+        let body = lister.get_listing_body(files, format).await?.into_bytes();
+        if let Some(cache) = cache {
+            let listing = CachedListing::new(metadata.modified, has_marker, info.clone(), body.clone());
+            self.state.listings.insert(key, listing, cache.max_size);
+        }
+        self.body = Body::Bytes(body);
+        Ok(())
+    }
+
+    fn check_listing_conditions(info: &CondInfo, headers: &Headers) -> MiddlewareResult<()> {
+        match ConditionalChecker::new(info, headers).check() {
+            Err(MiddlewareOutput::Status(Status::Ok, ..)) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn query_param(&self, name: &str) -> Option<&String> {
+        match &self.request.uri {
+            Uri::OriginForm { path } | Uri::AbsoluteForm { path, .. } => path.query.as_ref()?.get(name),
+            _ => None,
+        }
+    }
+
+    fn listing_format(&mut self) -> ListingFormat {
+        if self.query_param(consts::DIR_LISTING_FORMAT_PARAM).map(|format| format == "json") == Some(true) {
+            return ListingFormat::Json;
+        }
+
+        self.response.add_vary(consts::H_ACCEPT);
+        let ranges = match self.request.headers.get(consts::H_ACCEPT) {
+            Some(accept) => accept.iter().filter_map(|r| content_negotiator::parse_media_range(r)).collect::<Vec<_>>(),
+            _ => return ListingFormat::Html,
+        };
+        let json_quality = content_negotiator::get_quality(&ranges, consts::H_MEDIA_JSON);
+        if json_quality > content_negotiator::get_quality(&ranges, consts::H_MEDIA_HTML) {
+            ListingFormat::Json
+        } else {
+            ListingFormat::Html
+        }
+    }
+
+    fn record_timing(&mut self, phase: &'static str, since: Instant) {
+        if let Some(timing) = &mut self.request.timing {
+            timing.record(phase, since);
+        }
+    }
+
+    async fn set_file_body(&mut self, cgi: bool, info: &mut CondInfo, metadata: &FileInfo) -> MiddlewareResult<()> {
+        let target = &self.target.clone();
         let path = Path::new(target);
         let file_ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
         let target_no_ext = &target[..target.len() - file_ext.len() - 1];
 
         if target_no_ext.ends_with("_cgi") {
             let is_nph = target_no_ext.ends_with("_nph_cgi");
-            CgiRunner::new(&self.target, &mut self.request, &self.conn_info, &self.config, is_nph)
-                .get_response()
-                .await?;
+            let mut runner = CgiRunner::new(
+                &self.target, &self.path_info, self.request, self.conn_info, self.config, is_nph, self.auth_scheme,
+            );
+            let location = runner.get_response().await?;
+            return Err(self.local_redirect(location).await);
         }
 
         if !cgi {
+            let body_target = self.resolve_precompressed().await;
+            let sidecar = if body_target != self.target { self.file_source.metadata(&body_target).await } else { None };
+            if let Some(sidecar) = &sidecar {
+                let encoding = body_target.rsplit('.').next().unwrap_or_default();
+                let modified = sidecar.modified.into();
+                info.etag = Some(format!("{}-{}\"", Self::generate_etag(&modified).trim_end_matches('"'), encoding));
+                info.last_modified = Some(modified);
+            }
+
             let can_send_range = match ConditionalChecker::new(info, &self.request.headers).check() {
                 Err(MiddlewareOutput::Status(Status::Ok, ..)) => false,
-                Err(output) if !metadata.is_dir() => return Err(output),
+                Err(output) if !metadata.is_dir => return Err(output),
                 _ => true,
             };
 
-            self.media_type = util::media_type_by_ext(file_ext).to_string();
-            if self.request.method != Method::Head {
-                let file = File::open(&self.target).await?;
-                let len = file.metadata().await?.len();
-                self.body = Body::Stream(file, len as usize);
-                if can_send_range {
-                    self.set_range_body().await?;
-                }
+            self.is_static_file = true;
+            self.response.set_header(consts::H_ACCEPT_RANGES, consts::H_RANGE_UNIT_BYTES);
+            let cached = self.cached_file(&self.target, metadata).await;
+            self.media_type = self.resolve_media_type(file_ext, cached.as_deref()).await?;
+            self.set_content_language();
+            let cached = match &sidecar {
+                _ if body_target == self.target => cached,
+                Some(sidecar) => self.cached_file(&body_target, sidecar).await,
+                _ => None,
+            };
+            self.body = match cached {
+                Some(file) => Body::Bytes(file.content.clone()),
+                _ => match self.file_source.open(&body_target).await {
+                    Ok(body) => body,
+                    _ => return Err(MiddlewareOutput::Error(Status::NotFound, false)),
+                },
+            };
+            if can_send_range {
+                self.set_range_body().await?;
             }
         }
         Ok(())
     }
 
+    async fn cached_file(&self, path: &str, info: &FileInfo) -> Option<Arc<CachedFile>> {
+        let cache = self.config.file_cache?;
+        if self.config.file_source != FileSourceMode::Disk || info.is_dir || info.len > cache.max_file_size as u64 {
+            return None;
+        } else if let Some(file) = self.state.files.get(path, info) {
+            return Some(file);
+        }
+
+        let content = self.file_source.read(path).await.ok().filter(|content| content.len() as u64 == info.len)?;
+        let etag = Self::generate_etag(&info.modified.into());
+        let file = CachedFile { modified: info.modified, etag, content };
+        Some(self.state.files.insert(path, file, cache.max_size))
+    }
+
+    async fn resolve_media_type(&self, file_ext: &str, cached: Option<&CachedFile>) -> MiddlewareResult<String> {
+        let media_types = &self.config.media_types;
+        let by_ext = media_types.by_ext(file_ext);
+        let sniff = by_ext.is_none() && file_ext.is_empty() && media_types.sniff;
+
+        let mut content = vec![];
+        if sniff || by_ext.as_ref().map(|media_type| util::is_text_media_type(media_type)) == Some(true) {
+            content = match cached {
+                Some(file) => file.content[..file.content.len().min(consts::MEDIA_TYPE_SNIFF_LENGTH)].to_vec(),
+                _ => self.read_sniffed_content().await?,
+            };
+        }
+
+        let media_type = match by_ext {
+            Some(media_type) => media_type,
+            _ => match util::media_type_by_content(&content).filter(|_| sniff) {
+                Some(media_type) => media_type.to_string(),
+                _ => media_types.default.clone(),
+            },
+        };
+        Ok(media_types.with_charset(&media_type, &self.request.uri.to_string(), file_ext, &content))
+    }
+
+    async fn read_sniffed_content(&self) -> MiddlewareResult<Vec<u8>> {
+        let mut content = vec![];
+        Ok(match self.file_source.open(&self.target).await? {
+            Body::File(mut file, _) => {
+                content.resize(consts::MEDIA_TYPE_SNIFF_LENGTH, 0);
+                let len = file.read(&mut content).await?;
+                content.truncate(len);
+                content
+            }
+            Body::Bytes(mut bytes) => {
+                bytes.truncate(consts::MEDIA_TYPE_SNIFF_LENGTH);
+                bytes
+            }
+            Body::Mapped(file, range) => {
+                let high = range.high.min(consts::MEDIA_TYPE_SNIFF_LENGTH);
+                file.read(&Range { low: range.low, high })?
+            }
+            Body::Stream(_) => content,
+        })
+    }
+
+    fn set_cache_headers(&mut self) {
+        let uri = self.request.uri.to_string();
+        let policy = self
+            .config
+            .cache_control
+            .iter()
+            .find(|(RouteSpec(rule_regex), _)| rule_regex.is_match(&uri))
+            .map(|(_, policy)| policy.clone())
+            .or_else(|| if self.config.default_cache_control { CachePolicy::default_for(&self.target) } else { None });
+
+        if let Some(policy) = policy {
+            let directives = policy.directives();
+            self.response.set_header_multi(consts::H_CACHE_CONTROL, directives.iter().map(|d| d.as_str()).collect());
+            if let Some(max_age) = policy.max_age {
+                let expires = util::get_time_utc() + chrono::Duration::seconds(max_age as i64);
+                self.response.set_header(consts::H_EXPIRES, &util::format_time_imf(&expires));
+            }
+        }
+    }
+
+    async fn resolve_precompressed(&mut self) -> String {
+        if self.config.precompressed_files {
+            for (encoding, ext) in &[(consts::H_C_ENC_BROTLI, "br"), (consts::H_C_ENC_GZIP, "gz")] {
+                let sidecar = format!("{}.{}", self.target, ext);
+                if self.file_source.is_file(&sidecar).await {
+                    self.response.add_vary(consts::H_ACCEPT_ENCODING);
+                    if accepts_encoding(&self.request.headers, encoding) {
+                        self.response.set_header(consts::H_CONTENT_ENCODING, encoding);
+                        return sidecar;
+                    }
+                }
+            }
+        }
+        self.target.clone()
+    }
+
     async fn set_range_body(&mut self) -> MiddlewareResult<()> {
         match RangeParser::new(&self.request.headers, &mut self.body, &self.media_type).await.get_body().await {
             Err(output) => return Err(output),
             Ok(RangeBody::Range(range, content_range)) => {
                 match &mut self.body {
                     Body::Bytes(bytes) => self.body = Body::Bytes(bytes[range.low..range.high].to_vec()),
-                    Body::Stream(file, len) => {
-                        file.seek(SeekFrom::Start(range.low as u64)).await?;
-                        *len = range.high - range.low;
+                    Body::File(_, file_range) | Body::Mapped(_, file_range) => {
+                        *file_range = Range { low: file_range.low + range.low, high: file_range.low + range.high };
                     }
+                    Body::Stream(_) => return Ok(()),
                 };
                 self.response.set_header(consts::H_CONTENT_RANGE, &content_range);
                 self.response.set_status(Status::PartialContent);
@@ -166,25 +640,65 @@ impl<'a> ResponseGenerator<'a> {
         Ok(())
     }
 
-    fn generate_etag(modified: &DateTime<Utc>) -> String {
+    fn set_content_language(&mut self) {
+        let media_type = self.media_type.split(';').next().unwrap_or_default();
+        if util::is_text_media_type(media_type) {
+            if let Some(language) = self.config.media_types.language_for(&self.request.uri.to_string()) {
+                self.response.set_header(consts::H_CONTENT_LANGUAGE, language);
+            }
+        }
+    }
+
+    fn set_content_disposition(&mut self) {
+        let path = Path::new(&self.target);
+        let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        let file_ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+
+        let requested = self.query_param(consts::DOWNLOAD_PARAM).is_some();
+        if requested || self.config.downloads.is_forced(&self.request.uri.to_string(), file_ext) {
+            let disposition = util::attachment_disposition(file_name);
+            self.response.set_header(consts::H_CONTENT_DISPOSITION, &disposition);
+        }
+    }
+
+    pub fn generate_etag(modified: &DateTime<Utc>) -> String {
         let mut hasher = DefaultHasher::new();
         let time = util::format_time_imf(modified);
         time.hash(&mut hasher);
 
         let etag = format!("\"{:x}", hasher.finish());
-        time.chars().into_iter().rev().collect::<String>().hash(&mut hasher);
+        time.chars().rev().collect::<String>().hash(&mut hasher);
 
         etag + &format!("{:x}\"", hasher.finish())
     }
 }
 
+fn accepts_encoding(headers: &Headers, encoding: &str) -> bool {
+    headers.get(consts::H_ACCEPT_ENCODING).map(|encodings| encodings.iter().any(|value| {
+        let mut parts = value.split(';').map(|part| part.trim_matches(consts::OPTIONAL_WHITESPACE));
+        let name = parts.next().unwrap_or("");
+        let quality = parts.find_map(|p| p.strip_prefix("q=")).and_then(|q| q.parse::<f32>().ok()).unwrap_or(1.0);
+        (name.eq_ignore_ascii_case(encoding) || name == "*") && quality > 0.0
+    })).unwrap_or(false)
+}
+
+fn is_cgi_script(path: &Path) -> bool {
+    path.file_stem().and_then(|s| s.to_str()).map(|s| s.ends_with("_cgi")).unwrap_or(false)
+}
+
 fn rewrite_url(request: &mut Request, config: &Config) -> (String, String, String) {
     let raw_target = request.uri.to_string();
     let routed_target = route_raw_target(config, &raw_target).unwrap_or(raw_target.to_string());
-    let target = format!("{}{}", &config.file_root, &routed_target);
     if let Ok(uri) = Uri::from(&request.method, &routed_target) {
         request.uri = uri;
     }
+
+    let target = match &request.uri {
+        Uri::OriginForm { path } | Uri::AbsoluteForm { path, .. } => {
+            format!("{}/{}", &config.file_root, path.path_as_string())
+        }
+        _ => format!("{}/", &config.file_root),
+    };
     (raw_target, routed_target, target)
 }
 
@@ -194,7 +708,7 @@ fn route_raw_target(config: &Config, raw_target: &str) -> Option<String> {
             let sub = capture.iter().zip(rule_regex.capture_names()).skip(1)
                 .map(|(matches, name)| (matches.into_iter(), name.unwrap().to_string()))
                 .flat_map(|(captures, name)| captures.map(move |c| (name.to_string(), c.as_str().to_string())))
-                .map(|(name, var)| (name, TemplateSubstitution::Single(var)))
+                .map(|(name, var)| (name, TemplateSubstitution::Raw(var)))
                 .collect::<SubstitutionMap>();
 
             let end_match = rule_regex.find(raw_target).unwrap().end();
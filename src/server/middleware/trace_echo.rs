@@ -0,0 +1,48 @@
+use crate::consts;
+use crate::http::message::{Body, MessageBuilder};
+use crate::http::request::{Method, Request};
+use crate::http::response::{Response, Status};
+use crate::server::config::trace::Trace;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+
+pub struct TraceEchoer<'a> {
+    request: &'a Request,
+    trace: &'a Trace,
+    raw_target: &'a str,
+}
+
+impl<'a> TraceEchoer<'a> {
+    pub fn new(request: &'a Request, trace: &'a Trace, raw_target: &'a str) -> Self {
+        TraceEchoer { request, trace, raw_target }
+    }
+
+    pub fn check(&self) -> MiddlewareResult<()> {
+        if self.request.method != Method::Trace {
+            return Ok(());
+        } else if !self.trace.enabled {
+            return Err(MiddlewareOutput::Status(Status::MethodNotAllowed, false));
+        }
+
+        let headers = self
+            .request
+            .headers
+            .get_all()
+            .iter()
+            .filter(|(name, _)| !self.trace.strip_headers.iter().any(|strip| strip.eq_ignore_ascii_case(name)))
+            .map(|(name, values)| format!("{}: {}{}", name, values.join(", "), consts::CRLF))
+            .collect::<Vec<_>>();
+
+        let body = format!(
+            "{} {} {}{}{}{}",
+            self.request.method, self.raw_target, self.request.http_version, consts::CRLF, headers.join(""),
+            consts::CRLF,
+        );
+        let response = MessageBuilder::<Response>::new()
+            .with_header(consts::H_CACHE_CONTROL, consts::H_CACHE_NO_STORE)
+            .with_body(Body::Bytes(body.into_bytes()), consts::H_MEDIA_HTTP)
+            .build();
+
+        middleware::log_request(self.request, response.status, self.raw_target);
+        Err(MiddlewareOutput::Response(Box::new(response), false))
+    }
+}
@@ -0,0 +1,68 @@
+use std::mem;
+
+use crate::http::message::Body;
+use crate::http::request::{Method, Request};
+use crate::http::response::Status;
+use crate::server::config::{Config, FileSourceMode};
+use crate::server::config::writable_route::WritableRoute;
+use crate::server::file_source::FileSource;
+use crate::server::file_source::writer::FileWriter;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+
+pub struct UploadHandler<'a> {
+    request: &'a mut Request,
+    config: &'a Config,
+    target: &'a str,
+    file_source: &'a dyn FileSource,
+}
+
+impl<'a> UploadHandler<'a> {
+    pub fn new(request: &'a mut Request, config: &'a Config, target: &'a str, file_source: &'a dyn FileSource) -> Self {
+        UploadHandler { request, config, target, file_source }
+    }
+
+    pub async fn check(&mut self) -> MiddlewareResult<()> {
+        let route = match self.config.writable_route(&self.request.uri.to_string()) {
+            Some(route) if self.request.method == Method::Put || self.request.method == Method::Delete => route,
+            _ => return Ok(()),
+        };
+        if self.config.file_source != FileSourceMode::Disk {
+            return Err(MiddlewareOutput::Status(Status::MethodNotAllowed, false));
+        }
+
+        Err(match self.request.method {
+            Method::Put => self.put(route).await,
+            _ => self.delete().await,
+        })
+    }
+
+    async fn put(&mut self, route: WritableRoute) -> MiddlewareOutput {
+        let writer = FileWriter::new(&self.config.file_root);
+        if route.create_dirs {
+            if let Err(err) = writer.create_parent_dirs(self.target).await {
+                return middleware::write_error_output(self.request, err, Status::Conflict);
+            }
+        }
+
+        let content = match &mut self.request.body {
+            Some(Body::Bytes(bytes)) => mem::take(bytes),
+            _ => vec![],
+        };
+        match writer.write(self.target, &content).await {
+            Ok(true) => MiddlewareOutput::Status(Status::Created, false),
+            Ok(false) => MiddlewareOutput::Status(Status::NoContent, false),
+            Err(err) => middleware::write_error_output(self.request, err, Status::Conflict),
+        }
+    }
+
+    async fn delete(&self) -> MiddlewareOutput {
+        match self.file_source.metadata(self.target).await {
+            Some(info) if info.is_dir => MiddlewareOutput::Status(Status::MethodNotAllowed, false),
+            Some(_) => match FileWriter::new(&self.config.file_root).remove(self.target).await {
+                Ok(_) => MiddlewareOutput::Status(Status::NoContent, false),
+                Err(err) => middleware::write_error_output(self.request, err, Status::NotFound),
+            },
+            _ => MiddlewareOutput::Error(Status::NotFound, false),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::mem;
+
+use async_std::path::Path;
+use chrono::{DateTime, Utc};
+
+use crate::{consts, util};
+use crate::http::message::{Body, MessageBuilder};
+use crate::http::request::{Method, Request};
+use crate::http::response::{Response, Status};
+use crate::http::uri::{AbsolutePath, Uri};
+use crate::server::config::{Config, FileSourceMode};
+use crate::server::file_source::{FileInfo, FileSource};
+use crate::server::file_source::writer::FileWriter;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+use crate::server::middleware::response_gen::ResponseGenerator;
+
+const ALLOWED_METHODS: &[Method] = &[
+    Method::Options, Method::Get, Method::Head, Method::Put, Method::Delete, Method::Mkcol, Method::Propfind,
+];
+const PROPSTAT_OK: &str = "HTTP/1.1 200 OK";
+
+pub struct WebDavHandler<'a> {
+    request: &'a mut Request,
+    config: &'a Config,
+    raw_target: &'a str,
+    target: &'a str,
+    file_source: &'a dyn FileSource,
+}
+
+impl<'a> WebDavHandler<'a> {
+    pub fn new(
+        request: &'a mut Request,
+        config: &'a Config,
+        raw_target: &'a str,
+        target: &'a str,
+        file_source: &'a dyn FileSource,
+    ) -> Self {
+        WebDavHandler { request, config, raw_target, target, file_source }
+    }
+
+    pub async fn check(&mut self) -> MiddlewareResult<()> {
+        if !self.config.webdav_enabled(&self.request.uri.to_string()) {
+            return Ok(());
+        }
+
+        let is_write = [Method::Put, Method::Delete, Method::Mkcol].contains(&self.request.method);
+        if is_write && self.config.file_source != FileSourceMode::Disk {
+            return Err(MiddlewareOutput::Status(Status::MethodNotAllowed, false));
+        }
+
+        match self.request.method {
+            Method::Options => Err(self.options_output()),
+            Method::Propfind => Err(self.propfind().await?),
+            Method::Put => Err(self.put().await),
+            Method::Delete => Err(self.delete().await),
+            Method::Mkcol => Err(self.mkcol().await),
+            _ => Ok(()),
+        }
+    }
+
+    fn options_output(&self) -> MiddlewareOutput {
+        let allowed = ALLOWED_METHODS.iter().map(|method| method.to_string()).collect::<Vec<_>>();
+        let response = MessageBuilder::<Response>::new()
+            .with_header(consts::H_DAV, consts::H_DAV_COMPLIANCE)
+            .with_header_multi(consts::H_ALLOW, allowed.iter().map(|method| method.as_str()).collect())
+            .with_header(consts::H_MS_AUTHOR_VIA, "DAV")
+            .build();
+
+        middleware::log_request(self.request, response.status, self.raw_target);
+        MiddlewareOutput::Response(Box::new(response), false)
+    }
+
+    async fn propfind(&self) -> MiddlewareResult<MiddlewareOutput> {
+        let include_children = match self.request.headers.get(consts::H_DEPTH).map(|depth| depth[0].as_str()) {
+            Some(consts::H_DEPTH_ZERO) => false,
+            Some(consts::H_DEPTH_ONE) => true,
+            _ => return Err(MiddlewareOutput::Error(Status::Forbidden, false)),
+        };
+        let info = match self.file_source.metadata(self.target).await {
+            Some(info) => info,
+            _ => return Err(MiddlewareOutput::Error(Status::NotFound, false)),
+        };
+
+        let segments = match Uri::from(&self.request.method, self.raw_target) {
+            Ok(Uri::OriginForm { path }) | Ok(Uri::AbsoluteForm { path, .. }) => path.path,
+            _ => return Err(MiddlewareOutput::Error(Status::BadRequest, false)),
+        };
+        let mut responses = vec![self.prop_response(&segments, &info)];
+
+        if include_children && info.is_dir {
+            let mut entries = self.file_source.read_dir(self.target).await.unwrap_or_default();
+            entries.retain(|entry| !entry.name.starts_with('.') || self.config.dir_listing_show_hidden);
+            entries.sort_by_key(|entry| (!entry.info.is_dir, entry.name.clone()));
+
+            for entry in entries {
+                let mut child_segments = segments.clone();
+                child_segments.push(entry.name);
+                responses.push(self.prop_response(&child_segments, &entry.info));
+            }
+        }
+
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">{}</D:multistatus>\n",
+            responses.join(""),
+        );
+        let media_type = format!("{}; charset={}", consts::H_MEDIA_XML, consts::CHARSET_UTF_8);
+        let response = MessageBuilder::<Response>::new()
+            .with_status(Status::MultiStatus)
+            .with_body(Body::Bytes(body.into_bytes()), &media_type)
+            .build();
+
+        middleware::log_request(self.request, response.status, self.raw_target);
+        Ok(MiddlewareOutput::Response(Box::new(response), false))
+    }
+
+    fn prop_response(&self, segments: &[String], info: &FileInfo) -> String {
+        let path = AbsolutePath { path: segments.to_vec(), query: None, trailing_slash: false }.to_string();
+        let href = if info.is_dir && !segments.is_empty() { path + "/" } else { path };
+        let name = segments.last().map(String::as_str).unwrap_or("");
+        let modified = DateTime::<Utc>::from(info.modified);
+
+        let mut props = format!(
+            "<D:displayname>{}</D:displayname><D:getlastmodified>{}</D:getlastmodified><D:getetag>{}</D:getetag>",
+            util::escape_html(name), util::format_time_imf(&modified),
+            util::escape_html(&ResponseGenerator::generate_etag(&modified)),
+        );
+        if info.is_dir {
+            props.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            let file_ext = Path::new(name).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+            let media_types = &self.config.media_types;
+            let media_type = media_types.by_ext(file_ext).unwrap_or_else(|| media_types.default.clone());
+            props.push_str(&format!(
+                "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>\
+                <D:getcontenttype>{}</D:getcontenttype>",
+                info.len, util::escape_html(&media_type),
+            ));
+        }
+
+        format!(
+            "<D:response><D:href>{}</D:href><D:propstat><D:prop>{}</D:prop><D:status>{}</D:status></D:propstat>\
+            </D:response>",
+            util::escape_html(&href), props, PROPSTAT_OK,
+        )
+    }
+
+    async fn put(&mut self) -> MiddlewareOutput {
+        let content = match &mut self.request.body {
+            Some(Body::Bytes(bytes)) => mem::take(bytes),
+            _ => vec![],
+        };
+        match FileWriter::new(&self.config.file_root).write(self.target, &content).await {
+            Ok(true) => MiddlewareOutput::Status(Status::Created, false),
+            Ok(false) => MiddlewareOutput::Status(Status::NoContent, false),
+            Err(err) => middleware::write_error_output(self.request, err, Status::Conflict),
+        }
+    }
+
+    async fn delete(&self) -> MiddlewareOutput {
+        match FileWriter::new(&self.config.file_root).remove(self.target).await {
+            Ok(_) => MiddlewareOutput::Status(Status::NoContent, false),
+            Err(err) => middleware::write_error_output(self.request, err, Status::NotFound),
+        }
+    }
+
+    async fn mkcol(&self) -> MiddlewareOutput {
+        if matches!(&self.request.body, Some(Body::Bytes(bytes)) if !bytes.is_empty()) {
+            return MiddlewareOutput::Error(Status::UnsupportedMediaType, false);
+        }
+        match FileWriter::new(&self.config.file_root).create_dir(self.target).await {
+            Ok(_) => MiddlewareOutput::Status(Status::Created, false),
+            Err(err) => middleware::write_error_output(self.request, err, Status::Conflict),
+        }
+    }
+}
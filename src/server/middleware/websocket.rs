@@ -0,0 +1,81 @@
+use async_std::net::TcpStream;
+use sha1::{Digest, Sha1};
+
+use crate::consts;
+use crate::http::message::MessageBuilder;
+use crate::http::request::{Method, Request};
+use crate::http::response::{Response, Status};
+use crate::server::config::Config;
+use crate::server::config::route_spec::RouteSpec;
+use crate::server::middleware::{self, MiddlewareOutput, MiddlewareResult};
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const SUPPORTED_VERSION: &str = "13";
+const KEY_LENGTH: usize = 16;
+
+pub struct WebSocketUpgrader<'a> {
+    request: &'a Request,
+    config: &'a Config,
+}
+
+impl<'a> WebSocketUpgrader<'a> {
+    pub fn new(request: &'a Request, config: &'a Config) -> Self {
+        WebSocketUpgrader { request, config }
+    }
+
+    pub async fn check(&self) -> MiddlewareResult<()> {
+        let target = self.request.uri.to_string();
+        let backend = match self.config.websocket_backends.iter().find(|(RouteSpec(r), _)| r.is_match(&target)) {
+            Some((_, backend)) => backend,
+            _ => return Ok(()),
+        };
+
+        let protocols = self.request.upgrade_protocols();
+        if !protocols.iter().any(|protocol| protocol.eq_ignore_ascii_case(consts::H_UPGRADE_WEBSOCKET)) {
+            return self.upgrade_required_output(consts::H_UPGRADE, consts::H_UPGRADE_WEBSOCKET);
+        }
+        let key = match self.request.headers.get(consts::H_SEC_WEBSOCKET_KEY) {
+            Some(key) if key.len() == 1 && base64::decode(&key[0]).map(|k| k.len() == KEY_LENGTH).unwrap_or(false) => {
+                &key[0]
+            }
+            _ => return Err(MiddlewareOutput::Error(Status::BadRequest, true)),
+        };
+        if self.request.method != Method::Get {
+            return Err(MiddlewareOutput::Error(Status::BadRequest, true));
+        }
+        if !self.has_token(consts::H_SEC_WEBSOCKET_VERSION, SUPPORTED_VERSION) {
+            return self.upgrade_required_output(consts::H_SEC_WEBSOCKET_VERSION, SUPPORTED_VERSION);
+        }
+
+        let stream = match TcpStream::connect(backend).await {
+            Ok(stream) => stream,
+            _ => return Err(MiddlewareOutput::Error(Status::BadGateway, true)),
+        };
+        let accept = base64::encode(Sha1::digest(format!("{}{}", key, ACCEPT_GUID).as_bytes()));
+        let response = MessageBuilder::<Response>::new()
+            .with_upgrade(consts::H_UPGRADE_WEBSOCKET)
+            .with_header(consts::H_SEC_WEBSOCKET_ACCEPT, &accept)
+            .build();
+
+        middleware::log_request(self.request, response.status, format!("{} -> {}", self.request.uri, backend));
+        Err(MiddlewareOutput::Upgrade(Box::new(response), stream))
+    }
+
+    fn has_token(&self, name: &str, token: &str) -> bool {
+        self.request
+            .headers
+            .get(name)
+            .map(|values| values.iter().any(|value| value.eq_ignore_ascii_case(token)))
+            .unwrap_or(false)
+    }
+
+    fn upgrade_required_output(&self, name: &str, value: &str) -> MiddlewareResult<()> {
+        middleware::log_request(self.request, Status::UpgradeRequired, &self.request.uri);
+
+        let response = MessageBuilder::<Response>::new()
+            .with_status(Status::UpgradeRequired)
+            .with_header(name, value)
+            .build();
+        Err(MiddlewareOutput::Response(Box::new(response), false))
+    }
+}
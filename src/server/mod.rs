@@ -1,10 +1,21 @@
 pub mod file_server;
 pub mod template;
 pub mod config;
+pub mod metrics;
+pub mod file_source;
+pub mod throttle;
+pub mod connection_limiter;
+pub mod socket_options;
+pub mod geoip;
+#[cfg(feature = "acme")]
+pub mod acme;
 
-mod middleware;
+pub mod middleware;
+
+use crate::server::config::Config;
 
 pub trait Server {
     fn start(&self);
     fn stop(&self);
+    fn reload(&self, config: Config);
 }
@@ -0,0 +1,118 @@
+use std::mem;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+use async_std::io;
+use async_std::net::TcpStream;
+
+use crate::log;
+use crate::server::config::tcp_options::TcpOptions;
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const KEEPALIVE_IDLE: libc::c_int = libc::TCP_KEEPALIVE;
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+const KEEPALIVE_IDLE: libc::c_int = libc::TCP_KEEPIDLE;
+
+#[cfg(any(
+    target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd",
+    target_os = "dragonfly",
+))]
+const REUSE_PORT: Option<libc::c_int> = Some(libc::SO_REUSEPORT);
+#[cfg(not(any(
+    target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd",
+    target_os = "dragonfly",
+)))]
+const REUSE_PORT: Option<libc::c_int> = None;
+
+const LISTEN_BACKLOG: libc::c_int = 128;
+
+pub fn apply(stream: &TcpStream, options: &TcpOptions) -> io::Result<()> {
+    stream.set_nodelay(options.nodelay)?;
+
+    if let Some(interval) = options.keepalive {
+        let interval = interval.max(1).min(libc::c_int::MAX as u64) as libc::c_int;
+        set_option(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+        set_option(stream.as_raw_fd(), libc::IPPROTO_TCP, KEEPALIVE_IDLE, interval)?;
+        set_option(stream.as_raw_fd(), libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, interval)?;
+    }
+    if let Some(size) = options.send_buffer_size {
+        set_option(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_SNDBUF, clamp_size(size))?;
+    }
+    if let Some(size) = options.recv_buffer_size {
+        set_option(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_RCVBUF, clamp_size(size))?;
+    }
+    Ok(())
+}
+
+fn clamp_size(size: usize) -> libc::c_int {
+    size.min(libc::c_int::MAX as usize) as libc::c_int
+}
+
+fn set_option(fd: RawFd, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> io::Result<()> {
+    let result = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    check(result)
+}
+
+pub fn bind_reusing_port(address: &str) -> io::Result<net::TcpListener> {
+    let address = address.to_socket_addrs()?.next().ok_or(io::ErrorKind::AddrNotAvailable)?;
+    let family = if address.is_ipv4() { libc::AF_INET } else { libc::AF_INET6 };
+
+    let fd = unsafe { libc::socket(family, libc::SOCK_STREAM, 0) };
+    check(fd)?;
+    let listener = unsafe { net::TcpListener::from_raw_fd(fd) };
+    check(unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) })?;
+
+    set_option(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1)?;
+    match REUSE_PORT {
+        Some(reuse_port) => {
+            if let Err(err) = set_option(fd, libc::SOL_SOCKET, reuse_port, 1) {
+                log::warn(format!("Could not set SO_REUSEPORT on {}: {}!", address, err));
+            }
+        }
+        _ => log::warn("SO_REUSEPORT is not supported on this platform!"),
+    }
+
+    bind(fd, &address)?;
+    check(unsafe { libc::listen(fd, LISTEN_BACKLOG) })?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+fn bind(fd: RawFd, address: &SocketAddr) -> io::Result<()> {
+    let result = match address {
+        SocketAddr::V4(address) => {
+            let mut raw = unsafe { mem::zeroed::<libc::sockaddr_in>() };
+            raw.sin_family = libc::AF_INET as libc::sa_family_t;
+            raw.sin_port = address.port().to_be();
+            raw.sin_addr.s_addr = u32::from_ne_bytes(address.ip().octets());
+            unsafe { libc::bind(fd, &raw as *const _ as *const libc::sockaddr, mem::size_of_val(&raw) as _) }
+        }
+        SocketAddr::V6(address) => {
+            let mut raw = unsafe { mem::zeroed::<libc::sockaddr_in6>() };
+            raw.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            raw.sin6_port = address.port().to_be();
+            raw.sin6_addr.s6_addr = address.ip().octets();
+            raw.sin6_flowinfo = address.flowinfo();
+            raw.sin6_scope_id = address.scope_id();
+            unsafe { libc::bind(fd, &raw as *const _ as *const libc::sockaddr, mem::size_of_val(&raw) as _) }
+        }
+    };
+    check(result)
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
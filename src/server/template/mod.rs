@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use crate::server::template::template_parser::TemplateParser;
+use crate::util;
 
 pub mod templates;
 
@@ -13,12 +14,14 @@ pub enum TemplatePart {
     String(String),
     Placeholder(PlaceholderName),
     MultiplePlaceholder(PlaceholderName, Template),
+    Conditional(PlaceholderName, Template, Option<Template>),
 }
 
 pub type SubstitutionMap = HashMap<PlaceholderName, TemplateSubstitution>;
 
 pub enum TemplateSubstitution {
     Single(String),
+    Raw(String),
     Multiple(Vec<SubstitutionMap>),
 }
 
@@ -38,7 +41,8 @@ impl Template {
             match part {
                 TemplatePart::String(value) => output.push_str(value),
                 TemplatePart::Placeholder(name) => match placeholders.get(name) {
-                    Some(TemplateSubstitution::Single(output_part)) => output.push_str(output_part),
+                    Some(TemplateSubstitution::Single(output_part)) => output.push_str(&util::escape_html(output_part)),
+                    Some(TemplateSubstitution::Raw(output_part)) => output.push_str(output_part),
                     _ => return None,
                 },
                 TemplatePart::MultiplePlaceholder(name, template) => match placeholders.get(name) {
@@ -47,6 +51,20 @@ impl Template {
                     },
                     _ => return None,
                 },
+                TemplatePart::Conditional(name, then, otherwise) => {
+                    let truthy = match placeholders.get(name) {
+                        Some(TemplateSubstitution::Single(value)) | Some(TemplateSubstitution::Raw(value)) => {
+                            !value.is_empty()
+                        }
+                        Some(TemplateSubstitution::Multiple(maps)) => !maps.is_empty(),
+                        _ => false,
+                    };
+                    match (truthy, otherwise) {
+                        (true, _) => output.push_str(&then.substitute(placeholders)?),
+                        (false, Some(otherwise)) => output.push_str(&otherwise.substitute(placeholders)?),
+                        _ => {}
+                    }
+                }
             };
         }
         Some(output)
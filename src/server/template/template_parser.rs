@@ -1,5 +1,10 @@
 use crate::server::template::{Template, TemplatePart};
 
+const IF_START: &str = "{{#if ";
+const IF_ELSE: &str = "{{else}}";
+const IF_END: &str = "{{/if}}";
+const TAG_END: &str = "}}";
+
 pub struct TemplateParser {
     file: String,
     parts: Vec<TemplatePart>,
@@ -46,16 +51,19 @@ impl TemplateParser {
                     pos = end_index + 1;
                     TemplatePart::MultiplePlaceholder(name, parts)
                 }
+                '{' if starts_with(&chars, pos, IF_START) => {
+                    let (part, end_index) = Self::parse_conditional(&chars, pos)?;
+                    pos = end_index;
+                    part
+                }
                 '\\' => {
                     pos += 2;
                     TemplatePart::String(chars[pos - 1].to_string())
                 }
                 _ => {
-                    let start_of_next_part = chars[pos..]
-                        .iter()
-                        .position(|c| "[*\\".contains(*c))
-                        .unwrap_or(chars.len() - pos)
-                        + pos;
+                    let start_of_next_part = (pos..chars.len())
+                        .find(|&index| "[*\\".contains(chars[index]) || starts_with(&chars, index, IF_START))
+                        .unwrap_or(chars.len());
                     let text = chars[pos..start_of_next_part].iter().collect();
 
                     pos = start_of_next_part;
@@ -65,4 +73,42 @@ impl TemplateParser {
         }
         Some(self.parts)
     }
+
+    fn parse_conditional(chars: &[char], pos: usize) -> Option<(TemplatePart, usize)> {
+        let name_start = pos + IF_START.len();
+        let name_end = (name_start..chars.len()).find(|&index| starts_with(chars, index, TAG_END))?;
+        let name = chars[name_start..name_end].iter().collect::<String>().trim().to_string();
+
+        let body_start = name_end + TAG_END.len();
+        let mut depth = 0;
+        let mut else_index = None;
+        let mut index = body_start;
+        while index < chars.len() {
+            if starts_with(chars, index, IF_START) {
+                depth += 1;
+            } else if starts_with(chars, index, IF_ELSE) && depth == 0 && else_index.is_none() {
+                else_index = Some(index);
+            } else if starts_with(chars, index, IF_END) {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            index += 1;
+        }
+        if index >= chars.len() {
+            return None;
+        }
+
+        let parse = |start: usize, end: usize| TemplateParser::new(chars[start..end].iter().collect()).parse();
+        let (then, otherwise) = match else_index {
+            Some(else_index) => (parse(body_start, else_index)?, Some(parse(else_index + IF_ELSE.len(), index)?)),
+            _ => (parse(body_start, index)?, None),
+        };
+        Some((TemplatePart::Conditional(name, then, otherwise), index + IF_END.len()))
+    }
+}
+
+fn starts_with(chars: &[char], pos: usize, pattern: &str) -> bool {
+    pattern.chars().enumerate().all(|(offset, ch)| chars.get(pos + offset) == Some(&ch))
 }
@@ -1,24 +1,77 @@
+use std::borrow::Cow;
+
 use async_std::fs;
+use futures::future::{BoxFuture, FutureExt};
 
-use crate::consts;
+use crate::{consts, log};
 use crate::server::template::Template;
 
+const INCLUDE_START: &str = "{{> ";
+const INCLUDE_END: &str = "}}";
+
 #[derive(Clone)]
 pub struct Templates {
     pub error: Template,
     pub dir_listing: Template,
+
+    template_root: String,
+    reload: bool,
 }
 
 impl Templates {
-    pub async fn new(template_root: &str) -> Option<Self> {
-        let error_path = format!("{}/{}", template_root, consts::TEMPLATE_ERROR);
-        let dir_listing_path = format!("{}/{}", template_root, consts::TEMPLATE_DIR_LISTING);
-
-        let error_template = fs::read_to_string(error_path).await.ok()?;
-        let dir_listing_template = fs::read_to_string(dir_listing_path).await.ok()?;
+    pub async fn new(template_root: &str, reload: bool) -> Option<Self> {
+        let error_template = load_template(template_root, consts::TEMPLATE_ERROR.to_string(), vec![]).await?;
+        let dir_listing_template = load_template(template_root, consts::TEMPLATE_DIR_LISTING.to_string(), vec![]).await?;
 
         let error = Template::new(error_template)?;
         let dir_listing = Template::new(dir_listing_template)?;
-        Some(Templates { error, dir_listing })
+        Some(Templates { error, dir_listing, template_root: template_root.to_string(), reload })
     }
+
+    pub async fn current(&self) -> Cow<'_, Self> {
+        if !self.reload {
+            return Cow::Borrowed(self);
+        }
+
+        match Templates::new(&self.template_root, true).await {
+            Some(templates) => Cow::Owned(templates),
+            _ => {
+                log::warn("Template directory invalid or incomplete; using the templates loaded at startup!");
+                Cow::Borrowed(self)
+            }
+        }
+    }
+}
+
+fn load_template(template_root: &str, name: String, mut including: Vec<String>) -> BoxFuture<'_, Option<String>> {
+    async move {
+        if name.split('/').any(|part| part.is_empty() || part == "..") {
+            log::warn(format!("Template include `{}` is outside the template directory!", name));
+            return None;
+        } else if including.contains(&name) {
+            log::warn(format!("Template include cycle: {} -> {}!", including.join(" -> "), name));
+            return None;
+        }
+
+        let mut rest = match fs::read_to_string(format!("{}/{}", template_root, name)).await {
+            Ok(file) => file,
+            _ if !including.is_empty() => {
+                log::warn(format!("Template include `{}` could not be read!", name));
+                return None;
+            }
+            _ => return None,
+        };
+        including.push(name);
+
+        let mut output = String::new();
+        while let Some(start) = rest.find(INCLUDE_START) {
+            let end = rest[start..].find(INCLUDE_END)? + start;
+            let partial = rest[start + INCLUDE_START.len()..end].trim().to_string();
+            output.push_str(&rest[..start]);
+            output.push_str(&load_template(template_root, partial, including.clone()).await?);
+            rest = rest[end + INCLUDE_END.len()..].to_string();
+        }
+        output.push_str(&rest);
+        Some(output)
+    }.boxed()
 }
@@ -0,0 +1,62 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_std::io::{self, Write};
+use async_std::task;
+
+pub struct ThrottledWriter<'a, W: Write + Unpin> {
+    writer: &'a mut W,
+    rate: u64,
+    tokens: f64,
+    last_refill: Instant,
+    delay: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl<'a, W: Write + Unpin> ThrottledWriter<'a, W> {
+    pub fn new(writer: &'a mut W, rate: u64) -> Self {
+        ThrottledWriter { writer, rate, tokens: rate as f64, last_refill: Instant::now(), delay: None }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let refilled = self.tokens + now.duration_since(self.last_refill).as_secs_f64() * self.rate as f64;
+        self.tokens = refilled.min(self.rate as f64);
+        self.last_refill = now;
+    }
+}
+
+impl<W: Write + Unpin> Write for ThrottledWriter<'_, W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        loop {
+            if let Some(delay) = &mut self.delay {
+                if delay.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.delay = None;
+            }
+
+            self.refill();
+            let wanted = buf.len().min(self.rate as usize).max(1);
+            if self.tokens >= wanted as f64 {
+                let result = Pin::new(&mut *self.writer).poll_write(cx, &buf[..wanted.min(buf.len())]);
+                if let Poll::Ready(Ok(written)) = result {
+                    self.tokens -= written as f64;
+                }
+                return result;
+            }
+
+            let wait = (wanted as f64 - self.tokens) / self.rate as f64;
+            self.delay = Some(Box::pin(task::sleep(Duration::from_secs_f64(wait))));
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.writer).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.writer).poll_close(cx)
+    }
+}
@@ -1,6 +1,6 @@
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
-use chrono::{DateTime, Local, Utc};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
 
 use crate::consts;
 
@@ -10,6 +10,35 @@ pub struct Range {
     pub high: usize,
 }
 
+pub struct Timing {
+    start: Instant,
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timing {
+    pub fn new() -> Self {
+        Timing { start: Instant::now(), phases: vec![] }
+    }
+
+    pub fn record(&mut self, phase: &'static str, since: Instant) {
+        let duration = since.elapsed();
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += duration,
+            _ => self.phases.push((phase, duration)),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn get_time_utc() -> DateTime<Utc> {
     SystemTime::now().into()
 }
@@ -18,54 +47,233 @@ pub fn get_time_local() -> DateTime<Local> {
     SystemTime::now().into()
 }
 
-pub fn parse_time_imf(time: &str) -> Option<DateTime<Utc>> {
-    DateTime::parse_from_str(time, "%a, %d %b %Y %T GMT").ok().map(|t| t.with_timezone(&Utc))
+pub fn parse_time_http(time: &str) -> Option<DateTime<Utc>> {
+    const HTTP_DATE_FORMATS: &[&str] = &["%a, %d %b %Y %T GMT", "%A, %d-%b-%y %T GMT", "%a %b %e %T %Y"];
+    HTTP_DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(time, format).ok())
+        .map(|time| DateTime::from_utc(time, Utc))
 }
 
 pub fn format_time_imf(time: &DateTime<Utc>) -> String {
     time.format("%a, %d %b %Y %T GMT").to_string()
 }
 
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let len = a.len().max(b.len());
+    let diff = (0..len).fold(a.len() ^ b.len(), |diff, i| {
+        diff | (*a.get(i).unwrap_or(&0) ^ *b.get(i).unwrap_or(&0)) as usize
+    });
+    diff == 0
+}
+
+pub fn escape_json(str: &str) -> String {
+    let mut escaped = String::with_capacity(str.len());
+    for ch in str.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            _ if ch.is_control() => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+pub fn attachment_disposition(file_name: &str) -> String {
+    let fallback = file_name
+        .chars()
+        .map(|ch| if ch.is_ascii() && !ch.is_ascii_control() && ch != '"' && ch != '\\' { ch } else { '_' })
+        .collect::<String>();
+    let encoded = file_name
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_'
+            | b'`' | b'|' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect::<String>();
+
+    if fallback == file_name {
+        format!("attachment; filename=\"{}\"", fallback)
+    } else {
+        format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+    }
+}
+
+pub fn escape_html(str: &str) -> String {
+    let mut escaped = String::with_capacity(str.len());
+    for ch in str.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
 pub fn is_visible_char(ch: char) -> bool {
     ('!'..='~').contains(&ch)
 }
 
-pub fn media_type_by_ext(ext: &str) -> &str {
-    match ext {
+pub fn media_type_by_ext(ext: &str) -> &'static str {
+    match ext.to_ascii_lowercase().as_str() {
+        "3gp" => consts::H_MEDIA_3GP,
+        "7z" => consts::H_MEDIA_7Z,
         "aac" => consts::H_MEDIA_AAC,
+        "apng" => consts::H_MEDIA_APNG,
+        "atom" => consts::H_MEDIA_ATOM,
         "avi" => consts::H_MEDIA_AVI,
+        "avif" => consts::H_MEDIA_AVIF,
         "bmp" => consts::H_MEDIA_BITMAP,
+        "bz2" => consts::H_MEDIA_BZIP2,
         "cgi" => consts::H_MEDIA_CGI_SCRIPT,
         "css" => consts::H_MEDIA_CSS,
         "csv" => consts::H_MEDIA_CSV,
+        "doc" => consts::H_MEDIA_DOC,
+        "docx" => consts::H_MEDIA_DOCX,
+        "eot" => consts::H_MEDIA_EOT,
         "epub" => consts::H_MEDIA_EPUB,
-        "gz" => consts::H_MEDIA_GZIP,
+        "flac" => consts::H_MEDIA_FLAC,
+        "gz" | "tgz" => consts::H_MEDIA_GZIP,
         "gif" => consts::H_MEDIA_GIF,
+        "heic" => consts::H_MEDIA_HEIC,
         "htm" | "html" => consts::H_MEDIA_HTML,
         "ico" => consts::H_MEDIA_ICON,
+        "ics" => consts::H_MEDIA_CALENDAR,
+        "jar" => consts::H_MEDIA_JAR,
         "jpg" | "jpeg" => consts::H_MEDIA_JPEG,
-        "js" => consts::H_MEDIA_JAVASCRIPT,
-        "json" => consts::H_MEDIA_JSON,
+        "js" | "mjs" => consts::H_MEDIA_JAVASCRIPT,
+        "json" | "map" => consts::H_MEDIA_JSON,
+        "jsonld" => consts::H_MEDIA_JSON_LD,
+        "m4a" => consts::H_MEDIA_M4A,
+        "md" | "markdown" => consts::H_MEDIA_MARKDOWN,
+        "mid" | "midi" => consts::H_MEDIA_MIDI,
+        "mkv" => consts::H_MEDIA_MKV,
+        "mov" => consts::H_MEDIA_QUICKTIME,
         "mp3" => consts::H_MEDIA_MP3,
-        "mp4" => consts::H_MEDIA_MP4,
+        "mp4" | "m4v" => consts::H_MEDIA_MP4,
+        "mpeg" | "mpg" => consts::H_MEDIA_MPEG,
+        "ods" => consts::H_MEDIA_ODS,
+        "odt" => consts::H_MEDIA_ODT,
         "oga" => consts::H_MEDIA_OGG_AUDIO,
+        "ogg" | "ogx" => consts::H_MEDIA_OGG,
+        "ogv" => consts::H_MEDIA_OGG_VIDEO,
+        "opus" => consts::H_MEDIA_OPUS,
+        "otf" => consts::H_MEDIA_OTF,
         "png" => consts::H_MEDIA_PNG,
         "pdf" => consts::H_MEDIA_PDF,
         "php" => consts::H_MEDIA_PHP,
+        "ppt" => consts::H_MEDIA_PPT,
+        "pptx" => consts::H_MEDIA_PPTX,
+        "rar" => consts::H_MEDIA_RAR,
+        "rss" => consts::H_MEDIA_RSS,
         "rtf" => consts::H_MEDIA_RTF,
+        "sh" => consts::H_MEDIA_SH,
         "svg" => consts::H_MEDIA_SVG,
         "swf" => consts::H_MEDIA_SWF,
+        "tar" => consts::H_MEDIA_TAR,
+        "tif" | "tiff" => consts::H_MEDIA_TIFF,
+        "tsv" => consts::H_MEDIA_TSV,
         "ttf" => consts::H_MEDIA_TTF,
         "txt" => consts::H_MEDIA_TEXT,
+        "vcf" => consts::H_MEDIA_VCARD,
+        "vtt" => consts::H_MEDIA_WEBVTT,
+        "wasm" => consts::H_MEDIA_WASM,
         "wav" => consts::H_MEDIA_WAV,
         "weba" => consts::H_MEDIA_WEBM_AUDIO,
         "webm" => consts::H_MEDIA_WEBM_VIDEO,
+        "webmanifest" => consts::H_MEDIA_MANIFEST,
         "webp" => consts::H_MEDIA_WEBP_IMAGE,
         "woff" => consts::H_MEDIA_WOFF,
         "woff2" => consts::H_MEDIA_WOFF2,
         "xhtml" => consts::H_MEDIA_XHTML,
+        "xls" => consts::H_MEDIA_XLS,
+        "xlsx" => consts::H_MEDIA_XLSX,
         "xml" => consts::H_MEDIA_XML,
+        "xz" => consts::H_MEDIA_XZ,
+        "yaml" | "yml" => consts::H_MEDIA_YAML,
         "zip" => consts::H_MEDIA_ZIP,
         _ => consts::H_MEDIA_BINARY,
     }
 }
+
+pub fn media_type_by_content(content: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", consts::H_MEDIA_PNG),
+        (b"\xff\xd8\xff", consts::H_MEDIA_JPEG),
+        (b"GIF87a", consts::H_MEDIA_GIF),
+        (b"GIF89a", consts::H_MEDIA_GIF),
+        (b"%PDF-", consts::H_MEDIA_PDF),
+        (b"PK\x03\x04", consts::H_MEDIA_ZIP),
+        (b"\x1f\x8b", consts::H_MEDIA_GZIP),
+        (b"\0asm", consts::H_MEDIA_WASM),
+    ];
+
+    if let Some((_, media_type)) = SIGNATURES.iter().find(|(signature, _)| content.starts_with(signature)) {
+        return Some(media_type);
+    } else if charset_by_bom(content).is_some() && !content.starts_with(consts::BOM_UTF_8) {
+        return Some(consts::H_MEDIA_TEXT);
+    }
+
+    let text = match std::str::from_utf8(content) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&content[..e.valid_up_to()]).ok()?,
+        _ => return None,
+    };
+    if text.contains('\0') {
+        return None;
+    }
+
+    let start = text.trim_start_matches('\u{feff}').trim_start().chars().take(14).collect::<String>();
+    let start = start.to_ascii_lowercase();
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        Some(consts::H_MEDIA_HTML)
+    } else if start.starts_with("<?xml") {
+        Some(consts::H_MEDIA_XML)
+    } else {
+        Some(consts::H_MEDIA_TEXT)
+    }
+}
+
+pub fn charset_by_bom(content: &[u8]) -> Option<&'static str> {
+    if content.starts_with(consts::BOM_UTF_8) {
+        Some(consts::CHARSET_UTF_8)
+    } else if content.starts_with(consts::BOM_UTF_16_BE) {
+        Some(consts::CHARSET_UTF_16_BE)
+    } else if content.starts_with(consts::BOM_UTF_16_LE) {
+        Some(consts::CHARSET_UTF_16_LE)
+    } else {
+        None
+    }
+}
+
+pub fn is_text_media_type(media_type: &str) -> bool {
+    let subtype = &media_type[media_type.find('/').map(|index| index + 1).unwrap_or(0)..];
+    media_type.starts_with("text/") || ["json", "xml", "javascript"].contains(&subtype) ||
+        subtype.ends_with("+json") || subtype.ends_with("+xml")
+}
+
+pub fn file_category_by_ext(ext: &str) -> &str {
+    let media_type = media_type_by_ext(ext);
+    match ext {
+        "7z" | "bz2" | "epub" | "gz" | "rar" | "tar" | "tgz" | "xz" | "zip" => "archive",
+        "c" | "cpp" | "h" | "md" | "pl" | "py" | "rs" | "sh" | "toml" | "yaml" | "yml" => "text",
+        _ if media_type == consts::H_MEDIA_JSON || media_type == consts::H_MEDIA_XML => "text",
+        _ => match &media_type[..media_type.find('/').unwrap_or(0)] {
+            "image" => "image",
+            "audio" => "audio",
+            "video" => "video",
+            "text" => "text",
+            _ => "binary",
+        },
+    }
+}
@@ -0,0 +1,66 @@
+mod common;
+
+const CREDENTIALS: &str = "user:$apr1$lucent$3Uj84dTWxhQiAMOF2m7t/.";
+const AUTHORIZATION: &str = "Authorization: Basic dXNlcjpzZWNyZXQ=\r\n";
+
+async fn responses(policy: &str) -> (String, String, String) {
+    let root = common::temp_root("auth-cache-control");
+    std::fs::create_dir(root.join("private")).unwrap();
+    common::write_file(&root, "private/page.txt", "private");
+    common::write_file(&root, "public.txt", "public");
+
+    let builder = common::builder(&root)
+        .with_basic_auth("/private", "Private", CREDENTIALS)
+        .with("auth_cache_control", policy);
+    let (server, address) = common::start(builder).await;
+    let get = |target: &str, extra: &str| {
+        format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", target, extra)
+    };
+    let private = common::exchange(address, get("/private/page.txt", AUTHORIZATION).as_bytes()).await;
+    let authorized_public = common::exchange(address, get("/public.txt", AUTHORIZATION).as_bytes()).await;
+    let anonymous = common::exchange(address, get("/public.txt", "").as_bytes()).await;
+
+    server.shutdown().await;
+    common::remove_root(&root);
+    (private, authorized_public, anonymous)
+}
+
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    let head = &response[..response.find("\r\n\r\n").unwrap()];
+    head.lines().skip(1).find_map(|line| {
+        let (line_name, value) = line.split_at(line.find(':')?);
+        Some(value[1..].trim()).filter(|_| line_name.eq_ignore_ascii_case(name))
+    })
+}
+
+fn varies_on_authorization(response: &str) -> bool {
+    let vary = header(response, "Vary").unwrap_or_default();
+    vary.split(',').any(|field| field.trim().eq_ignore_ascii_case("Authorization"))
+}
+
+#[async_std::test]
+async fn authorized_responses_are_private() {
+    let (private, authorized_public, anonymous) = responses("private").await;
+    for response in &[&private, &authorized_public] {
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert_eq!(header(response, "Cache-Control"), Some("private"));
+        assert!(varies_on_authorization(response), "{}", response);
+    }
+
+    assert!(anonymous.starts_with("HTTP/1.1 200"), "{}", anonymous);
+    assert_ne!(header(&anonymous, "Cache-Control"), Some("private"));
+    assert!(!varies_on_authorization(&anonymous));
+}
+
+#[async_std::test]
+async fn authorized_responses_can_be_uncacheable() {
+    let (private, authorized_public, anonymous) = responses("no-store").await;
+    for response in &[&private, &authorized_public] {
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert_eq!(header(response, "Cache-Control"), Some("no-store"));
+        assert!(varies_on_authorization(response), "{}", response);
+    }
+
+    assert_ne!(header(&anonymous, "Cache-Control"), Some("no-store"));
+    assert!(!varies_on_authorization(&anonymous));
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+const ENV_SCRIPT: &str = "printf 'Content-Type: text/plain\\r\\n\\r\\n'\nenv\n";
+const CREDENTIALS: &str = "user:$apr1$lucent$3Uj84dTWxhQiAMOF2m7t/.";
+
+async fn script_env(request: &str) -> HashMap<String, String> {
+    let root = common::temp_root("cgi-env");
+    std::fs::create_dir(root.join("cgi")).unwrap();
+    std::fs::create_dir(root.join("private")).unwrap();
+    common::write_file(&root, "cgi/env_cgi.sh", ENV_SCRIPT);
+    common::write_file(&root, "private/env_cgi.sh", ENV_SCRIPT);
+
+    let builder = common::builder(&root)
+        .with_cgi_executor("sh", "sh")
+        .with_basic_auth("/private", "Private", CREDENTIALS);
+    let (server, address) = common::start(builder).await;
+    let response = common::exchange(address, request.as_bytes()).await;
+    server.shutdown().await;
+    common::remove_root(&root);
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    body.lines()
+        .filter_map(|line| {
+            let index = line.find('=')?;
+            Some((line[..index].to_string(), line[index + 1..].to_string()))
+        })
+        .collect()
+}
+
+fn var<'a>(env: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    env.get(name).map(String::as_str)
+}
+
+#[async_std::test]
+async fn request_variables_are_set() {
+    let request = "GET /cgi/env_cgi.sh/extra/path?a=1&b=2 HTTP/1.1\r\nHost: Example.com:8080\r\n\
+        X-Custom-Header: custom\r\nAccept-Language: en\r\nConnection: close\r\n\r\n";
+    let env = script_env(request).await;
+
+    assert_eq!(var(&env, "SCRIPT_NAME"), Some("/cgi/env_cgi.sh"));
+    assert_eq!(var(&env, "PATH_INFO"), Some("/extra/path"));
+    assert_eq!(var(&env, "REQUEST_METHOD"), Some("GET"));
+    assert_eq!(var(&env, "SERVER_NAME"), Some("example.com"));
+    assert_eq!(var(&env, "SERVER_PROTOCOL"), Some("HTTP/1.1"));
+    assert_eq!(var(&env, "HTTP_X_CUSTOM_HEADER"), Some("custom"));
+    assert_eq!(var(&env, "HTTP_ACCEPT_LANGUAGE"), Some("en"));
+
+    let query = var(&env, "QUERY_STRING").unwrap();
+    let mut params = query.split('&').collect::<Vec<_>>();
+    params.sort_unstable();
+    assert_eq!(params, ["a=1", "b=2"]);
+}
+
+#[async_std::test]
+async fn empty_query_string_is_still_set() {
+    let env = script_env("GET /cgi/env_cgi.sh HTTP/1.0\r\nHost: localhost\r\n\r\n").await;
+
+    assert_eq!(var(&env, "QUERY_STRING"), Some(""));
+    assert_eq!(var(&env, "SCRIPT_NAME"), Some("/cgi/env_cgi.sh"));
+    assert_eq!(var(&env, "PATH_INFO"), None);
+    assert_eq!(var(&env, "SERVER_NAME"), Some("localhost"));
+    assert_eq!(var(&env, "SERVER_PROTOCOL"), Some("HTTP/1.0"));
+}
+
+#[async_std::test]
+async fn authenticated_user_is_set_without_credentials() {
+    let request = "GET /private/env_cgi.sh HTTP/1.0\r\nHost: localhost\r\nAuthorization: Basic dXNlcjpzZWNyZXQ=\r\n\
+        Proxy: http://proxy.example\r\nProxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n\r\n";
+    let env = script_env(request).await;
+
+    assert_eq!(var(&env, "AUTH_TYPE"), Some("Basic"));
+    assert_eq!(var(&env, "REMOTE_USER"), Some("user"));
+    assert_eq!(var(&env, "HTTP_AUTHORIZATION"), None);
+    assert_eq!(var(&env, "HTTP_PROXY"), None);
+    assert_eq!(var(&env, "HTTP_PROXY_AUTHORIZATION"), None);
+}
+
+#[async_std::test]
+async fn unchecked_credentials_are_not_passed() {
+    let request = "GET /cgi/env_cgi.sh HTTP/1.0\r\nHost: localhost\r\nAuthorization: Basic dXNlcjpzZWNyZXQ=\r\n\r\n";
+    let env = script_env(request).await;
+
+    assert_eq!(var(&env, "AUTH_TYPE"), None);
+    assert_eq!(var(&env, "REMOTE_USER"), None);
+    assert_eq!(var(&env, "HTTP_AUTHORIZATION"), None);
+}
+
+const ECHO_SCRIPT: &str = "printf 'Content-Type: text/plain\\r\\n\\r\\n'\ncat\n";
+const IGNORE_SCRIPT: &str = "printf 'Content-Type: text/plain\\r\\n\\r\\nignored'\n";
+
+struct ServerProcess {
+    child: Child,
+    address: SocketAddr,
+}
+
+impl ServerProcess {
+    fn start(root: &Path) -> Self {
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let config = format!(
+            "addresses: [\"{}\"]\nfile_root: \"{}\"\ntemplate_root: \"{}\"\n\
+             cgi_executors: {{ sh: sh }}\nrouting_table: {{}}\nbasic_auth: {{}}\n",
+            address,
+            root.to_str().unwrap(),
+            concat!(env!("CARGO_MANIFEST_DIR"), "/resources/templates"),
+        );
+        common::write_file(root, "config.yaml", &config);
+
+        let child = Command::new(env!("CARGO_BIN_EXE_lucent"))
+            .arg(root.join("config.yaml"))
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        for _ in 0..100 {
+            if std::net::TcpStream::connect(address).is_ok() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        ServerProcess { child, address }
+    }
+
+    fn stop(mut self) -> String {
+        self.child.kill().unwrap();
+        let mut errors = String::new();
+        self.child.stderr.take().unwrap().read_to_string(&mut errors).unwrap();
+        self.child.wait().unwrap();
+        errors
+    }
+}
+
+fn post(target: &str, body: &[u8]) -> Vec<u8> {
+    let head = format!(
+        "POST {} HTTP/1.0\r\nContent-Length: {}\r\n\r\n",
+        target,
+        body.len(),
+    );
+    [head.as_bytes(), body].concat()
+}
+
+fn script_root() -> PathBuf {
+    let root = common::temp_root("cgi-stdin");
+    common::write_file(&root, "echo_cgi.sh", ECHO_SCRIPT);
+    common::write_file(&root, "ignore_cgi.sh", IGNORE_SCRIPT);
+    root
+}
+
+#[async_std::test]
+async fn request_body_is_written_to_stdin() {
+    let root = script_root();
+    let server = ServerProcess::start(&root);
+    let body = (0..100_000).map(|i| b'a' + (i % 26) as u8).collect::<Vec<_>>();
+    let response = common::exchange(server.address, &post("/echo_cgi.sh", &body)).await;
+    let errors = server.stop();
+    common::remove_root(&root);
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.split_once("\r\n\r\n").unwrap().1.as_bytes() == &body[..]);
+    assert!(!errors.contains("WARN"), "{}", errors);
+}
+
+#[async_std::test]
+async fn scripts_may_exit_without_reading_stdin() {
+    let root = script_root();
+    let server = ServerProcess::start(&root);
+    let body = vec![b'x'; 1 << 20];
+    let response = common::exchange(server.address, &post("/ignore_cgi.sh", &body)).await;
+    let echoed = common::exchange(server.address, &post("/echo_cgi.sh", b"still running")).await;
+    let errors = server.stop();
+    common::remove_root(&root);
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("\r\n\r\nignored"), "{}", response);
+    assert!(echoed.ends_with("\r\n\r\nstill running"), "{}", echoed);
+    assert!(!errors.contains("WARN"), "{}", errors);
+}
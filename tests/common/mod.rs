@@ -0,0 +1,57 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_std::io::prelude::{ReadExt, WriteExt};
+use async_std::net::TcpStream;
+use async_std::sync::Arc;
+use async_std::task;
+
+use lucent::server::config::builder::ConfigBuilder;
+use lucent::server::file_server::FileServer;
+
+static NEXT_ROOT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn temp_root(name: &str) -> PathBuf {
+    let index = NEXT_ROOT.fetch_add(1, Ordering::SeqCst);
+    let root = std::env::temp_dir().join(format!("lucent-{}-{}-{}", name, std::process::id(), index));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+pub fn remove_root(root: &Path) {
+    let _ = fs::remove_dir_all(root);
+}
+
+pub fn write_file(root: &Path, name: &str, contents: &str) {
+    fs::write(root.join(name), contents).unwrap();
+}
+
+pub fn builder(root: &Path) -> ConfigBuilder {
+    ConfigBuilder::new()
+        .with_file_root(root.to_str().unwrap())
+        .with_template_root(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/templates"))
+        .with_address("127.0.0.1:0")
+}
+
+pub async fn start(builder: ConfigBuilder) -> (Arc<FileServer>, SocketAddr) {
+    let server = Arc::new(FileServer::new(builder.build().unwrap()).await.unwrap());
+    let address = server.local_addrs()[0];
+    task::spawn({
+        let server = Arc::clone(&server);
+        async move { server.run().await }
+    });
+    (server, address)
+}
+
+pub async fn exchange(address: SocketAddr, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(request).await.unwrap();
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await.unwrap();
+    String::from_utf8_lossy(&response).to_string()
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use async_std::io::prelude::{BufReadExt, ReadExt, WriteExt};
+use async_std::io::BufReader;
+use async_std::net::TcpStream;
+use md5::{Digest, Md5};
+
+use lucent::util;
+
+const ECHO_SCRIPT: &str = "printf 'Content-Type: text/plain\\r\\n\\r\\n'\nhead -c \"$CONTENT_LENGTH\"\n";
+
+fn md5_hex(data: &str) -> String {
+    util::to_hex(&Md5::digest(data.as_bytes()))
+}
+
+fn authorization(nonce: &str, method: &str, uri: &str) -> String {
+    let ha1 = md5_hex("user:Private:secret");
+    let ha2 = md5_hex(&format!("{}:{}", method, uri));
+    let response = md5_hex(&format!("{}:{}:00000001:cnonce:auth:{}", ha1, nonce, ha2));
+    format!(
+        "Authorization: Digest username=\"user\", realm=\"Private\", nonce=\"{}\", uri=\"{}\", qop=auth, \
+         nc=00000001, cnonce=\"cnonce\", response=\"{}\"\r\n",
+        nonce, uri, response,
+    )
+}
+
+#[async_std::test]
+async fn digest_credentials_are_accepted_with_expect_continue() {
+    let root = common::temp_root("digest-auth");
+    common::write_file(&root, "echo_cgi.sh", ECHO_SCRIPT);
+    let credentials = format!("user:{}", md5_hex("user:Private:secret"));
+    let builder = common::builder(&root)
+        .with_cgi_executor("sh", "sh")
+        .with_entry("digest_auth", "/", vec!["Private", "MD5", &credentials]);
+    let (server, address) = common::start(builder).await;
+
+    let challenge = b"GET /echo_cgi.sh HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    let challenge = common::exchange(address, challenge).await;
+    let nonce = challenge.split("nonce=\"").nth(1).and_then(|rest| rest.split('"').next()).unwrap();
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let head = format!(
+        "POST /echo_cgi.sh HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: 11\r\nExpect: 100-continue\r\n\
+         Connection: close\r\n\r\n",
+        authorization(nonce, "POST", "/echo_cgi.sh"),
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    let mut reader = BufReader::new(&stream);
+    let mut interim = String::new();
+    while !interim.ends_with("\r\n\r\n") {
+        assert_ne!(reader.read_line(&mut interim).await.unwrap(), 0, "{}", interim);
+    }
+    (&stream).write_all(b"posted body").await.unwrap();
+    let mut response = String::new();
+    reader.read_to_string(&mut response).await.unwrap();
+
+    server.shutdown().await;
+    common::remove_root(&root);
+
+    assert!(interim.starts_with("HTTP/1.1 100"), "{}", interim);
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains("posted body"), "{}", response);
+}
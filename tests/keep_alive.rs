@@ -0,0 +1,55 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use async_std::io::{self, prelude::{ReadExt, WriteExt}};
+use async_std::net::TcpStream;
+
+const REQUEST: &[u8] = b"GET /file.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+const CLOSING_REQUEST: &[u8] = b"GET /file.txt HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+async fn read_response(stream: &mut TcpStream) -> String {
+    let mut response = vec![];
+    while !response.ends_with(b"contents") {
+        let mut buf = [0; 256];
+        let read = io::timeout(Duration::from_secs(5), stream.read(&mut buf)).await.unwrap();
+        assert_ne!(read, 0, "{}", String::from_utf8_lossy(&response));
+        response.extend_from_slice(&buf[..read]);
+    }
+    String::from_utf8(response).unwrap()
+}
+
+async fn keep_alive(address: SocketAddr) -> (TcpStream, String) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(REQUEST).await.unwrap();
+    let response = read_response(&mut stream).await;
+    (stream, response)
+}
+
+#[async_std::test]
+async fn idle_keep_alive_connections_do_not_hold_a_worker() {
+    let root = common::temp_root("keep-alive");
+    common::write_file(&root, "file.txt", "contents");
+    let builder = common::builder(&root)
+        .with_entry("workers", "count", 1)
+        .with_entry("workers", "queue_size", 1)
+        .with_entry("timeouts", "read", 30);
+    let (server, address) = common::start(builder).await;
+
+    let (mut first, first_response) = keep_alive(address).await;
+    let (mut second, second_response) = keep_alive(address).await;
+    let mut closing = TcpStream::connect(address).await.unwrap();
+    closing.write_all(CLOSING_REQUEST).await.unwrap();
+    let closing_response = read_response(&mut closing).await;
+    first.write_all(REQUEST).await.unwrap();
+    let reused_response = read_response(&mut first).await;
+    second.write_all(CLOSING_REQUEST).await.unwrap();
+    let closed_response = read_response(&mut second).await;
+
+    server.shutdown().await;
+    common::remove_root(&root);
+    for response in &[first_response, second_response, closing_response, reused_response, closed_response] {
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    }
+}
@@ -0,0 +1,59 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use std::{env, fs, process, thread};
+
+fn write_file(root: &Path, name: &str, contents: &str) {
+    fs::write(root.join(name), contents).unwrap();
+}
+
+#[test]
+fn pipelined_requests_are_answered_in_order() {
+    let root = env::temp_dir().join(format!("lucent-pipelining-{}", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    write_file(&root, "first.txt", "first body");
+    write_file(&root, "second.txt", "second body");
+    write_file(&root, "echo_cgi.sh", "printf 'Content-Type: text/plain\\r\\n\\r\\n'\nhead -c \"$CONTENT_LENGTH\"\n");
+
+    let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let config = format!(
+        "address: \"{}\"\nfile_root: \"{}\"\ntemplate_root: \"{}\"\n\
+         cgi_executors: {{ sh: sh }}\nrouting_table: {{}}\nbasic_auth: {{}}\n",
+        address,
+        root.to_str().unwrap(),
+        concat!(env!("CARGO_MANIFEST_DIR"), "/resources/templates"),
+    );
+    write_file(&root, "config.yaml", &config);
+    let mut server = Command::new(env!("CARGO_BIN_EXE_lucent"))
+        .arg(root.join("config.yaml"))
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut stream = (0..100)
+        .find_map(|_| TcpStream::connect(address).map_err(|_| thread::sleep(Duration::from_millis(20))).ok())
+        .expect("server did not start");
+    let requests = concat!(
+        "GET /first.txt HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        "POST /echo_cgi.sh HTTP/1.1\r\nHost: localhost\r\nContent-Length: 11\r\n\r\nposted body",
+        "GET /second.txt HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    stream.write_all(requests.as_bytes()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let mut response = String::new();
+    let read = stream.read_to_string(&mut response);
+
+    server.kill().unwrap();
+    server.wait().unwrap();
+    let _ = fs::remove_dir_all(&root);
+
+    read.unwrap();
+    assert_eq!(response.matches("HTTP/1.1 200").count(), 3, "{}", response);
+    let first = response.find("first body").expect("missing first response");
+    let posted = response.find("posted body").expect("missing CGI response");
+    let second = response.find("second body").expect("missing second response");
+    assert!(first < posted && posted < second, "{}", response);
+}
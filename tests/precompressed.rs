@@ -0,0 +1,38 @@
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    let head = &response[..response.find("\r\n\r\n").unwrap()];
+    head.lines().skip(1).find_map(|line| {
+        let (field, value) = line.split_once(':')?;
+        Some(value.trim()).filter(|_| field.eq_ignore_ascii_case(name))
+    })
+}
+
+#[async_std::test]
+async fn sidecars_have_their_own_validators() {
+    let root = common::temp_root("precompressed");
+    common::write_file(&root, "page.txt", "identity");
+    common::write_file(&root, "page.txt.gz", "gzipped");
+    let earlier = SystemTime::now() - Duration::from_secs(3600);
+    std::fs::File::options().write(true).open(root.join("page.txt")).unwrap().set_modified(earlier).unwrap();
+
+    let (server, address) = common::start(common::builder(&root).with("precompressed_files", true)).await;
+    let get = |extra: &str| format!("GET /page.txt HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", extra);
+    let identity = common::exchange(address, get("").as_bytes()).await;
+    let gzipped = common::exchange(address, get("Accept-Encoding: gzip\r\n").as_bytes()).await;
+    let gzip_etag = header(&gzipped, "etag").unwrap();
+    let revalidated = format!("Accept-Encoding: gzip\r\nIf-None-Match: {}\r\n", gzip_etag);
+    let revalidated = common::exchange(address, get(&revalidated).as_bytes()).await;
+    let mismatched = common::exchange(address, get(&format!("If-None-Match: {}\r\n", gzip_etag)).as_bytes()).await;
+
+    server.shutdown().await;
+    common::remove_root(&root);
+
+    assert!(gzipped.ends_with("gzipped"), "{}", gzipped);
+    assert_ne!(header(&identity, "etag"), Some(gzip_etag));
+    assert_ne!(header(&identity, "last-modified"), header(&gzipped, "last-modified"));
+    assert!(revalidated.starts_with("HTTP/1.1 304"), "{}", revalidated);
+    assert!(mismatched.starts_with("HTTP/1.1 200") && mismatched.ends_with("identity"), "{}", mismatched);
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use std::net::SocketAddr;
+
+async fn request(address: SocketAddr, method: &str, target: &str, extra: &str) -> String {
+    let request = format!("{} {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", method, target, extra);
+    common::exchange(address, request.as_bytes()).await
+}
+
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    let head = &response[..response.find("\r\n\r\n").unwrap()];
+    head.lines().skip(1).find_map(|line| {
+        let (field, value) = line.split_once(':')?;
+        Some(value.trim()).filter(|_| field.eq_ignore_ascii_case(name))
+    })
+}
+
+#[async_std::test]
+async fn route_headers_are_added_to_every_response() {
+    let root = common::temp_root("response-headers");
+    std::fs::create_dir(root.join("dir")).unwrap();
+    common::write_file(&root, "file.txt", "file");
+
+    let headers = serde_yaml::from_str::<serde_yaml::Value>("{ X-Frame-Options: DENY }").unwrap();
+    let (server, address) = common::start(common::builder(&root).with_entry("response_headers", "/", headers)).await;
+    let found = request(address, "GET", "/file.txt", "").await;
+    let etag = format!("If-None-Match: {}\r\n", header(&found, "etag").unwrap());
+    let not_modified = request(address, "GET", "/file.txt", &etag).await;
+    let missing = request(address, "GET", "/missing.txt", "").await;
+    let redirect = request(address, "GET", "/dir", "").await;
+    let not_allowed = request(address, "DELETE", "/file.txt", "").await;
+
+    server.shutdown().await;
+    common::remove_root(&root);
+
+    for (status, response) in &[
+        ("200", &found), ("304", &not_modified), ("404", &missing), ("30", &redirect), ("405", &not_allowed),
+    ] {
+        assert!(response.starts_with(&format!("HTTP/1.1 {}", status)), "{}", response);
+        assert_eq!(header(response, "x-frame-options"), Some("DENY"), "{}", response);
+    }
+}
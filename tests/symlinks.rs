@@ -0,0 +1,63 @@
+mod common;
+
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+
+use lucent::server::config::builder::ConfigBuilder;
+
+async fn status_of(builder: ConfigBuilder, targets: &[&str]) -> Vec<String> {
+    let (server, address) = common::start(builder).await;
+    let mut statuses = vec![];
+    for target in targets {
+        let request = format!("GET /{} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", target);
+        let response = common::exchange(address, request.as_bytes()).await;
+        statuses.push(response.lines().next().unwrap_or_default().to_string());
+    }
+    server.shutdown().await;
+    statuses
+}
+
+fn link_root() -> (PathBuf, PathBuf) {
+    let root = common::temp_root("symlinks-root");
+    let outside = common::temp_root("symlinks-outside");
+    common::write_file(&root, "inside.txt", "inside");
+    common::write_file(&outside, "secret.txt", "secret");
+    symlink(root.join("inside.txt"), root.join("inside_link.txt")).unwrap();
+    symlink(outside.join("secret.txt"), root.join("escaping_link.txt")).unwrap();
+    (root, outside)
+}
+
+async fn statuses_for(policy: Option<&str>) -> Vec<String> {
+    let (root, outside) = link_root();
+    let builder = match policy {
+        Some(policy) => common::builder(&root).with("symlinks", policy),
+        _ => common::builder(&root),
+    };
+    let statuses = status_of(builder, &["inside.txt", "inside_link.txt", "escaping_link.txt"]).await;
+    common::remove_root(&root);
+    common::remove_root(&outside);
+    statuses
+}
+
+const OK: &str = "HTTP/1.1 200";
+const NOT_FOUND: &str = "HTTP/1.1 404";
+
+#[async_std::test]
+async fn follow_serves_every_link() {
+    assert_eq!(statuses_for(Some("follow")).await, [OK, OK, OK]);
+}
+
+#[async_std::test]
+async fn within_root_refuses_escaping_links() {
+    assert_eq!(statuses_for(Some("within-root")).await, [OK, OK, NOT_FOUND]);
+}
+
+#[async_std::test]
+async fn never_refuses_every_link() {
+    assert_eq!(statuses_for(Some("never")).await, [OK, NOT_FOUND, NOT_FOUND]);
+}
+
+#[async_std::test]
+async fn default_policy_stays_within_root() {
+    assert_eq!(statuses_for(None).await, [OK, OK, NOT_FOUND]);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use async_std::sync::Arc;
+use async_std::task;
+use futures::future::{BoxFuture, FutureExt};
+
+use lucent::consts;
+use lucent::http::message::{Body, MessageBuilder};
+use lucent::http::response::Response;
+use lucent::server::file_server::FileServer;
+use lucent::server::middleware::{MiddlewareContext, MiddlewareOutput, MiddlewareResult, MiddlewareStage};
+use lucent::server::middleware::Middleware;
+
+struct TrailerResponder;
+
+impl Middleware for TrailerResponder {
+    fn handle<'a>(&'a self, context: &'a mut MiddlewareContext<'_>) -> BoxFuture<'a, MiddlewareResult<()>> {
+        async move {
+            let length = if context.target.ends_with("/chunked") { consts::MAX_BODY_BEFORE_CHUNK + 1 } else { 4 };
+            let response = MessageBuilder::<Response>::new()
+                .with_body(Body::Bytes(vec![b'a'; length]), consts::H_MEDIA_TEXT)
+                .with_trailer("X-Checksum", "abc123")
+                .build();
+            Err(MiddlewareOutput::Response(Box::new(response), false))
+        }.boxed()
+    }
+}
+
+async fn respond(target: &str, version: &str) -> String {
+    let root = common::temp_root("trailers");
+    let server = Arc::new(FileServer::new(common::builder(&root).build().unwrap()).await.unwrap());
+    server.add_middleware(MiddlewareStage::Request, TrailerResponder);
+    let address = server.local_addrs()[0];
+    task::spawn({
+        let server = Arc::clone(&server);
+        async move { server.run().await }
+    });
+
+    let request = format!("GET {} {}\r\nHost: localhost\r\nConnection: close\r\n\r\n", target, version);
+    let response = common::exchange(address, request.as_bytes()).await;
+    server.shutdown().await;
+    common::remove_root(&root);
+    response
+}
+
+fn head(response: &str) -> &str {
+    &response[..response.find("\r\n\r\n").unwrap()]
+}
+
+#[async_std::test]
+async fn trailers_are_sent_with_chunked_bodies() {
+    let response = respond("/chunked", "HTTP/1.1").await;
+    assert!(head(&response).contains("\r\nTransfer-Encoding: chunked"), "{}", response);
+    assert!(head(&response).contains("\r\nTrailer: X-Checksum"), "{}", response);
+    assert!(response.ends_with("\r\n0\r\nX-Checksum: abc123\r\n\r\n"), "{}", response);
+}
+
+#[async_std::test]
+async fn trailers_are_dropped_without_chunking() {
+    let response = respond("/small", "HTTP/1.1").await;
+    assert!(head(&response).contains("\r\nContent-Length: 4"), "{}", response);
+    assert!(!response.contains("Trailer"), "{}", response);
+    assert!(!response.contains("X-Checksum"), "{}", response);
+    assert!(response.ends_with("\r\n\r\naaaa"), "{}", response);
+}
+
+#[async_std::test]
+async fn trailers_are_dropped_for_http_1_0() {
+    let response = respond("/chunked", "HTTP/1.0").await;
+    assert!(!head(&response).contains("Transfer-Encoding"), "{}", response);
+    assert!(!response.contains("Trailer"), "{}", response);
+    assert!(!response.contains("X-Checksum"), "{}", response);
+    assert!(response.ends_with(&"a".repeat(consts::MAX_BODY_BEFORE_CHUNK + 1)), "{}", head(&response));
+}
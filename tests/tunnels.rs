@@ -0,0 +1,103 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use async_std::io::{self, prelude::{ReadExt, WriteExt}};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task;
+use futures::StreamExt;
+
+use lucent::server::config::builder::ConfigBuilder;
+
+async fn echo_backend() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    task::spawn(async move {
+        while let Some(Ok(stream)) = listener.incoming().next().await {
+            task::spawn(async move {
+                let _ = io::copy(&mut &stream, &mut &stream).await;
+            });
+        }
+    });
+    address
+}
+
+fn tunnel_builder(root: &std::path::Path) -> ConfigBuilder {
+    common::builder(root)
+        .with_entry("workers", "count", 1)
+        .with_entry("connect_proxy", "enabled", true)
+        .with_entry("connect_proxy", "allowed", vec!["127.0.0.1:*"])
+}
+
+async fn open_tunnel(address: SocketAddr, backend: SocketAddr) -> TcpStream {
+    let mut tunnel = TcpStream::connect(address).await.unwrap();
+    let request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\nearly", backend);
+    tunnel.write_all(request.as_bytes()).await.unwrap();
+
+    let mut head = vec![];
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        tunnel.read_exact(&mut byte).await.unwrap();
+        head.push(byte[0]);
+    }
+    assert!(head.starts_with(b"HTTP/1.1 200"), "{}", String::from_utf8_lossy(&head));
+    assert_eq!(read_echo(&mut tunnel, 5).await, "early");
+    tunnel
+}
+
+async fn read_echo(tunnel: &mut TcpStream, length: usize) -> String {
+    let mut echoed = vec![0; length];
+    io::timeout(Duration::from_secs(5), tunnel.read_exact(&mut echoed)).await.unwrap();
+    String::from_utf8(echoed).unwrap()
+}
+
+async fn get(address: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(b"GET /file.txt HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+
+    let mut response = vec![];
+    let read = io::timeout(Duration::from_secs(5), stream.read_to_end(&mut response)).await;
+    assert!(read.is_ok() || !response.is_empty(), "{:?}", read);
+    String::from_utf8_lossy(&response).to_string()
+}
+
+#[async_std::test]
+async fn tunnels_do_not_hold_a_worker() {
+    let root = common::temp_root("tunnels");
+    common::write_file(&root, "file.txt", "contents");
+    let backend = echo_backend().await;
+    let (server, address) = common::start(tunnel_builder(&root)).await;
+
+    let mut tunnel = open_tunnel(address, backend).await;
+    let response = get(address).await;
+    tunnel.write_all(b"after").await.unwrap();
+    let echoed = read_echo(&mut tunnel, 5).await;
+
+    server.shutdown().await;
+    common::remove_root(&root);
+    assert!(response.starts_with("HTTP/1.1 200") && response.ends_with("contents"), "{}", response);
+    assert_eq!(echoed, "after");
+}
+
+#[async_std::test]
+async fn tunnels_count_toward_the_connection_limit() {
+    let root = common::temp_root("tunnels-limit");
+    common::write_file(&root, "file.txt", "contents");
+    let backend = echo_backend().await;
+    let builder = tunnel_builder(&root)
+        .with_entry("connection_limit", "max", 1)
+        .with_entry("connection_limit", "on_limit", "reject");
+    let (server, address) = common::start(builder).await;
+
+    let tunnel = open_tunnel(address, backend).await;
+    let refused = get(address).await;
+    drop(tunnel);
+    task::sleep(Duration::from_millis(200)).await;
+    let served = get(address).await;
+
+    server.shutdown().await;
+    common::remove_root(&root);
+    assert!(refused.starts_with("HTTP/1.1 503"), "{}", refused);
+    assert!(served.starts_with("HTTP/1.1 200"), "{}", served);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::path::PathBuf;
+
+use lucent::server::config::builder::ConfigBuilder;
+
+const HOSTS: &[(&str, &str)] = &[
+    ("Example.COM.", "exact"),
+    ("*.example.com", "wildcard"),
+    ("a.example.com", "exact-sub"),
+    ("[::1]", "ipv6"),
+];
+
+const NOT_FOUND: &str = "HTTP/1.1 404";
+
+fn host_root(name: &str) -> PathBuf {
+    let root = common::temp_root(&format!("vhost-{}", name));
+    common::write_file(&root, "who.txt", name);
+    root
+}
+
+async fn served_by(hosts: &[&str]) -> Vec<String> {
+    served_with_default(None, hosts).await
+}
+
+async fn served_with_default(default_host: Option<&str>, hosts: &[&str]) -> Vec<String> {
+    let base = host_root("base");
+    let mut roots = vec![base.clone()];
+    let mut builder = common::builder(&base);
+    if let Some(default_host) = default_host {
+        builder = builder.with("default_host", default_host);
+    }
+    for (name, label) in HOSTS {
+        let root = host_root(label);
+        builder = builder.with_virtual_host(name, ConfigBuilder::new().with_file_root(root.to_str().unwrap()));
+        roots.push(root);
+    }
+
+    let (server, address) = common::start(builder).await;
+    let mut served = vec![];
+    for host in hosts {
+        let request = format!("GET /who.txt HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", host);
+        let response = common::exchange(address, request.as_bytes()).await;
+        match response.strip_prefix("HTTP/1.1 200") {
+            Some(_) => served.push(response.split_once("\r\n\r\n").unwrap_or_default().1.to_string()),
+            _ => served.push(response.lines().next().unwrap_or_default().to_string()),
+        }
+    }
+
+    server.shutdown().await;
+    roots.iter().for_each(|root| common::remove_root(root));
+    served
+}
+
+#[async_std::test]
+async fn host_names_are_normalized() {
+    let served = served_by(&["example.com", "EXAMPLE.com", "example.com.", "example.com.:8080", "example.com:80"]);
+    assert_eq!(served.await, ["exact"; 5]);
+}
+
+#[async_std::test]
+async fn bracketed_ipv6_hosts_match_with_and_without_port() {
+    assert_eq!(served_by(&["[::1]", "[::1]:8080", "[::2]"]).await, ["ipv6", "ipv6", NOT_FOUND]);
+}
+
+#[async_std::test]
+async fn wildcards_match_exactly_one_label() {
+    let served = served_by(&["b.example.com", "B.Example.com.", "x.b.example.com", "example.com", "badexample.com"]);
+    assert_eq!(served.await, ["wildcard", "wildcard", NOT_FOUND, "exact", NOT_FOUND]);
+}
+
+#[async_std::test]
+async fn exact_names_take_precedence_over_wildcards() {
+    let served = served_by(&["a.example.com", "a.example.com.", "aa.example.com"]);
+    assert_eq!(served.await, ["exact-sub", "exact-sub", "wildcard"]);
+}
+
+#[async_std::test]
+async fn unknown_hosts_are_not_found_without_a_default() {
+    assert_eq!(served_by(&["example.org", "localhost", "127.0.0.1:8080"]).await, [NOT_FOUND; 3]);
+}
+
+#[async_std::test]
+async fn unknown_hosts_use_the_default_host() {
+    let served = served_with_default(Some("A.Example.com."), &["example.org", "x.b.example.com", "example.com"]);
+    assert_eq!(served.await, ["exact-sub", "exact-sub", "exact"]);
+}